@@ -0,0 +1,122 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nih_plug::prelude::NoteEvent;
+use scale_colorizr::ScaleColorizr;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const NUM_SAMPLES: usize = 48_000;
+
+/// A second of white-ish noise, so the filters have something to chew on.
+fn input() -> [Vec<f32>; 2] {
+    let mut state = 0x1234_5678u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state as f32 / u32::MAX as f32).mul_add(2.0, -1.0)
+    };
+
+    let left = (0..NUM_SAMPLES).map(|_| next()).collect();
+    let right = (0..NUM_SAMPLES).map(|_| next()).collect();
+    [left, right]
+}
+
+fn note_on(timing: u32, note: u8) -> NoteEvent<()> {
+    NoteEvent::NoteOn {
+        timing,
+        voice_id: None,
+        channel: 0,
+        note,
+        velocity: 1.0,
+    }
+}
+
+fn note_off(timing: u32, note: u8) -> NoteEvent<()> {
+    NoteEvent::NoteOff {
+        timing,
+        voice_id: None,
+        channel: 0,
+        note,
+        velocity: 0.0,
+    }
+}
+
+/// Holds `num_notes` notes for the whole render.
+fn held_notes(num_notes: u8) -> Vec<NoteEvent<()>> {
+    (0..num_notes).map(|idx| note_on(0, 24 + idx)).collect()
+}
+
+fn filter_loop(c: &mut Criterion) {
+    let [left, right] = input();
+    let mut group = c.benchmark_group("filter loop");
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+
+    // The default voice limit is 16, so this never steals
+    for num_notes in [1, 4, 8, 16] {
+        let events = held_notes(num_notes);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_notes),
+            &events,
+            |b, events| {
+                let mut plugin = ScaleColorizr::default();
+                b.iter(|| plugin.render_offline(SAMPLE_RATE, [&left, &right], black_box(events)));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn voice_stealing(c: &mut Criterion) {
+    let [left, right] = input();
+    let mut group = c.benchmark_group("voice stealing");
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+
+    // A new note every `interval` samples that never gets released, so once the voices fill up
+    // every note steals one
+    for interval in [64, 256, 1024] {
+        let events: Vec<_> = (0..NUM_SAMPLES / interval)
+            .map(|idx| note_on((idx * interval) as u32, 24 + (idx % 96) as u8))
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(interval),
+            &events,
+            |b, events| {
+                let mut plugin = ScaleColorizr::default();
+                b.iter(|| plugin.render_offline(SAMPLE_RATE, [&left, &right], black_box(events)));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn block_splitting(c: &mut Criterion) {
+    let [left, right] = input();
+    let mut group = c.benchmark_group("block splitting");
+    group.throughput(Throughput::Elements(NUM_SAMPLES as u64));
+
+    // Short notes every `interval` samples, so blocks get cut short well below the maximum block
+    // size
+    for interval in [3, 17, 63, 512] {
+        let events: Vec<_> = (0..NUM_SAMPLES / interval)
+            .flat_map(|idx| {
+                let timing = (idx * interval) as u32;
+                let note = 48 + (idx % 12) as u8;
+                [note_on(timing, note), note_off(timing + 1, note)]
+            })
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(interval),
+            &events,
+            |b, events| {
+                let mut plugin = ScaleColorizr::default();
+                b.iter(|| plugin.render_offline(SAMPLE_RATE, [&left, &right], black_box(events)));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, filter_loop, voice_stealing, block_splitting);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+//! Renders a WAV file through Scale Colorizr with the notes from a MIDI file, without a host.
+//!
+//! Usage: `scale_colorizr_render <input.wav> <notes.mid> <output.wav>`
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use nih_plug::prelude::NoteEvent;
+use scale_colorizr::ScaleColorizr;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, input_path, midi_path, output_path] = args.as_slice() else {
+        eprintln!("usage: scale_colorizr_render <input.wav> <notes.mid> <output.wav>");
+        std::process::exit(1);
+    };
+
+    let mut reader = hound::WavReader::open(input_path)?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    // Mono files go to both channels, anything past the second channel gets dropped
+    let num_channels = usize::from(spec.channels);
+    let left: Vec<f32> = samples
+        .chunks_exact(num_channels)
+        .map(|frame| frame[0])
+        .collect();
+    let right: Vec<f32> = samples
+        .chunks_exact(num_channels)
+        .map(|frame| frame[num_channels.min(2) - 1])
+        .collect();
+
+    let events = read_midi(midi_path, spec.sample_rate)?;
+
+    let mut plugin = ScaleColorizr::default();
+    let [left, right] = plugin.render_offline(spec.sample_rate as f32, [&left, &right], &events);
+
+    let mut writer = hound::WavWriter::create(
+        output_path,
+        hound::WavSpec {
+            channels: 2,
+            sample_rate: spec.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        },
+    )?;
+    for (left, right) in left.iter().zip(&right) {
+        writer.write_sample(*left)?;
+        writer.write_sample(*right)?;
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Reads every note on and off in a standard MIDI file, with the timings converted to samples.
+fn read_midi(path: &str, sample_rate: u32) -> Result<Vec<NoteEvent<()>>, Box<dyn Error>> {
+    let bytes = std::fs::read(path)?;
+    let smf = Smf::parse(&bytes)?;
+    let Timing::Metrical(ticks_per_beat) = smf.header.timing else {
+        return Err("only metrical MIDI timing is supported".into());
+    };
+    let ticks_per_beat = f64::from(ticks_per_beat.as_int());
+
+    // Merge all the tracks into one list. The sort is stable, so events on the same tick keep their
+    // order within a track
+    let mut track_events: Vec<(u64, TrackEventKind)> = smf
+        .tracks
+        .iter()
+        .flat_map(|track| {
+            track.iter().scan(0u64, |tick, event| {
+                *tick += u64::from(event.delta.as_int());
+                Some((*tick, event.kind))
+            })
+        })
+        .collect();
+    track_events.sort_by_key(|(tick, _)| *tick);
+
+    let mut events = Vec::new();
+    // 120 BPM until told otherwise
+    let mut microseconds_per_beat = 500_000.0;
+    let mut last_tick = 0;
+    let mut seconds = 0.0;
+    for (tick, kind) in track_events {
+        seconds += (tick - last_tick) as f64 / ticks_per_beat * microseconds_per_beat / 1_000_000.0;
+        last_tick = tick;
+        let timing = (seconds * f64::from(sample_rate)).round() as u32;
+
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                microseconds_per_beat = f64::from(tempo.as_int());
+            }
+            TrackEventKind::Midi { channel, message } => match message {
+                MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                    events.push(NoteEvent::NoteOn {
+                        timing,
+                        voice_id: None,
+                        channel: channel.as_int(),
+                        note: key.as_int(),
+                        velocity: f32::from(vel.as_int()) / 127.0,
+                    });
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    events.push(NoteEvent::NoteOff {
+                        timing,
+                        voice_id: None,
+                        channel: channel.as_int(),
+                        note: key.as_int(),
+                        velocity: 0.0,
+                    });
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
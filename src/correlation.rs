@@ -0,0 +1,43 @@
+//! How alike the output's two channels are. This goes from 1 when they're the same, through 0 when
+//! they have nothing to do with each other, to -1 when one is the other upside down. Anything much
+//! below 0 loses a lot when it gets summed to mono.
+
+use nih_plug::prelude::*;
+
+/// How far back the meter looks. This is about as slow as a VU meter, so it can be read.
+const INTEGRATION_MS: f32 = 300.0;
+
+#[derive(Clone, Copy, Default)]
+pub struct CorrelationMeter {
+    product: f32,
+    left_power: f32,
+    right_power: f32,
+}
+
+impl CorrelationMeter {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Take in a buffer, returning the correlation as of its end. Silence counts as perfectly
+    /// correlated, since there's nothing there to lose.
+    pub fn process(&mut self, buffer: &Buffer, sample_rate: f32) -> f32 {
+        let [left, right] = buffer.as_slice_immutable() else {
+            return 1.0;
+        };
+
+        let coefficient = (-1000.0 / (INTEGRATION_MS * sample_rate)).exp();
+        for (&left, &right) in left.iter().zip(right.iter()) {
+            self.product = coefficient.mul_add(self.product - left * right, left * right);
+            self.left_power = coefficient.mul_add(self.left_power - left * left, left * left);
+            self.right_power = coefficient.mul_add(self.right_power - right * right, right * right);
+        }
+
+        let power = (self.left_power * self.right_power).sqrt();
+        if power < util::MINUS_INFINITY_GAIN * util::MINUS_INFINITY_GAIN {
+            return 1.0;
+        }
+
+        (self.product / power).clamp(-1.0, 1.0)
+    }
+}
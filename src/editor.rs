@@ -2,66 +2,212 @@
 #![allow(clippy::cast_possible_truncation)]
 
 use crate::editor::utils::PowersOfTen;
-use crate::spectrum::SpectrumOutput;
-use crate::{FilterDisplay, FrequencyDisplay, ScaleColorizrParams, VERSION};
+use crate::midi_recorder::MidiRecorder;
+use crate::scale::{parse_note_name, ScaleParams};
+use crate::scope::{Scope, ScopeOutput, SCOPE_LEN};
+use crate::spectrum::{
+    SpectrumOutput, SpectrumSettings, SpectrumWindow, SPECTRUM_BACKGROUND_INTERVALS,
+    SPECTRUM_OVERLAPS, SPECTRUM_WINDOW_SIZES,
+};
+use crate::test_tone::TestToneSettings;
+use crate::tuning::{Tuning, NUM_TUNING_SLOTS};
+use crate::{
+    BackgroundTask, EnvelopeMode, EnvelopeParams, EnvelopeStage, FalloffParams, FilterDescriptor,
+    FilterDisplay, FilterDisplayOutput, FilterMode, ScaleColorizr, ScaleColorizrParams,
+    NUM_FILTERS, VERSION,
+};
 use colorgrad::{CatmullRomGradient, Color, Gradient};
 use cozy_ui::centered;
 use cozy_ui::colors::HIGHLIGHT_COL32;
+use cozy_ui::util::get_set::Operation;
 use cozy_ui::widgets::button::toggle;
 use cozy_ui::widgets::Knob;
-use cozy_util::filter::svf::GenericSVF;
 use crossbeam::atomic::AtomicCell;
 use directories::ProjectDirs;
 use libsw::Sw;
-use nih_plug::context::gui::ParamSetter;
+use nih_plug::context::gui::{AsyncExecutor, ParamSetter};
 use nih_plug::midi::NoteEvent;
 use nih_plug::params::enums::Enum;
 use nih_plug::params::smoothing::AtomicF32;
-use nih_plug::params::{EnumParam, Param};
+use nih_plug::params::{EnumParam, IntParam, Param};
 use nih_plug::prelude::Editor;
 use nih_plug_egui::egui::epaint::{PathShape, PathStroke};
 use nih_plug_egui::egui::{
     include_image, pos2, remap, remap_clamp, vec2, Align2, Color32, DragValue, FontData,
-    FontDefinitions, FontId, Frame, Grid, Layout, Margin, Mesh, Pos2, Rect, RichText, Rounding,
-    Sense, Shadow, Stroke, Ui, WidgetText, Window,
+    FontDefinitions, FontId, Frame, Grid, Label, Layout, Margin, Mesh, Pos2, Rect, RichText,
+    Rounding, Sense, Shadow, Stroke, Ui, Vec2, WidgetText, Window,
 };
 use nih_plug_egui::{create_egui_editor, egui, EguiState};
 use noise::{NoiseFn, OpenSimplex, Perlin};
 use num_complex::Complex32;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::f32::consts::E;
+use std::collections::{BTreeSet, VecDeque};
+use std::f32::consts::PI;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU32;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 use strum_macros::Display;
 
+pub use self::presets::{Preset, PresetScan};
 use self::utils::{begin_set, end_set, get_set, get_set_normalized};
 
+mod files;
+mod presets;
 mod utils;
 
+/// The graph's range by default. This can be changed in the settings to anywhere between
+/// [`FREQ_RANGE_LOWEST_HZ`] and [`FREQ_RANGE_HIGHEST_HZ`].
 const FREQ_RANGE_START_HZ: f32 = 20.0;
-const FREQ_RANGE_END_HZ: f32 = 15_000.0;
-
-fn knob<P, Text>(ui: &mut Ui, setter: &ParamSetter, param: &P, diameter: f32, description: Text)
-where
-    P: Param,
-    Text: Into<WidgetText>,
-{
-    ui.add(
-        Knob::new(
-            param.name(),
-            diameter,
-            get_set_normalized(param, setter),
-            begin_set(param, setter),
-            end_set(param, setter),
-        )
-        .label(param.name().to_ascii_uppercase())
-        .description(description)
-        .modulated_value(param.modulated_normalized_value())
-        .default_value(param.default_normalized_value()),
+const FREQ_RANGE_END_HZ: f32 = 20_000.0;
+const FREQ_RANGE_LOWEST_HZ: f32 = 10.0;
+const FREQ_RANGE_HIGHEST_HZ: f32 = 24_000.0;
+/// How far in the graph can be zoomed, as a ratio between the frequencies at its edges.
+const GRAPH_MIN_SPAN: f32 = 2.0;
+/// How many frames of the limiter's gain reduction are kept for its history strip.
+const GAIN_REDUCTION_HISTORY_LEN: usize = 240;
+/// The gain reduction that fills the history strip, in dB.
+const GAIN_REDUCTION_RANGE_DB: f32 = 12.0;
+/// How far either side of a click the capture tool looks for a peak, in points.
+const CAPTURE_RADIUS: f32 = 8.0;
+/// How long a toast stays up, in seconds. It fades out over the last second.
+const TOAST_SECS: f32 = 4.0;
+/// How long the editor's size has to stay put before it gets written to the config, so dragging
+/// the corner doesn't write it on every frame.
+const SIZE_SAVE_DELAY_SECS: f32 = 0.5;
+/// How long fades between two states take, in seconds. This is egui's own animation time.
+const FADE_SECS: f32 = 1.0 / 12.0;
+/// The longest a frame can count as taking for animation, so a stall doesn't skip everything
+/// ahead.
+const MAX_FRAME_SECS: f32 = 0.1;
+/// The sizes new editors can be set to open at, in logical pixels.
+const EDITOR_SIZES: [(u32, u32); 4] = [(800, 600), (1000, 750), (1200, 900), (1600, 1200)];
+
+/// A knob with its current value written underneath, so it can be read without the host's UI, and
+/// with any modulation from the host drawn under that.
+fn knob<P: Param>(ui: &mut Ui, setter: &ParamSetter, param: &P, diameter: f32, description: &str) {
+    let response = ui
+        .vertical(|ui| {
+            let response = ui.add(
+                Knob::new(
+                    param.name(),
+                    diameter,
+                    get_set_normalized(param, setter),
+                    begin_set(param, setter),
+                    end_set(param, setter),
+                )
+                .label(param.name().to_ascii_uppercase())
+                .description(description)
+                .modulated_value(param.modulated_normalized_value())
+                .default_value(param.default_normalized_value()),
+            );
+
+            let value = param.normalized_value_to_string(param.modulated_normalized_value(), true);
+            let (value_rect, _) =
+                ui.allocate_exact_size(vec2(response.rect.width(), 12.0), Sense::hover());
+            ui.painter().text(
+                value_rect.center(),
+                Align2::CENTER_CENTER,
+                value,
+                FontId::proportional(10.0),
+                Color32::GRAY,
+            );
+            draw_modulation(ui, value_rect, param);
+
+            response
+        })
+        .inner;
+    help_overlay(ui, response.rect, description);
+}
+
+/// When the host is modulating `param`, draw how far it's been moved along the bottom of `rect`,
+/// from the value the knob is set to up to the value actually in use.
+fn draw_modulation<P: Param>(ui: &Ui, rect: Rect, param: &P) {
+    let unmodulated = param.unmodulated_normalized_value();
+    let modulated = param.modulated_normalized_value();
+    if (modulated - unmodulated).abs() < 1e-4 {
+        return;
+    }
+
+    let y = rect.bottom() - 1.0;
+    let start = pos2(remap(unmodulated, 0.0..=1.0, rect.x_range()), y);
+    let end = pos2(remap(modulated, 0.0..=1.0, rect.x_range()), y);
+    let painter = ui.painter();
+    painter.line_segment([start, end], Stroke::new(2.0, HIGHLIGHT_COL32));
+    painter.circle_filled(end, 2.5, HIGHLIGHT_COL32);
+}
+
+/// Where the help overlay's on/off state lives in egui's memory, so the widget helpers can check
+/// it without the editor state getting passed down to them.
+const HELP_MODE_ID: &str = "help_mode";
+
+/// While the help overlay is on, paint `text` over the bottom of `rect`, on top of everything else.
+fn help_overlay(ui: &Ui, rect: Rect, text: &str) {
+    let help_mode = ui
+        .ctx()
+        .data(|data| data.get_temp(egui::Id::new(HELP_MODE_ID)))
+        .unwrap_or(false);
+    if !help_mode {
+        return;
+    }
+
+    let painter = ui.ctx().layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new(HELP_MODE_ID),
+    ));
+    let galley = painter.layout(
+        text.to_string(),
+        FontId::proportional(10.0),
+        Color32::WHITE,
+        rect.width().max(100.0),
+    );
+    let text_rect = Align2::CENTER_BOTTOM.anchor_size(rect.center_bottom(), galley.size());
+    painter.rect_filled(
+        text_rect.expand(3.0),
+        Rounding::same(3.0),
+        Color32::from_black_alpha(220),
     );
+    painter.galley(text_rect.min, galley, Color32::WHITE);
+}
+
+/// Move `value` towards 1 if `target` is set, or towards 0 otherwise, taking [`FADE_SECS`] to go
+/// all the way. Returns the new value.
+fn fade(value: &mut f32, target: bool, dt: f32) -> f32 {
+    let step = dt / FADE_SECS;
+    *value = if target {
+        (*value + step).min(1.0)
+    } else {
+        (*value - step).max(0.0)
+    };
+    *value
+}
+
+/// Lock one of the buffers shared with the plugin. An editor panicking while holding one can't
+/// leave it half updated, so poisoning is ignored rather than taking every later editor down too.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A drag value for editing a parameter as text, for when a knob is too much.
+#[allow(clippy::cast_possible_truncation)]
+fn drag_value<'a, P: Param>(param: &'a P, setter: &'a ParamSetter) -> DragValue<'a> {
+    DragValue::from_get_set(|value| {
+        value.map_or_else(
+            || f64::from(param.modulated_normalized_value()),
+            |v| {
+                setter.begin_set_parameter(param);
+                setter.set_parameter_normalized(param, v as f32);
+                setter.end_set_parameter(param);
+                v
+            },
+        )
+    })
+    .custom_parser(|s| param.string_to_normalized_value(s).map(f64::from))
+    .speed(0.002)
+    .range(0.0..=1.0)
+    .custom_formatter(|v, _| param.normalized_value_to_string(v as f32, true))
 }
 
 static CONFIG_DIR: Lazy<PathBuf> = Lazy::new(|| {
@@ -70,27 +216,343 @@ static CONFIG_DIR: Lazy<PathBuf> = Lazy::new(|| {
         .expect("no home directory is set")
 });
 static CONFIG_FILE: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("config.toml"));
+static RECORDINGS_DIR: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("recordings"));
 
 struct EditorState {
     show_debug: bool,
     show_about: bool,
+    /// Whether every control gets its description drawn over it.
+    show_help: bool,
     show_settings: bool,
+    show_presets: bool,
+    /// Whether the warning for allowing filters above Nyquist is waiting on an answer.
+    confirm_above_nyquist: bool,
+    settings_tab: SettingsTab,
+    settings_search: String,
+    show_gate: bool,
+    show_detune: bool,
+    show_shift: bool,
+    show_falloff: bool,
+    show_scale: bool,
+    show_noise_gate: bool,
+    show_envelope: bool,
+    show_filter: bool,
+    show_test_tone: bool,
+    show_voices: bool,
+    /// The file path fields for each tuning slot in the scale window.
+    tuning_paths: [String; NUM_TUNING_SLOTS],
     config_io_error: Option<String>,
+    /// A short message at the bottom of the editor, and when it went up.
+    toast: Option<(String, Instant)>,
     options: EditorOptions,
-    pre_spectrum: SpectrumOutput,
-    post_spectrum: SpectrumOutput,
+    gradient: CachedGradient,
+    pre_spectrum: Arc<Mutex<SpectrumOutput>>,
+    post_spectrum: Arc<Mutex<SpectrumOutput>>,
+    scope: Arc<Mutex<ScopeOutput>>,
+    spectrum_settings: Arc<AtomicCell<SpectrumSettings>>,
+    filter_display: Arc<Mutex<FilterDisplayOutput>>,
+    graph_view: GraphView,
+    /// Whether the scope draws the input behind the output.
+    scope_show_dry: bool,
+    /// Whether clicking the spectrum detunes a harmonic onto the peak under the cursor.
+    capture: bool,
+    graph_buffers: GraphBuffers,
+    graph_viewport: GraphViewport,
+    line_noise: LineNoise,
+    /// When the last frame started, and how long it took to get to this one in seconds. Animation
+    /// goes by this rather than the frame number, so it runs at the same speed however often the
+    /// host repaints.
+    last_frame: Option<Instant>,
+    frame_dt: f32,
+    /// A size the editor has been resized to that hasn't been written to the config yet, and when
+    /// it was first seen.
+    unsaved_size: Option<((u32, u32), Instant)>,
+    /// Fades the input spectrum up while the delta output is off.
+    delta_fade: f32,
+    /// The stolen voice count as of the last frame, and when it last went up.
+    voices_stolen: u32,
+    last_steal: Option<Instant>,
+    filter_line_active: f32,
+    filter_line_elapsed: Duration,
+    /// The limiter's gain reduction in dB for the last [`GAIN_REDUCTION_HISTORY_LEN`] frames,
+    /// oldest first.
+    gain_reduction_history: VecDeque<f32>,
+    presets: Arc<Mutex<PresetScan>>,
+    async_executor: AsyncExecutor<ScaleColorizr>,
+    /// The name in the preset window's save field. This is also set to the last loaded preset.
+    preset_name: String,
+    /// Every category presets can lock, found when the editor opens.
+    preset_categories: Vec<String>,
+    program_change: Arc<AtomicCell<Option<usize>>>,
+}
+
+/// Scratch buffers for drawing the graph, kept between frames so the draw path doesn't have to
+/// allocate them every time.
+#[derive(Default)]
+struct GraphBuffers {
+    points: Vec<Pos2>,
+    sampled_frequencies: Vec<f32>,
+    active_filters: Vec<FilterDescriptor>,
+    skipped_frequencies: Vec<f32>,
+}
+
+/// What the central graph is showing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GraphView {
+    Spectrum,
+    Scope,
+}
+
+/// The frequencies at the graph's edges when it's zoomed all the way out.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+struct GraphRange {
+    start_hz: f32,
+    end_hz: f32,
+}
+
+impl Default for GraphRange {
+    fn default() -> Self {
+        Self {
+            start_hz: FREQ_RANGE_START_HZ,
+            end_hz: FREQ_RANGE_END_HZ,
+        }
+    }
+}
+
+/// How the noise on the filter line moves.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+struct LineAnimation {
+    /// How fast the noise drifts, relative to the original speed. 0 holds it still.
+    speed: f32,
+    /// How stretched out the noise is along the line. Bigger is coarser.
+    scale: f32,
+}
+
+impl Default for LineAnimation {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// The part of the frequency axis the graph shows, as the log10 of the frequencies at its edges.
+/// Everything on the graph goes through this to find where a frequency ends up.
+#[derive(Clone, Copy)]
+struct GraphViewport {
+    log_min: f32,
+    log_max: f32,
+    /// How far the view can go in either direction.
+    log_start: f32,
+    log_end: f32,
+}
+
+impl GraphViewport {
+    /// A view of all of `range`.
+    fn new(range: GraphRange) -> Self {
+        let log_start = range.start_hz.log10();
+        let log_end = range.end_hz.log10();
+        Self {
+            log_min: log_start,
+            log_max: log_end,
+            log_start,
+            log_end,
+        }
+    }
+
+    /// Where `freq` is across the graph, from 0 at the left edge to 1 at the right.
+    fn t(self, freq: f32) -> f32 {
+        (freq.log10() - self.log_min) / (self.log_max - self.log_min)
+    }
+
+    fn x(self, rect: Rect, freq: f32) -> f32 {
+        self.t(freq).mul_add(rect.width() - 1.0, rect.left())
+    }
+
+    fn freq(self, rect: Rect, x: f32) -> f32 {
+        let t = (x - rect.left()) / (rect.width() - 1.0);
+        10.0f32.powf(t.mul_add(self.log_max - self.log_min, self.log_min))
+    }
+
+    /// Scale the span by `factor`, keeping the frequency at `anchor` (from 0 to 1 across the
+    /// graph) where it is.
+    fn zoom(&mut self, anchor: f32, factor: f32) {
+        let span = self.log_max - self.log_min;
+        let new_span = (span * factor).clamp(
+            GRAPH_MIN_SPAN.log10().min(self.log_end - self.log_start),
+            self.log_end - self.log_start,
+        );
+        let anchor_log = anchor.mul_add(span, self.log_min);
+        self.log_min = anchor.mul_add(-new_span, anchor_log);
+        self.log_max = self.log_min + new_span;
+        self.pan(0.0);
+    }
+
+    /// Move the view along by `amount` graph widths, stopping at either end of the range.
+    fn pan(&mut self, amount: f32) {
+        let span = self.log_max - self.log_min;
+        self.log_min = amount
+            .mul_add(span, self.log_min)
+            .clamp(self.log_start, self.log_end - span);
+        self.log_max = self.log_min + span;
+    }
+}
+
+/// The noise that animates the filter line, seeded from the plugin's seed.
+struct LineNoise {
+    seed: u64,
+    animate: Perlin,
+    line: OpenSimplex,
+    /// How far the noise has drifted. This adds up the time between frames, so it moves at the
+    /// same speed no matter how often the host repaints, and changing the speed doesn't make it
+    /// jump.
+    position: f64,
+    scale: f32,
+    /// Fades the line's color in and out as voices start and stop.
+    active: f32,
+}
+
+impl LineNoise {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            animate: Perlin::new(seed as u32),
+            line: OpenSimplex::new((seed >> 32) as u32),
+            position: 0.0,
+            scale: 1.0,
+            active: 0.0,
+        }
+    }
+
+    /// Move the noise along by a frame that took `dt` seconds.
+    fn advance(&mut self, animation: LineAnimation, dt: f32, is_active: bool) {
+        // This used to move 0.005 every frame, at what was usually 60 frames a second
+        self.position += 0.3 * f64::from(animation.speed * dt);
+        self.scale = animation.scale;
+        fade(&mut self.active, is_active, dt);
+    }
 }
 
 impl EditorState {
-    fn new(pre_spectrum: SpectrumOutput, post_spectrum: SpectrumOutput) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        pre_spectrum: Arc<Mutex<SpectrumOutput>>,
+        post_spectrum: Arc<Mutex<SpectrumOutput>>,
+        scope: Arc<Mutex<ScopeOutput>>,
+        spectrum_settings: Arc<AtomicCell<SpectrumSettings>>,
+        filter_display: Arc<Mutex<FilterDisplayOutput>>,
+        program_change: Arc<AtomicCell<Option<usize>>>,
+        presets: Arc<Mutex<PresetScan>>,
+        async_executor: AsyncExecutor<ScaleColorizr>,
+    ) -> Self {
         Self {
             show_debug: false,
             show_about: false,
+            show_help: false,
             show_settings: false,
+            show_presets: false,
+            confirm_above_nyquist: false,
+            settings_tab: SettingsTab::default(),
+            settings_search: String::new(),
+            show_gate: false,
+            show_detune: false,
+            show_shift: false,
+            show_falloff: false,
+            show_scale: false,
+            show_noise_gate: false,
+            show_envelope: false,
+            show_filter: false,
+            show_test_tone: false,
+            show_voices: false,
+            tuning_paths: Default::default(),
             config_io_error: None,
+            toast: None,
             options: EditorOptions::default(),
+            gradient: EditorOptions::default().build_gradient(),
             pre_spectrum,
             post_spectrum,
+            scope,
+            spectrum_settings,
+            filter_display,
+            graph_view: GraphView::Spectrum,
+            scope_show_dry: false,
+            capture: false,
+            graph_buffers: GraphBuffers::default(),
+            graph_viewport: GraphViewport::new(GraphRange::default()),
+            line_noise: LineNoise::new(0),
+            last_frame: None,
+            frame_dt: 0.0,
+            delta_fade: 0.0,
+            voices_stolen: 0,
+            last_steal: None,
+            filter_line_active: 0.0,
+            filter_line_elapsed: Duration::ZERO,
+            unsaved_size: None,
+            gain_reduction_history: VecDeque::with_capacity(GAIN_REDUCTION_HISTORY_LEN),
+            presets,
+            async_executor,
+            preset_name: String::new(),
+            preset_categories: Vec::new(),
+            program_change,
+        }
+    }
+
+    fn load_preset(&mut self, idx: usize, params: &ScaleColorizrParams, setter: &ParamSetter) {
+        let Some(entry) = lock(&self.presets).presets.get(idx).cloned() else {
+            return;
+        };
+
+        match Preset::load(&entry.path) {
+            Ok(preset) => {
+                preset.apply(params, setter, &self.options.locked_categories);
+                self.preset_name.clone_from(&entry.name);
+            }
+            Err(e) => self.config_io_error = Some(e),
+        }
+    }
+
+    /// Start finding the presets again on the background thread. The list fills back in over the
+    /// next few frames.
+    fn rescan_presets(&self) {
+        // Set here as well, so nothing uses the old list in the meantime
+        lock(&self.presets).scanning = true;
+        self.async_executor
+            .execute_background(BackgroundTask::ScanPresets);
+    }
+}
+
+#[derive(Clone, Copy, Default, Display, PartialEq, Eq)]
+enum SettingsTab {
+    #[default]
+    Audio,
+    #[strum(to_string = "MIDI")]
+    Midi,
+    Appearance,
+    Advanced,
+}
+
+impl SettingsTab {
+    const ALL: [Self; 4] = [Self::Audio, Self::Midi, Self::Appearance, Self::Advanced];
+}
+
+/// Which settings get shown. While there's a search, everything that matches it gets shown no
+/// matter which tab it's on.
+struct SettingsFilter<'a> {
+    tab: SettingsTab,
+    search: &'a str,
+}
+
+impl SettingsFilter<'_> {
+    fn shows(&self, tab: SettingsTab, name: &str) -> bool {
+        if self.search.trim().is_empty() {
+            self.tab == tab
+        } else {
+            name.to_lowercase()
+                .contains(&self.search.trim().to_lowercase())
         }
     }
 }
@@ -107,29 +569,146 @@ enum GradientType {
 }
 
 #[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
 struct EditorOptions {
     gradient_type: GradientType,
     gradient_colors: Vec<[u8; 3]>,
+    spectrum: SpectrumSettings,
+    /// Set once the warning for allowing filters above Nyquist has been accepted, so it only ever
+    /// shows up once.
+    nyquist_warning_accepted: bool,
+    /// The size new editors open at. If this isn't set, they open at the size the last one was.
+    default_size: Option<(u32, u32)>,
+    last_size: Option<(u32, u32)>,
+    graph_range: GraphRange,
+    line_animation: LineAnimation,
+    /// Parameter categories that loading a preset leaves alone. See [`presets::category()`].
+    locked_categories: BTreeSet<String>,
+}
+
+type CachedGradient = Arc<dyn Gradient + Send + Sync>;
+
+impl EditorOptions {
+    /// Builds the gradient used for the filter line. This allocates, so the result should be cached
+    /// and only rebuilt when the options change.
+    fn build_gradient(&self) -> CachedGradient {
+        let colors = match self.gradient_type {
+            GradientType::Rainbow => return Arc::new(colorgrad::preset::rainbow()),
+            GradientType::Lesbian => vec![
+                Color::from_rgba8(213, 45, 0, 255),
+                Color::from_rgba8(238, 118, 39, 255),
+                Color::from_rgba8(255, 154, 86, 255),
+                Color::from_rgba8(255, 255, 255, 255),
+                Color::from_rgba8(209, 98, 164, 255),
+                Color::from_rgba8(181, 86, 144, 255),
+                Color::from_rgba8(163, 2, 98, 255),
+            ],
+            GradientType::Bi => vec![
+                Color::from_rgba8(214, 2, 12, 255),
+                Color::from_rgba8(155, 79, 150, 255),
+                Color::from_rgba8(0, 56, 168, 255),
+            ],
+            GradientType::Trans => vec![
+                Color::from_rgba8(91, 206, 250, 255),
+                Color::from_rgba8(245, 169, 184, 255),
+                Color::from_rgba8(255, 255, 255, 255),
+            ],
+            GradientType::Ace => vec![
+                Color::from_rgba8(0, 0, 0, 255),
+                Color::from_rgba8(163, 163, 163, 255),
+                Color::from_rgba8(255, 255, 255, 255),
+                Color::from_rgba8(128, 0, 128, 255),
+            ],
+            GradientType::Custom => self
+                .gradient_colors
+                .iter()
+                .map(|[r, g, b]| Color::from_rgba8(*r, *g, *b, 255))
+                .collect(),
+        };
+
+        Arc::new(
+            colorgrad::GradientBuilder::new()
+                .colors(&colors)
+                .mode(colorgrad::BlendMode::Oklab)
+                .build::<CatmullRomGradient>()
+                .unwrap(),
+        )
+    }
 }
 
+/// The editor state for a new instance, sized from the config. Problems reading the config get
+/// reported once the editor is opened, so here they just mean falling back to the smallest size.
 pub fn default_editor_state() -> Arc<EguiState> {
-    EguiState::from_size(800, 600)
+    let (width, height) = fs::read_to_string(CONFIG_FILE.as_path())
+        .ok()
+        .and_then(|file| toml::from_str::<EditorOptions>(&file).ok())
+        .and_then(|options| options.default_size.or(options.last_size))
+        .unwrap_or(EDITOR_SIZES[0]);
+
+    EguiState::from_size(width, height)
+}
+
+/// Load the preset numbered `program` while the editor is closed, with the same numbering and
+/// locked categories the editor would use. This is for the background thread. There's nowhere to
+/// report problems, so a preset that can't be loaded just gets skipped.
+pub fn load_program_without_editor(
+    program: usize,
+    params: &ScaleColorizrParams,
+    preset_scan: &Mutex<PresetScan>,
+    sample_rate: f32,
+) {
+    // Without an editor the presets might never have been scanned, and the numbers follow the
+    // scan
+    PresetScan::run(preset_scan);
+    let Some(entry) = lock(preset_scan).presets.get(program).cloned() else {
+        return;
+    };
+
+    let locked = fs::read_to_string(CONFIG_FILE.as_path())
+        .ok()
+        .and_then(|file| toml::from_str::<EditorOptions>(&file).ok())
+        .map(|options| options.locked_categories)
+        .unwrap_or_default();
+    if let Ok(preset) = Preset::load(&entry.path) {
+        preset.apply_without_editor(params, &locked, sample_rate);
+    }
 }
 
-#[allow(clippy::too_many_lines)]
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 pub fn create(
     params: Arc<ScaleColorizrParams>,
-    displays: Arc<FrequencyDisplay>,
-    pre_spectrum: SpectrumOutput,
-    post_spectrum: SpectrumOutput,
+    pre_spectrum: Arc<Mutex<SpectrumOutput>>,
+    post_spectrum: Arc<Mutex<SpectrumOutput>>,
+    scope: Arc<Mutex<ScopeOutput>>,
+    correlation: Arc<AtomicF32>,
+    limiter_gain: Arc<AtomicCell<f32>>,
+    voices_stolen: Arc<AtomicU32>,
+    test_tone: Arc<AtomicCell<TestToneSettings>>,
+    midi_recorder: Arc<MidiRecorder>,
+    preset_scan: Arc<Mutex<PresetScan>>,
+    async_executor: AsyncExecutor<ScaleColorizr>,
+    spectrum_settings: Arc<AtomicCell<SpectrumSettings>>,
     sample_rate: Arc<AtomicF32>,
     midi_debug: Arc<AtomicCell<Option<NoteEvent<()>>>>,
-    biquads: Arc<FilterDisplay>,
+    program_change: Arc<AtomicCell<Option<usize>>>,
+    filter_display: Arc<Mutex<FilterDisplayOutput>>,
 ) -> Option<Box<dyn Editor>> {
     create_egui_editor(
         params.editor_state.clone(),
-        EditorState::new(pre_spectrum, post_spectrum),
+        EditorState::new(
+            pre_spectrum,
+            post_spectrum,
+            scope,
+            spectrum_settings,
+            filter_display,
+            program_change,
+            preset_scan,
+            async_executor,
+        ),
         |ctx, state| {
+            // Anything left over from the last time the editor was open is stale by now
+            state.program_change.store(None);
+            state.last_frame = None;
             cozy_ui::setup(ctx);
             ctx.style_mut(|style| {
                 style.visuals.widgets.open.weak_bg_fill = cozy_ui::colors::WIDGET_BACKGROUND_COL32;
@@ -160,8 +739,13 @@ pub fn create(
             } else {
                 match CONFIG_FILE.as_path().try_exists() {
                     Ok(true) => match std::fs::read_to_string(CONFIG_FILE.as_path()) {
-                        Ok(file) => match toml::from_str(&file) {
-                            Ok(options) => state.options = options,
+                        Ok(file) => match toml::from_str::<EditorOptions>(&file) {
+                            Ok(options) => {
+                                state.gradient = options.build_gradient();
+                                state.spectrum_settings.store(options.spectrum);
+                                state.graph_viewport = GraphViewport::new(options.graph_range);
+                                state.options = options;
+                            }
                             Err(e) => {
                                 state.config_io_error = Some(format!("Invalid config - {e:?}"));
                             }
@@ -171,8 +755,8 @@ pub fn create(
                         }
                     },
                     Ok(false) => {
-                        if let Err(e) = fs::write(
-                            CONFIG_FILE.as_path(),
+                        if let Err(e) = files::write_atomic(
+                            &CONFIG_FILE,
                             toml::to_string_pretty(&EditorOptions::default()).unwrap(),
                         ) {
                             state.config_io_error =
@@ -182,8 +766,61 @@ pub fn create(
                     Err(e) => state.config_io_error = Some(format!("Can't read config - {e:?}")),
                 }
             }
+
+            state.rescan_presets();
         },
         move |ctx, setter, state| {
+            let now = Instant::now();
+            state.frame_dt = state
+                .last_frame
+                .map_or(0.0, |last| now.duration_since(last).as_secs_f32())
+                .min(MAX_FRAME_SECS);
+            state.last_frame = Some(now);
+
+            let scanning_presets = {
+                let mut scan = lock(&state.presets);
+                if let Some(e) = scan.error.take() {
+                    state.config_io_error = Some(e);
+                }
+                scan.scanning
+            };
+            // Program changes are picked up here while the editor is open, so the host gets told
+            // about the new values. The plugin loads them itself while it's closed. They wait for
+            // the preset scan, since the numbers aren't settled until it's done.
+            if !scanning_presets {
+                if let Some(program) = state.program_change.take() {
+                    state.load_preset(program, &params, setter);
+                }
+            }
+
+            // Remembered so new instances can open at the same size, once it's stopped changing.
+            // This is held off while the config is broken, so it doesn't get overwritten before it
+            // can be fixed.
+            let size = params.editor_state.size();
+            if state.options.last_size == Some(size) {
+                state.unsaved_size = None;
+            } else {
+                match state.unsaved_size {
+                    Some((unsaved, since)) if unsaved == size => {
+                        if since.elapsed().as_secs_f32() >= SIZE_SAVE_DELAY_SECS
+                            && state.config_io_error.is_none()
+                        {
+                            state.options.last_size = Some(size);
+                            state.unsaved_size = None;
+                            if let Err(e) = files::write_atomic(
+                                &CONFIG_FILE,
+                                toml::to_string_pretty(&state.options).unwrap(),
+                            ) {
+                                state.config_io_error =
+                                    Some(format!("Couldn't write config: {e:?}"));
+                            }
+                        }
+                    }
+                    _ => state.unsaved_size = Some((size, Instant::now())),
+                }
+                ctx.request_repaint_after(Duration::from_secs_f32(SIZE_SAVE_DELAY_SECS));
+            }
+
             egui::TopBottomPanel::top("menu")
                 .frame(Frame::side_top_panel(&ctx.style()).shadow(Shadow {
                     offset: vec2(0.0, 4.0),
@@ -199,22 +836,70 @@ pub fn create(
                             &mut state.show_about
                         };
                         *about_debug |= ui.button("ABOUT").clicked();
-                        ui.add(
-                        toggle(
-                            "delta",
-                            params.delta.name().to_ascii_uppercase(),
-                            get_set(&params.delta, setter),
-                            begin_set(&params.delta, setter),
-                            end_set(&params.delta, setter),
-                        )
-                        .description(
-                            "Takes the difference between the dry and wet signal, the \"Delta\"",
-                        ),
-                    );
+                        ui.toggle_value(&mut state.show_help, "?")
+                            .on_hover_text("Shows what every control does");
+                        // Written every frame, before any overlay gets drawn
+                        ctx.data_mut(|data| data.insert_temp(egui::Id::new(HELP_MODE_ID), state.show_help));
+                        ui.selectable_value(&mut state.graph_view, GraphView::Spectrum, "SPECTRUM");
+                        ui.selectable_value(&mut state.graph_view, GraphView::Scope, "SCOPE");
+                        if state.graph_view == GraphView::Scope {
+                            ui.toggle_value(&mut state.scope_show_dry, "DRY")
+                                .on_hover_text("Draws the input behind the output");
+                        } else {
+                            ui.toggle_value(&mut state.capture, "CAPTURE")
+                                .on_hover_text("Click a peak in the input spectrum to detune the closest harmonic onto it");
+                        }
+                        let freeze = ui.add(
+                            toggle(
+                                "freeze",
+                                "FREEZE",
+                                get_set(&params.performance.freeze, setter),
+                                begin_set(&params.performance.freeze, setter),
+                                end_set(&params.performance.freeze, setter),
+                            )
+                            .description("Keeps voices playing after their keys are let go of"),
+                        );
+                        help_overlay(ui, freeze.rect, "Keeps voices playing after their keys are let go of");
+                        let ceiling = ui.add(
+                            toggle(
+                                "ceiling",
+                                "CEILING",
+                                get_set(&params.output.ceiling_enabled, setter),
+                                begin_set(&params.output.ceiling_enabled, setter),
+                                end_set(&params.output.ceiling_enabled, setter),
+                            )
+                            .description("Turns the output down when its true peak goes over the ceiling level"),
+                        );
+                        help_overlay(ui, ceiling.rect, "Turns the output down when its true peak goes over the ceiling level");
                         state.show_settings |= ui.button("SETTINGS").clicked();
+                        state.show_presets |= ui.button("PRESETS").clicked();
+                        state.show_envelope |= ui.button("ENVELOPE").clicked();
+                        state.show_filter |= ui.button("FILTER").clicked();
+                        state.show_gate |= ui.button("GATE").clicked();
+                        state.show_detune |= ui.button("DETUNE").clicked();
+                        state.show_shift |= ui.button("SHIFT").clicked();
+                        state.show_falloff |= ui.button("FALLOFF").clicked();
+                        state.show_scale |= ui.button("SCALE").clicked();
+                        state.show_noise_gate |= ui.button("NOISE GATE").clicked();
+                        state.show_test_tone |= ui.button("TEST TONE").clicked();
 
                         ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
-                            switch(ui, &params.filter_mode, setter);
+                            switch(ui, &params.filter.filter_mode, setter);
+                            switch(ui, &params.envelope.mode, setter);
+                            switch(ui, &params.filter.slope, setter);
+                            switch(ui, &params.filter.spread, setter);
+                            switch(ui, &params.rhythm.quantize, setter);
+                            switch(ui, &params.output.source, setter);
+                            correlation_meter(
+                                ui,
+                                correlation.load(std::sync::atomic::Ordering::Relaxed),
+                            );
+                            let voices_stolen = voices_stolen.load(std::sync::atomic::Ordering::Relaxed);
+                            if voices_stolen > state.voices_stolen {
+                                state.last_steal = Some(Instant::now());
+                            }
+                            state.voices_stolen = voices_stolen;
+                            steal_indicator(ui, voices_stolen, state.last_steal);
                             if let Some(error) = &state.config_io_error {
                                 ui.label(RichText::new("⚠").color(Color32::GOLD))
                                     .on_hover_text(error);
@@ -229,52 +914,194 @@ pub fn create(
                         knob(
                             ui,
                             setter,
-                            &params.gain,
+                            &params.output.input_gain,
+                            40.0,
+                            "Drives or trims the input before it reaches the filters",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            params.output.band_gain(),
+                            50.0,
+                            "The band gain used for the filters. Below 0 dB the harmonics get turned down instead",
+                        );
+                        let fine_gain = ui.add(toggle(
+                            "fine_gain_enabled",
+                            "FINE",
+                            get_set(&params.output.fine_gain_enabled, setter),
+                            begin_set(&params.output.fine_gain_enabled, setter),
+                            end_set(&params.output.fine_gain_enabled, setter),
+                        ));
+                        help_overlay(
+                            ui,
+                            fine_gain.rect,
+                            "Switches the band gain to a range of -2 to 2 dB, for small boosts and cuts",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.output.delta_mix,
                             50.0,
-                            "The band gain used for the filters",
+                            "Fades from the difference between the dry and wet signal, the \"Delta\", through the dry signal to the wet one",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.output.ceiling,
+                            40.0,
+                            "The highest the output's true peak can go with the ceiling on",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.output.duck_dry,
+                            40.0,
+                            "Turns the dry signal down while the filters ring",
                         );
                         knob(
                             ui,
                             setter,
-                            &params.attack,
+                            &params.envelope.attack,
                             50.0,
                             "The attack for the filter envelope",
                         );
                         knob(
                             ui,
                             setter,
-                            &params.release,
+                            &params.envelope.release,
                             50.0,
                             "The release for the filter envelope",
                         );
                         knob(
                             ui,
                             setter,
-                            &params.band_width,
+                            &params.envelope.hold,
+                            40.0,
+                            "In one shot and sidechain trigger modes, how long hits stay at full level",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.decay,
+                            40.0,
+                            "In one shot and sidechain trigger modes, how long hits take to fade out",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.band_width,
                             50.0,
                             "Changes how narrow the filters are",
                         );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.morph,
+                            40.0,
+                            "In morph mode, goes from cutting the filters' notes to boosting them",
+                        );
+                        let voices_in_use = lock(&state.filter_display)
+                            .read()
+                            .iter()
+                            .filter(|voice| voice.envelope.is_some())
+                            .count();
+                        state.show_voices |=
+                            voice_stepper(ui, setter, &params.voice_count, voices_in_use);
                     });
                 })
             });
 
+            let gain_reduction = nih_plug::util::gain_to_db(limiter_gain.swap(1.0).recip());
+            if params.output.ceiling_enabled.value() {
+                if state.gain_reduction_history.len() == GAIN_REDUCTION_HISTORY_LEN {
+                    state.gain_reduction_history.pop_front();
+                }
+                state.gain_reduction_history.push_back(gain_reduction);
+
+                egui::TopBottomPanel::bottom("gain_reduction")
+                    .exact_height(24.0)
+                    .show(ctx, |ui| {
+                        draw_gain_reduction(ui, &state.gain_reduction_history);
+                    });
+            } else {
+                state.gain_reduction_history.clear();
+            }
+
             egui::CentralPanel::default().show(ctx, |ui| {
                 egui::Frame::canvas(ui.style())
                     .stroke(Stroke::new(2.0, Color32::DARK_GRAY))
                     .show(ui, |ui| {
-                        let (_, rect) = ui.allocate_space(ui.available_size_before_wrap());
+                        let (rect, response) = ui.allocate_exact_size(
+                            ui.available_size_before_wrap(),
+                            Sense::click_and_drag(),
+                        );
+                        if state.graph_view == GraphView::Scope {
+                            draw_scope(
+                                ui,
+                                rect,
+                                lock(&state.scope).read(),
+                                state.scope_show_dry,
+                                &mut state.graph_buffers.points,
+                            );
+                            help_overlay(
+                                ui,
+                                Rect::from_center_size(rect.center(), vec2(rect.width() / 2.0, 0.0)),
+                                "The output, lined up on the start of a cycle so it holds still. The input can be shown behind it in gray",
+                            );
+                            return;
+                        }
+
+                        let viewport = &mut state.graph_viewport;
+                        if response.double_clicked() {
+                            *viewport = GraphViewport::new(state.options.graph_range);
+                        } else if response.dragged() {
+                            viewport.pan(-response.drag_delta().x / rect.width());
+                        }
+                        if let Some(pos) = response.hover_pos() {
+                            let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+                            if scroll.abs() > 0.0 {
+                                let anchor = (pos.x - rect.left()) / rect.width();
+                                viewport.zoom(anchor, (-scroll * 0.002).exp());
+                            }
+                        }
+                        let viewport = *viewport;
+
+                        if state.capture && response.clicked() {
+                            if let Some(pos) = response.interact_pointer_pos() {
+                                let nyquist = sample_rate.load(std::sync::atomic::Ordering::Relaxed) / 2.0;
+                                let peak = find_peak(
+                                    lock(&state.pre_spectrum).read().bins(),
+                                    nyquist,
+                                    viewport.freq(rect, pos.x - CAPTURE_RADIUS),
+                                    viewport.freq(rect, pos.x + CAPTURE_RADIUS),
+                                );
+                                let message = match peak {
+                                    Some(peak) => capture_peak(peak, lock(&state.filter_display).read(), params.as_ref(), setter),
+                                    None => "There's no peak there".to_string(),
+                                };
+                                state.toast = Some((message, Instant::now()));
+                            }
+                        }
 
-                        draw_log_grid(ui, rect);
+                        draw_log_grid(ui, rect, viewport);
+                        help_overlay(
+                            ui,
+                            Rect::from_center_size(rect.center(), vec2(rect.width() / 2.0, 0.0)),
+                            "The input spectrum is drawn in gray and the output spectrum in color. The line shows what the filters are doing to each frequency. Scroll to zoom, drag to pan, and double click to reset. With CAPTURE on, clicking a peak detunes the closest harmonic onto it",
+                        );
 
                         draw_spectrum(
                             ui,
                             rect,
-                            &mut state.pre_spectrum,
+                            viewport,
+                            &mut lock(&state.pre_spectrum),
+                            &mut state.graph_buffers.points,
                             &sample_rate,
                             Color32::GRAY.gamma_multiply(remap(
-                                ui.ctx().animate_bool(
-                                    "delta_active".into(),
-                                    !params.delta.modulated_plain_value(),
+                                fade(
+                                    &mut state.delta_fade,
+                                    params.output.delta_mix.modulated_plain_value() >= 0.0,
+                                    state.frame_dt,
                                 ),
                                 0.0..=1.0,
                                 0.25..=1.0,
@@ -283,101 +1110,64 @@ pub fn create(
                         draw_spectrum(
                             ui,
                             rect,
-                            &mut state.post_spectrum,
+                            viewport,
+                            &mut lock(&state.post_spectrum),
+                            &mut state.graph_buffers.points,
                             &sample_rate,
-                            cozy_ui::colors::HIGHLIGHT_COL32.gamma_multiply(
-                                ui.memory(|m| m.data.get_temp("active_amt".into()).unwrap_or(0.0)),
-                            ),
+                            cozy_ui::colors::HIGHLIGHT_COL32
+                                .gamma_multiply(state.filter_line_active),
                         );
 
                         let filter_line_stopwatch = Sw::new_started();
-                        match state.options.gradient_type {
-                            GradientType::Rainbow => {
-                                draw_filter_line(ui, rect, &biquads, colorgrad::preset::rainbow());
-                            }
-                            GradientType::Lesbian => draw_filter_line(
-                                ui,
-                                rect,
-                                &biquads,
-                                colorgrad::GradientBuilder::new()
-                                    .colors(&[
-                                        Color::from_rgba8(213, 45, 0, 255),
-                                        Color::from_rgba8(238, 118, 39, 255),
-                                        Color::from_rgba8(255, 154, 86, 255),
-                                        Color::from_rgba8(255, 255, 255, 255),
-                                        Color::from_rgba8(209, 98, 164, 255),
-                                        Color::from_rgba8(181, 86, 144, 255),
-                                        Color::from_rgba8(163, 2, 98, 255),
-                                    ])
-                                    .mode(colorgrad::BlendMode::Oklab)
-                                    .build::<CatmullRomGradient>()
-                                    .unwrap(),
-                            ),
-                            GradientType::Bi => draw_filter_line(
-                                ui,
-                                rect,
-                                &biquads,
-                                colorgrad::GradientBuilder::new()
-                                    .colors(&[
-                                        Color::from_rgba8(214, 2, 12, 255),
-                                        Color::from_rgba8(155, 79, 150, 255),
-                                        Color::from_rgba8(0, 56, 168, 255),
-                                    ])
-                                    .mode(colorgrad::BlendMode::Oklab)
-                                    .build::<CatmullRomGradient>()
-                                    .unwrap(),
-                            ),
-                            GradientType::Trans => draw_filter_line(
-                                ui,
-                                rect,
-                                &biquads,
-                                colorgrad::GradientBuilder::new()
-                                    .colors(&[
-                                        Color::from_rgba8(91, 206, 250, 255),
-                                        Color::from_rgba8(245, 169, 184, 255),
-                                        Color::from_rgba8(255, 255, 255, 255),
-                                    ])
-                                    .mode(colorgrad::BlendMode::Oklab)
-                                    .build::<CatmullRomGradient>()
-                                    .unwrap(),
-                            ),
-                            GradientType::Ace => draw_filter_line(
-                                ui,
-                                rect,
-                                &biquads,
-                                colorgrad::GradientBuilder::new()
-                                    .colors(&[
-                                        Color::from_rgba8(0, 0, 0, 255),
-                                        Color::from_rgba8(163, 163, 163, 255),
-                                        Color::from_rgba8(255, 255, 255, 255),
-                                        Color::from_rgba8(128, 0, 128, 255),
-                                    ])
-                                    .mode(colorgrad::BlendMode::Oklab)
-                                    .build::<CatmullRomGradient>()
-                                    .unwrap(),
-                            ),
-                            GradientType::Custom => draw_filter_line(
-                                ui,
-                                rect,
-                                &biquads,
-                                colorgrad::GradientBuilder::new()
-                                    .colors(
-                                        &state
-                                            .options
-                                            .gradient_colors
-                                            .iter()
-                                            .map(|[r, g, b]| Color::from_rgba8(*r, *g, *b, 255))
-                                            .collect::<Vec<Color>>(),
-                                    )
-                                    .mode(colorgrad::BlendMode::Oklab)
-                                    .build::<CatmullRomGradient>()
-                                    .unwrap(),
-                            ),
-                        };
-                        let draw_time = filter_line_stopwatch.elapsed();
-                        ui.memory_mut(|memory| {
-                            memory.data.insert_temp("filter_elapsed".into(), draw_time);
-                        });
+                        // The seed can change under us when the host loads a project
+                        let seed = params.seed.load(std::sync::atomic::Ordering::Relaxed);
+                        if state.line_noise.seed != seed {
+                            state.line_noise = LineNoise::new(seed);
+                        }
+                        let mut filter_display = lock(&state.filter_display);
+                        let filter_display = filter_display.read();
+                        let is_active = filter_display
+                            .iter()
+                            .any(|voice| voice.filters.iter().any(Option::is_some));
+                        state.line_noise.advance(
+                            state.options.line_animation,
+                            state.frame_dt,
+                            is_active,
+                        );
+                        state.filter_line_active = draw_filter_line(
+                            ui,
+                            rect,
+                            viewport,
+                            filter_display,
+                            &mut state.graph_buffers,
+                            &state.line_noise,
+                            &sample_rate,
+                            state.gradient.clone(),
+                        );
+                        state.filter_line_elapsed = filter_line_stopwatch.elapsed();
+                        draw_skipped_filters(
+                            ui,
+                            rect,
+                            filter_display,
+                            &mut state.graph_buffers.skipped_frequencies,
+                        );
+                        let envelope_rect = draw_envelope(
+                            ui,
+                            rect,
+                            &params.envelope,
+                            &params.filter.filter_mode,
+                            filter_display,
+                            &mut state.graph_buffers.points,
+                        );
+                        help_overlay(
+                            ui,
+                            envelope_rect,
+                            "The amplitude envelope's shape, with a dot for where each voice is along it",
+                        );
+
+                        if let Some(pos) = response.hover_pos() {
+                            draw_cursor_readout(ui, rect, viewport, pos, &params.scale);
+                        }
                     });
             });
 
@@ -385,45 +1175,37 @@ pub fn create(
                 .vscroll(true)
                 .open(&mut state.show_debug)
                 .show(ctx, |ui| {
+                    ui.label(format!("VOICES STOLEN: {}", state.voices_stolen));
                     ui.collapsing("VOICES", |ui| {
-                        for (idx, display) in displays.iter().enumerate() {
-                            ui.group(|ui| {
-                                ui.label(format!("VOICE {idx}"));
-                                Grid::new(format!("voice-{idx}")).show(ui, |ui| {
-                                    for (i, filter) in display.iter().enumerate() {
-                                        ui.label(filter.load().map_or("UNUSED".to_string(), |v| {
-                                            format!("FREQ: {v}")
-                                        }));
-
-                                        if (i + 1) % 3 == 0 {
-                                            ui.end_row();
-                                        }
-                                    }
-                                });
-                            });
-                        }
+                        let voice_count =
+                            usize::try_from(params.voice_count.value()).unwrap_or_default();
+                        voice_grid(
+                            ui,
+                            lock(&state.filter_display).read(),
+                            voice_count,
+                            &state.gradient,
+                        );
                     });
                     ui.collapsing("FREQ GRAPH", |ui| {
                         ui.group(|ui| {
                             ui.label(format!(
                                 "drawing filter line took: {:.2?}",
-                                ui.memory(|memory| memory
-                                    .data
-                                    .get_temp::<Duration>("filter_elapsed".into())
-                                    .unwrap_or_default())
+                                state.filter_line_elapsed
                             ));
                         });
                         ui.group(|ui| {
-                            ui.label(format!(
-                                "{:?}",
-                                ui.memory(|m| m
-                                    .data
-                                    .get_temp::<Vec<f32>>("sampled_frequencies".into())
-                                    .unwrap_or_default())
-                            ))
+                            ui.label(format!("{:?}", state.graph_buffers.sampled_frequencies))
                         })
                     });
-                    ui.collapsing("MIDI", |ui| ui.label(format!("{:?}", midi_debug.load())))
+                    ui.collapsing("MIDI", |ui| ui.label(format!("{:?}", midi_debug.load())));
+                    ui.collapsing("MIDI RECORDING", |ui| {
+                        midi_recording(
+                            ui,
+                            &midi_recorder,
+                            &state.async_executor,
+                            sample_rate.load(std::sync::atomic::Ordering::Relaxed),
+                        );
+                    });
                 });
 
             Window::new("ABOUT")
@@ -443,84 +1225,1023 @@ pub fn create(
                     });
                 });
 
-            Window::new("SETTINGS")
-                .open(&mut state.show_settings)
+            Window::new("GATE")
+                .open(&mut state.show_gate)
                 .show(ctx, |ui| {
+                    let rhythm = &params.rhythm;
                     ui.horizontal(|ui| {
-                        ui.label("Voice Count");
-                        ui.add(DragValue::from_get_set(|value| {
-                            value.map_or_else(|| f64::from(params.voice_count.modulated_normalized_value()), |v| {
-                                    setter.begin_set_parameter(&params.voice_count);
-                                    setter.set_parameter_normalized(&params.voice_count, v as f32);
-                                    setter.end_set_parameter(&params.voice_count);
-                                    v
-                                })
-                        }).custom_parser(|s| params.voice_count.string_to_normalized_value(s).map(f64::from)).speed(0.01).range(0.0..=1.0).custom_formatter(|v, _| {
-                            params.voice_count.normalized_value_to_string(v as f32, false)
-                        }))
+                        ui.add(
+                            toggle(
+                                "gate",
+                                "GATE",
+                                get_set(&rhythm.gate, setter),
+                                begin_set(&rhythm.gate, setter),
+                                end_set(&rhythm.gate, setter),
+                            )
+                            .description(
+                                "Chops the band gain up in time with the host's transport",
+                            ),
+                        );
+                        switch(ui, &rhythm.gate_steps, setter);
                     });
-                    ui.separator();
-                    ui.label(RichText::new("This allows the filters to go above the nyquist frequency."));
-                    ui.label(RichText::new("⚠ DO NOT TURN THIS OFF UNLESS YOU KNOW WHAT YOU ARE DOING. THIS WILL BLOW YOUR HEAD OFF ⚠").color(Color32::RED).strong());
-                    ui.add(toggle("safety_switch", "SAFETY SWITCH", get_set(&params.safety_switch, setter), begin_set(&params.safety_switch, setter), end_set(&params.safety_switch, setter)));
-                    ui.separator();
-                    ui.heading("Gradient Editor");
-                    let mut options_edited = egui::ComboBox::from_label("Gradient Type").selected_text(state.options.gradient_type.to_string()).show_ui(ui, |ui| {
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Rainbow, GradientType::Rainbow.to_string()).changed() ||
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Lesbian, GradientType::Lesbian.to_string()).changed() ||
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Bi, GradientType::Bi.to_string()).changed() ||
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Trans, GradientType::Trans.to_string()).changed() ||
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Ace, GradientType::Ace.to_string()).changed() ||
-                        ui.selectable_value(&mut state.options.gradient_type, GradientType::Custom, GradientType::Custom.to_string()).changed()
-                    }).inner.is_some_and(|i| i);
-
-                    if state.options.gradient_type == GradientType::Custom {
-                        let to_remove: Vec<_> = state.options.gradient_colors.iter_mut().enumerate().filter_map(|(i, color)| ui.horizontal(|ui| {
-                            let changed = ui.color_edit_button_srgb(color).changed();
-                            if ui.button("Delete").clicked() {
-                                options_edited = true;
-                                Some(i)
-                            } else {
-                                options_edited |= changed;
-                                None
-                            }
-                        }).inner).collect();
-
-                        for i in to_remove {
-                            state.options.gradient_colors.remove(i);
-                        }
-
-                        if ui.button("Add Color").clicked() {
-                            options_edited = true;
-                            state.options.gradient_colors.push([100, 0, 0]);
+                    knob(
+                        ui,
+                        setter,
+                        &rhythm.gate_depth,
+                        40.0,
+                        "How far the band gain drops on closed steps",
+                    );
+                    ui.horizontal(|ui| {
+                        for (idx, step) in rhythm
+                            .gate_pattern
+                            .iter()
+                            .take(rhythm.gate_steps.value().count())
+                            .enumerate()
+                        {
+                            ui.add(toggle(
+                                &format!("gate_step_{idx}"),
+                                (idx + 1).to_string(),
+                                get_set(&step.on, setter),
+                                begin_set(&step.on, setter),
+                                end_set(&step.on, setter),
+                            ));
                         }
-                    }
+                    });
+                });
 
-                    if options_edited {
-                        if let Err(e) = fs::write(CONFIG_FILE.as_path(), toml::to_string_pretty(&state.options).unwrap()) {
-                            state.config_io_error = Some(format!("Couldn't write config: {e:?}"));
+            Window::new("DETUNE")
+                .open(&mut state.show_detune)
+                .show(ctx, |ui| {
+                    ui.label("Moves each harmonic away from where it would be, in cents");
+                    Grid::new("detune").striped(true).show(ui, |ui| {
+                        for (idx, detune) in params.detune.iter().enumerate() {
+                            ui.label(format!("Harmonic {}", idx + 1));
+                            ui.add(drag_value(&detune.cents, setter));
+                            if ui.button("Reset").clicked() {
+                                setter.begin_set_parameter(&detune.cents);
+                                setter.set_parameter(&detune.cents, 0.0);
+                                setter.end_set_parameter(&detune.cents);
+                            }
+                            ui.end_row();
                         }
-                    }
+                    });
                 });
-        },
-    )
-}
-
-fn draw_log_grid(ui: &Ui, rect: Rect) {
-    let painter = ui.painter_at(rect);
-    let log_min = FREQ_RANGE_START_HZ.log10();
-    let log_max = FREQ_RANGE_END_HZ.log10();
 
-    let mut previous = 10.0;
-    for max in PowersOfTen::new(10.0, 20_000.0) {
+            Window::new("FALLOFF")
+                .open(&mut state.show_falloff)
+                .show(ctx, |ui| {
+                    ui.label("How much of the band gain each harmonic gets. Double click a point to reset it");
+                    falloff_editor(ui, &params.falloff, setter);
+                    ui.separator();
+                    let character = &params.character;
+                    ui.horizontal(|ui| {
+                        switch(ui, &character.from, setter);
+                        knob(
+                            ui,
+                            setter,
+                            &character.amount,
+                            40.0,
+                            "Blends the harmonics' levels from one profile to the other",
+                        );
+                        switch(ui, &character.to, setter);
+                        knob(
+                            ui,
+                            setter,
+                            &character.tilt,
+                            40.0,
+                            "Turns the upper harmonics down, or the lower ones down, on top of the character",
+                        );
+                    });
+                });
+
+            Window::new("SHIFT")
+                .open(&mut state.show_shift)
+                .show(ctx, |ui| {
+                    let filter = &params.filter;
+                    ui.label(
+                        "Frequency shifts what the filters add, for inharmonic, bell-like colors",
+                    );
+                    switch(ui, &filter.freq_shift, setter);
+                    ui.horizontal(|ui| {
+                        knob(
+                            ui,
+                            setter,
+                            &filter.freq_shift_ratio,
+                            40.0,
+                            "The shift as a fraction of the note's fundamental",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &filter.freq_shift_hz,
+                            40.0,
+                            "The shift in Hz, the same for every note",
+                        );
+                    });
+                });
+
+            Window::new("ENVELOPE")
+                .open(&mut state.show_envelope)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        let separate_notch_times = ui.add(toggle(
+                            "separate_notch_times",
+                            "NOTCH TIMES",
+                            get_set(&params.envelope.separate_notch_times, setter),
+                            begin_set(&params.envelope.separate_notch_times, setter),
+                            end_set(&params.envelope.separate_notch_times, setter),
+                        ));
+                        help_overlay(
+                            ui,
+                            separate_notch_times.rect,
+                            "Gives notch mode its own attack and release",
+                        );
+                        if params.envelope.separate_notch_times.value() {
+                            knob(
+                                ui,
+                                setter,
+                                &params.envelope.notch_attack,
+                                50.0,
+                                "The attack for the filter envelope in notch mode",
+                            );
+                            knob(
+                                ui,
+                                setter,
+                                &params.envelope.notch_release,
+                                50.0,
+                                "The release for the filter envelope in notch mode",
+                            );
+                        }
+                        let split_attack = ui.add(toggle(
+                            "split_attack",
+                            "CHORD ATTACK",
+                            get_set(&params.envelope.split_attack, setter),
+                            begin_set(&params.envelope.split_attack, setter),
+                            end_set(&params.envelope.split_attack, setter),
+                        ));
+                        help_overlay(
+                            ui,
+                            split_attack.rect,
+                            "Gives notes added to a chord that's already playing their own, faster attack",
+                        );
+                        if params.envelope.split_attack.value() {
+                            knob(
+                                ui,
+                                setter,
+                                &params.envelope.chord_attack,
+                                50.0,
+                                "The attack for notes that join a chord. It's never slower than the normal attack",
+                            );
+                            knob(
+                                ui,
+                                setter,
+                                &params.envelope.chord_window,
+                                40.0,
+                                "How soon after another held note a note has to start to count as part of its chord",
+                            );
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.trigger_threshold,
+                            40.0,
+                            "In sidechain trigger mode, how loud sidechain hits need to be",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.attack_shape,
+                            40.0,
+                            "The attack's curve, from exponential to linear to an s-curve",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.release_shape,
+                            40.0,
+                            "The release's curve, from exponential to linear to an s-curve",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.attack_keytrack,
+                            40.0,
+                            "Makes the attack shorter for high notes and longer for low ones",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.release_keytrack,
+                            40.0,
+                            "Makes the release shorter for high notes and longer for low ones",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.damping,
+                            40.0,
+                            "Widens the filters and mutes the upper harmonics as notes release",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.humanize,
+                            40.0,
+                            "Randomly varies each new voice's attack time and gain",
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.width_amount,
+                            40.0,
+                            "How far the width envelope moves the band width",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.width_attack,
+                            40.0,
+                            "The attack for the width envelope",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.envelope.width_decay,
+                            40.0,
+                            "The decay for the width envelope",
+                        );
+                    });
+                });
+
+            Window::new("FILTER")
+                .open(&mut state.show_filter)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.keytrack,
+                            40.0,
+                            "Widens the filters for low notes and narrows them for high ones",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.velocity,
+                            40.0,
+                            "Widens the filters for hard hits and narrows them for soft ones",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.scatter,
+                            40.0,
+                            "Randomly detunes each filter when a note starts, so repeated notes don't ring exactly the same",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.max_resonance,
+                            40.0,
+                            "How hard the filters ring at the narrowest band width",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &params.filter.stereo_alternation,
+                            40.0,
+                            "Pushes odd harmonics to the left and even harmonics to the right",
+                        );
+                    });
+                });
+
+            Window::new("NOISE GATE")
+                .open(&mut state.show_noise_gate)
+                .show(ctx, |ui| {
+                    let noise_gate = &params.noise_gate;
+                    ui.add(
+                        toggle(
+                            "noise_gate",
+                            "NOISE GATE",
+                            get_set(&noise_gate.enabled, setter),
+                            begin_set(&noise_gate.enabled, setter),
+                            end_set(&noise_gate.enabled, setter),
+                        )
+                        .description(
+                            "Turns down quiet input before the filters, so bleed and noise don't get boosted into hiss",
+                        ),
+                    );
+                    ui.horizontal(|ui| {
+                        knob(
+                            ui,
+                            setter,
+                            &noise_gate.threshold,
+                            40.0,
+                            "Input below this level gets turned down",
+                        );
+                        knob(
+                            ui,
+                            setter,
+                            &noise_gate.ratio,
+                            40.0,
+                            "How hard input below the threshold gets turned down",
+                        );
+                    });
+                });
+
+            Window::new("VOICES")
+                .open(&mut state.show_voices)
+                .show(ctx, |ui| {
+                    let voice_count =
+                        usize::try_from(params.voice_count.value()).unwrap_or_default();
+                    voice_grid(
+                        ui,
+                        lock(&state.filter_display).read(),
+                        voice_count,
+                        &state.gradient,
+                    );
+                });
+
+            Window::new("TEST TONE")
+                .open(&mut state.show_test_tone)
+                .show(ctx, |ui| {
+                    ui.label("Plays a note without any MIDI coming in, for setting things up before anything's routed to the plugin");
+                    let mut settings = test_tone.load();
+                    Grid::new("test_tone").show(ui, |ui| {
+                        ui.label("Note");
+                        ui.add(
+                            DragValue::new(&mut settings.note)
+                                .range(0..=127)
+                                .speed(0.1)
+                                .custom_formatter(|note, _| {
+                                    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                                    note_name(note as u8)
+                                })
+                                .custom_parser(|text| parse_note_name(text).map(|(note, _)| f64::from(note))),
+                        );
+                        ui.end_row();
+                        ui.label("Velocity");
+                        ui.add(DragValue::new(&mut settings.velocity).range(0.0..=1.0).speed(0.005).fixed_decimals(2));
+                        ui.end_row();
+                        ui.label("Length");
+                        ui.add(DragValue::new(&mut settings.length_ms).range(10.0..=10_000.0).speed(1.0).suffix(" ms"));
+                        ui.end_row();
+                        let mut repeat = settings.repeat_ms.is_some();
+                        ui.checkbox(&mut repeat, "Repeat every");
+                        let mut repeat_ms = settings.repeat_ms.unwrap_or(1000.0);
+                        ui.add_enabled(repeat, DragValue::new(&mut repeat_ms).range(50.0..=10_000.0).speed(1.0).suffix(" ms"));
+                        settings.repeat_ms = repeat.then_some(repeat_ms);
+                        ui.end_row();
+                    });
+                    if ui.button("Play").clicked() {
+                        settings.trigger = settings.trigger.wrapping_add(1);
+                    }
+                    test_tone.store(settings);
+                });
+
+            Window::new("SCALE")
+                .open(&mut state.show_scale)
+                .show(ctx, |ui| {
+                    let scale = &params.scale;
+                    ui.label("Locks every note to the closest note in the scale");
+                    switch(ui, &scale.scale, setter);
+                    switch(ui, &scale.root, setter);
+                    ui.separator();
+                    ui.label("Custom scale, counting up from the root");
+                    ui.horizontal(|ui| {
+                        for (idx, note) in scale.custom.iter().enumerate() {
+                            ui.add(toggle(
+                                &format!("scale_note_{idx}"),
+                                (idx + 1).to_string(),
+                                get_set(&note.enabled, setter),
+                                begin_set(&note.enabled, setter),
+                                end_set(&note.enabled, setter),
+                            ));
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Tuning Slot");
+                        ui.add(drag_value(&scale.tuning_slot, setter));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Reference Pitch");
+                        ui.add(drag_value(&scale.reference_pitch, setter));
+                    })
+                    .response
+                    .on_hover_text("Where A4 is for 12-TET and Scala tunings. MTS bulk dumps already set every note's frequency, so they stay put");
+                    ui.label("Load a Scala (.scl) file or an MTS bulk dump (.syx) into a slot, or drop one anywhere on the editor to load it into the current slot");
+                    // Only write locked when something changes, since the audio thread can't read
+                    // the tunings while that's going on
+                    let mut changed = None;
+                    Grid::new("tunings").striped(true).show(ui, |ui| {
+                        let tunings = scale.tunings.read().unwrap_or_else(PoisonError::into_inner);
+                        for (idx, (tuning, path)) in
+                            tunings.iter().zip(&mut state.tuning_paths).enumerate()
+                        {
+                            ui.label(format!("{}:", idx + 1));
+                            ui.label(tuning.as_ref().map_or("12-TET", |tuning| &tuning.name));
+                            ui.text_edit_singleline(path);
+                            if ui.button("Load").clicked() {
+                                match Tuning::load(path.as_ref()) {
+                                    Ok(loaded) => changed = Some((idx, Some(loaded))),
+                                    Err(e) => state.config_io_error = Some(e),
+                                }
+                            }
+                            if ui.button("Clear").clicked() {
+                                changed = Some((idx, None));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some((idx, tuning)) = changed {
+                        scale.tunings.write().unwrap_or_else(PoisonError::into_inner)[idx] = tuning;
+                    }
+                });
+
+            let mut show_presets = state.show_presets;
+            Window::new("PRESETS")
+                .vscroll(true)
+                .open(&mut show_presets)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut state.preset_name);
+                        if ui.button("Save").clicked() {
+                            match Preset::capture(params.as_ref()).save(&state.preset_name) {
+                                Ok(()) => state.rescan_presets(),
+                                Err(e) => state.config_io_error = Some(e),
+                            }
+                        }
+                        if ui.button("Refresh").clicked() {
+                            state.rescan_presets();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Set As Default")
+                            .on_hover_text("New instances start from the current settings")
+                            .clicked()
+                        {
+                            if let Err(e) = Preset::capture(params.as_ref()).save_as_default() {
+                                state.config_io_error = Some(e);
+                            }
+                        }
+                        if ui.button("Clear Default").clicked() {
+                            if let Err(e) = Preset::clear_default() {
+                                state.config_io_error = Some(e);
+                            }
+                        }
+                    });
+                    ui.separator();
+
+                    ui.horizontal(|ui| {
+                        for (slot, label) in ["A", "B"].into_iter().enumerate() {
+                            let selected = params.performance.morph_presets.read().unwrap_or_else(PoisonError::into_inner)[slot]
+                                .as_ref()
+                                .map_or_else(|| "None".to_string(), |(name, _)| name.clone());
+                            let mut chosen = None;
+                            egui::ComboBox::from_label(format!("MORPH {label}")).selected_text(selected).show_ui(ui, |ui| {
+                                for entry in &lock(&state.presets).presets {
+                                    if ui.selectable_label(false, &entry.name).clicked() {
+                                        chosen = Some(entry.clone());
+                                    }
+                                }
+                            });
+                            if let Some(entry) = chosen {
+                                match Preset::load(&entry.path) {
+                                    Ok(preset) => {
+                                        params.performance.morph_presets.write().unwrap_or_else(PoisonError::into_inner)[slot] =
+                                            Some((entry.name.clone(), preset));
+                                    }
+                                    Err(e) => state.config_io_error = Some(e),
+                                }
+                            }
+                        }
+                    });
+                    morph_drag(ui, &params, setter, &state.options.locked_categories);
+                    ui.separator();
+
+                    ui.collapsing("LOCK ON LOAD", |ui| {
+                        ui.label("Locked categories keep their settings when a preset is loaded or morphed. The look of the editor is never part of a preset");
+                        if state.preset_categories.is_empty() {
+                            state.preset_categories = presets::categories(params.as_ref());
+                        }
+                        for category in &state.preset_categories {
+                            let mut locked = state.options.locked_categories.contains(category);
+                            let label = if category.is_empty() { "General" } else { category };
+                            if ui.checkbox(&mut locked, label).changed() {
+                                if locked {
+                                    state.options.locked_categories.insert(category.clone());
+                                } else {
+                                    state.options.locked_categories.remove(category);
+                                }
+                                if let Err(e) = files::write_atomic(&CONFIG_FILE, toml::to_string_pretty(&state.options).unwrap()) {
+                                    state.config_io_error = Some(format!("Couldn't write config: {e:?}"));
+                                }
+                            }
+                        }
+                    });
+                    ui.separator();
+
+                    let mut to_load = None;
+                    let scan = lock(&state.presets);
+                    if scan.scanning {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Finding presets... {} so far", scan.presets.len()));
+                        });
+                    }
+                    Grid::new("presets").striped(true).show(ui, |ui| {
+                        for (idx, preset) in scan.presets.iter().enumerate() {
+                            // These are the program change numbers, with the bank as a prefix
+                            ui.label(format!("{}:{}", idx / 128, idx % 128));
+                            if ui
+                                .selectable_label(preset.name == state.preset_name, &preset.name)
+                                .clicked()
+                            {
+                                to_load = Some(idx);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    drop(scan);
+                    if let Some(idx) = to_load {
+                        state.load_preset(idx, &params, setter);
+                    }
+                });
+            state.show_presets = show_presets;
+
+            Window::new("SETTINGS")
+                .open(&mut state.show_settings)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        for tab in SettingsTab::ALL {
+                            ui.selectable_value(&mut state.settings_tab, tab, tab.to_string());
+                        }
+                        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.add(egui::TextEdit::singleline(&mut state.settings_search).hint_text("Search"));
+                        });
+                    });
+                    ui.separator();
+                    let filter = SettingsFilter { tab: state.settings_tab, search: &state.settings_search };
+
+                    if filter.shows(SettingsTab::Audio, "Retrigger") {
+                        ui.horizontal(|ui| {
+                            ui.label("Retrigger");
+                            switch(ui, &params.retrigger, setter);
+                        });
+                    }
+                    if filter.shows(SettingsTab::Audio, "Choke Groups") {
+                        ui.add(toggle("choke_groups", "CHOKE GROUPS", get_set(&params.choke_groups, setter), begin_set(&params.choke_groups, setter), end_set(&params.choke_groups, setter)).description("A new note releases the voices on its channel in the same group, like drum pads that replace each other"));
+                        if params.choke_groups.value() {
+                            ui.horizontal(|ui| {
+                                ui.label("Notes Per Group");
+                                ui.add(drag_value(&params.choke_group_size, setter));
+                            });
+                        }
+                    }
+                    if filter.shows(SettingsTab::Audio, "Aux Routing") {
+                        ui.horizontal(|ui| {
+                            ui.label("Aux Routing");
+                            switch(ui, &params.output.aux_routing, setter);
+                        });
+                    }
+                    if filter.shows(SettingsTab::Audio, "Resonance Curve") {
+                        ui.horizontal(|ui| {
+                            ui.label("Resonance Curve");
+                            switch(ui, &params.filter.resonance_curve, setter);
+                        });
+                    }
+                    if filter.shows(SettingsTab::Audio, "HF Compensation") {
+                        ui.add(toggle("hf_compensation", "HF COMPENSATION", get_set(&params.filter.hf_compensation, setter), begin_set(&params.filter.hf_compensation, setter), end_set(&params.filter.hf_compensation, setter)).description("Widens filters in the top octave, where they'd otherwise ring too narrow"));
+                    }
+                    if filter.shows(SettingsTab::Audio, "Automatic Harmonic Count") {
+                        ui.add(toggle("auto_harmonics", "AUTOMATIC HARMONIC COUNT", get_set(&params.filter.auto_harmonics, setter), begin_set(&params.filter.auto_harmonics, setter), end_set(&params.filter.auto_harmonics, setter)).description("Only runs the harmonics each note has below 20 kHz or Nyquist, so high notes use less CPU"));
+                    }
+
+                    if filter.shows(SettingsTab::Midi, "Freeze CC") {
+                        ui.horizontal(|ui| {
+                            ui.label("Freeze CC");
+                            ui.add(drag_value(&params.performance.freeze_cc, setter));
+                        });
+                    }
+                    if filter.shows(SettingsTab::Midi, "Band Width CC") {
+                        ui.horizontal(|ui| {
+                            ui.label("Band Width CC");
+                            ui.add(drag_value(&params.performance.band_width_cc, setter));
+                        });
+                    }
+                    if filter.shows(SettingsTab::Midi, "CC11 Expression") {
+                        ui.add(toggle("expression_cc", "CC11 EXPRESSION", get_set(&params.performance.expression, setter), begin_set(&params.performance.expression, setter), end_set(&params.performance.expression, setter)).description("Turns the voices on each channel down with that channel's expression pedal"));
+                    }
+                    if filter.shows(SettingsTab::Midi, "Velocity Range") {
+                        ui.horizontal(|ui| {
+                            ui.label("Velocity Range");
+                            ui.add(drag_value(&params.performance.min_velocity, setter));
+                            ui.label("to");
+                            ui.add(drag_value(&params.performance.max_velocity, setter));
+                        })
+                        .response
+                        .on_hover_text("Note ons outside of this range are ignored, so ghost notes don't take up voices");
+                    }
+                    if filter.shows(SettingsTab::Midi, "Duplicate Note Window") {
+                        ui.horizontal(|ui| {
+                            ui.label("Duplicate Note Window");
+                            ui.add(drag_value(&params.performance.duplicate_window, setter));
+                        })
+                        .response
+                        .on_hover_text("Repeats of a note that arrive this soon after it are merged into it instead of starting another voice. 0 turns this off");
+                    }
+                    if filter.shows(SettingsTab::Midi, "Release On Stop") {
+                        ui.add(toggle("release_on_stop", "RELEASE ON STOP", get_set(&params.performance.release_on_stop, setter), begin_set(&params.performance.release_on_stop, setter), end_set(&params.performance.release_on_stop, setter)).description("Releases every held voice when the host stops, for hosts that don't send note offs on stop"));
+                    }
+                    if filter.shows(SettingsTab::Midi, "MIDI Out") {
+                        ui.add(toggle("midi_out", "MIDI OUT", get_set(&params.midi_out, setter), begin_set(&params.midi_out, setter), end_set(&params.midi_out, setter)).description("Sends a note on and off for every voice that actually plays, after voice stealing"));
+                    }
+
+                    if filter.shows(SettingsTab::Advanced, "Allow Filters Above Nyquist Safety Switch") {
+                        let warning_accepted = state.options.nyquist_warning_accepted;
+                        let mut wants_warning = false;
+                        ui.add(toggle("allow_above_nyquist", "ALLOW FILTERS ABOVE NYQUIST", |value| {
+                            if let Operation::Set(allow) = value {
+                                if allow && !warning_accepted {
+                                    wants_warning = true;
+                                    return false;
+                                }
+                                setter.set_parameter(&params.filter.safety_switch, !allow);
+                                return allow;
+                            }
+
+                            !params.filter.safety_switch.value()
+                        }, begin_set(&params.filter.safety_switch, setter), end_set(&params.filter.safety_switch, setter)).description("Lets filters play at or above Nyquist, where they alias back down into the audible range"));
+                        state.confirm_above_nyquist |= wants_warning;
+
+                        let nyquist = sample_rate.load(std::sync::atomic::Ordering::Relaxed) / 2.0;
+                        let mut filter_display = lock(&state.filter_display);
+                        let filter_display = filter_display.read();
+                        let above_nyquist: Vec<_> = (0..NUM_FILTERS)
+                            .filter(|&idx| filter_display.iter().any(|voice| voice.skipped[idx].is_some() || voice.filters[idx].is_some_and(|filter| filter.frequency >= nyquist)))
+                            .map(|idx| (idx + 1).to_string())
+                            .collect();
+                        if above_nyquist.is_empty() {
+                            ui.label(format!("No harmonics are reaching Nyquist ({nyquist:.0} Hz) right now"));
+                        } else if params.filter.safety_switch.value() {
+                            ui.label(format!("Skipping harmonics {} for being above Nyquist ({nyquist:.0} Hz)", above_nyquist.join(", ")));
+                        } else {
+                            ui.label(RichText::new(format!("Harmonics {} are playing above Nyquist ({nyquist:.0} Hz)", above_nyquist.join(", "))).color(Color32::GOLD));
+                        }
+                    }
+                    if filter.shows(SettingsTab::Advanced, "Multithreading") {
+                        ui.add(toggle("multithreading", "MULTITHREADING", get_set(&params.multithreading, setter), begin_set(&params.multithreading, setter), end_set(&params.multithreading, setter)).description("Spreads voices across multiple threads. Voices are summed in parallel instead of chained, which sounds slightly different when filters overlap"));
+                    }
+                    if filter.shows(SettingsTab::Advanced, "Seed Reseed Random") {
+                        ui.horizontal(|ui| {
+                            let seed = params.seed.load(std::sync::atomic::Ordering::Relaxed);
+                            ui.label(format!("Seed {seed:016x}"));
+                            if ui
+                                .button("Reseed")
+                                .on_hover_text("Picks a new seed for humanize and the graph's noise")
+                                .clicked()
+                            {
+                                params
+                                    .seed
+                                    .store(rand::random(), std::sync::atomic::Ordering::Relaxed);
+                            }
+                        });
+                    }
+
+                    let mut options_edited = false;
+                    if filter.shows(SettingsTab::Appearance, "Gradient Editor Colors") {
+                        ui.heading("Gradient Editor");
+                        options_edited |= egui::ComboBox::from_label("Gradient Type").selected_text(state.options.gradient_type.to_string()).show_ui(ui, |ui| {
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Rainbow, GradientType::Rainbow.to_string()).changed() ||
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Lesbian, GradientType::Lesbian.to_string()).changed() ||
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Bi, GradientType::Bi.to_string()).changed() ||
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Trans, GradientType::Trans.to_string()).changed() ||
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Ace, GradientType::Ace.to_string()).changed() ||
+                            ui.selectable_value(&mut state.options.gradient_type, GradientType::Custom, GradientType::Custom.to_string()).changed()
+                        }).inner.is_some_and(|i| i);
+
+                        if state.options.gradient_type == GradientType::Custom {
+                            let to_remove: Vec<_> = state.options.gradient_colors.iter_mut().enumerate().filter_map(|(i, color)| ui.horizontal(|ui| {
+                                let changed = ui.color_edit_button_srgb(color).changed();
+                                if ui.button("Delete").clicked() {
+                                    options_edited = true;
+                                    Some(i)
+                                } else {
+                                    options_edited |= changed;
+                                    None
+                                }
+                            }).inner).collect();
+
+                            for i in to_remove {
+                                state.options.gradient_colors.remove(i);
+                            }
+
+                            if ui.button("Add Color").clicked() {
+                                options_edited = true;
+                                state.options.gradient_colors.push([100, 0, 0]);
+                            }
+                        }
+                    }
+
+                    if filter.shows(SettingsTab::Appearance, "Filter Line Animation Speed Noise Scale") {
+                        ui.heading("Filter Line");
+                        let animation = &mut state.options.line_animation;
+                        ui.horizontal(|ui| {
+                            ui.label("Animation Speed");
+                            options_edited |= ui.add(DragValue::new(&mut animation.speed).range(0.0..=10.0).speed(0.01).suffix("x")).on_hover_text("0 holds the noise still").changed();
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Noise Scale");
+                            options_edited |= ui.add(DragValue::new(&mut animation.scale).range(0.1..=10.0).speed(0.01).suffix("x")).changed();
+                        });
+                    }
+
+                    if filter.shows(SettingsTab::Appearance, "Graph Range Frequency") {
+                        ui.heading("Graph");
+                        let range = &mut state.options.graph_range;
+                        // Either end can be typed in as a note name too
+                        let parse_frequency = |text: &str| params.scale.parse_frequency(text).map(f64::from);
+                        let range_changed = ui.horizontal(|ui| {
+                            ui.label("Range");
+                            let start_changed = ui.add(DragValue::new(&mut range.start_hz).range(FREQ_RANGE_LOWEST_HZ..=range.end_hz / GRAPH_MIN_SPAN).suffix(" Hz").custom_parser(parse_frequency)).changed();
+                            ui.label("to");
+                            let end_changed = ui.add(DragValue::new(&mut range.end_hz).range(range.start_hz * GRAPH_MIN_SPAN..=FREQ_RANGE_HIGHEST_HZ).suffix(" Hz").custom_parser(parse_frequency)).changed();
+                            start_changed || end_changed
+                        }).inner;
+                        if range_changed {
+                            options_edited = true;
+                            state.graph_viewport = GraphViewport::new(*range);
+                        }
+                    }
+
+                    if filter.shows(SettingsTab::Appearance, "Spectrum Analyzer Window FFT Size Overlap Updates While Closed") {
+                        ui.heading("Spectrum Analyzer");
+                        options_edited |= egui::ComboBox::from_label("Window").selected_text(state.options.spectrum.window.to_string()).show_ui(ui, |ui| {
+                            [SpectrumWindow::Hann, SpectrumWindow::BlackmanHarris].into_iter().fold(false, |changed, window| ui.selectable_value(&mut state.options.spectrum.window, window, window.to_string()).changed() || changed)
+                        }).inner.is_some_and(|i| i);
+                        options_edited |= egui::ComboBox::from_label("FFT Size").selected_text(state.options.spectrum.window_size.to_string()).show_ui(ui, |ui| {
+                            SPECTRUM_WINDOW_SIZES.into_iter().fold(false, |changed, size| ui.selectable_value(&mut state.options.spectrum.window_size, size, size.to_string()).changed() || changed)
+                        }).inner.is_some_and(|i| i);
+                        options_edited |= egui::ComboBox::from_label("Overlap").selected_text(format!("{}x", state.options.spectrum.overlap)).show_ui(ui, |ui| {
+                            SPECTRUM_OVERLAPS.into_iter().fold(false, |changed, overlap| ui.selectable_value(&mut state.options.spectrum.overlap, overlap, format!("{overlap}x")).changed() || changed)
+                        }).inner.is_some_and(|i| i);
+                        let interval_label = |interval: u32| if interval == 0 { "Off".to_string() } else { format!("Every {interval} ms") };
+                        options_edited |= egui::ComboBox::from_label("Updates While Closed").selected_text(interval_label(state.options.spectrum.background_interval_ms)).show_ui(ui, |ui| {
+                            SPECTRUM_BACKGROUND_INTERVALS.into_iter().fold(false, |changed, interval| ui.selectable_value(&mut state.options.spectrum.background_interval_ms, interval, interval_label(interval)).changed() || changed)
+                        }).inner.is_some_and(|i| i);
+                    }
+
+                    if filter.shows(SettingsTab::Appearance, "Editor Size Default Window") {
+                        ui.heading("Editor");
+                        let size_label = |size: Option<(u32, u32)>| size.map_or_else(|| "Last Used".to_string(), |(width, height)| format!("{width}x{height}"));
+                        let size_changed = egui::ComboBox::from_label("Default Size").selected_text(size_label(state.options.default_size)).show_ui(ui, |ui| {
+                            std::iter::once(None).chain(EDITOR_SIZES.into_iter().map(Some)).fold(false, |changed, size| ui.selectable_value(&mut state.options.default_size, size, size_label(size)).changed() || changed)
+                        }).inner.is_some_and(|i| i);
+                        if size_changed {
+                            options_edited = true;
+                            // Picking a size resizes this editor too, so it can be seen right away
+                            if let Some(size) = state.options.default_size {
+                                params.editor_state.set_requested_size(size);
+                            }
+                        }
+                    }
+
+                    if options_edited {
+                        state.gradient = state.options.build_gradient();
+                        state.spectrum_settings.store(state.options.spectrum);
+                        if let Err(e) = files::write_atomic(&CONFIG_FILE, toml::to_string_pretty(&state.options).unwrap()) {
+                            state.config_io_error = Some(format!("Couldn't write config: {e:?}"));
+                        }
+                    }
+                });
+
+            let dropped_files = ctx.input(|input| input.raw.dropped_files.clone());
+            for path in dropped_files.into_iter().filter_map(|file| file.path) {
+                let message = load_dropped_tuning(&path, &params.scale, &mut state.tuning_paths);
+                state.toast = Some((message, Instant::now()));
+            }
+
+            if let Some((message, shown_at)) = &state.toast {
+                let age = shown_at.elapsed().as_secs_f32();
+                if age > TOAST_SECS {
+                    state.toast = None;
+                } else {
+                    egui::Area::new(egui::Id::new("toast"))
+                        .anchor(Align2::CENTER_BOTTOM, vec2(0.0, -40.0))
+                        .order(egui::Order::Foreground)
+                        .interactable(false)
+                        .show(ctx, |ui| {
+                            ui.set_opacity((TOAST_SECS - age).min(1.0));
+                            Frame::popup(ui.style()).show(ui, |ui| ui.label(message.as_str()));
+                        });
+                }
+            }
+
+            if state.confirm_above_nyquist {
+                Window::new("ALLOW FILTERS ABOVE NYQUIST")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                    .show(ctx, |ui| {
+                        ui.label("Filters at or above Nyquist alias back down into the audible range, and can get extremely loud without warning.");
+                        ui.label(RichText::new("Turn your speakers down before trying this.").color(Color32::RED).strong());
+                        ui.horizontal(|ui| {
+                            if ui.button("Allow").clicked() {
+                                setter.begin_set_parameter(&params.filter.safety_switch);
+                                setter.set_parameter(&params.filter.safety_switch, false);
+                                setter.end_set_parameter(&params.filter.safety_switch);
+                                state.confirm_above_nyquist = false;
+                                state.options.nyquist_warning_accepted = true;
+                                if let Err(e) = files::write_atomic(&CONFIG_FILE, toml::to_string_pretty(&state.options).unwrap()) {
+                                    state.config_io_error = Some(format!("Couldn't write config: {e:?}"));
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                state.confirm_above_nyquist = false;
+                            }
+                        });
+                    });
+            }
+        },
+    )
+}
+
+/// The frequency of the loudest bin in `bins` between `low` and `high` Hz, refined by fitting a
+/// parabola through it and its neighbours.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn find_peak(bins: &[f32], nyquist: f32, low: f32, high: f32) -> Option<f32> {
+    let bin_width = nyquist / bins.len() as f32;
+    let first = ((low / bin_width).floor() as usize).max(1);
+    let last = ((high / bin_width).ceil() as usize).min(bins.len().saturating_sub(2));
+    let peak = (first..=last).max_by(|&a, &b| bins[a].total_cmp(&bins[b]))?;
+
+    let [left, center, right] =
+        [bins[peak - 1], bins[peak], bins[peak + 1]].map(nih_plug::util::gain_to_db);
+    let curvature = left - 2.0 * center + right;
+    let offset = if curvature.abs() > f32::EPSILON {
+        (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    Some((peak as f32 + offset) * bin_width)
+}
+
+/// Detune whichever playing harmonic is closest to `peak` so it lands right on it, returning what
+/// to tell the user about it.
+fn capture_peak(
+    peak: f32,
+    filters: &FilterDisplay,
+    params: &ScaleColorizrParams,
+    setter: &ParamSetter,
+) -> String {
+    let closest = filters
+        .iter()
+        .flat_map(|voice| voice.filters.iter().enumerate())
+        .filter_map(|(idx, filter)| {
+            Some((idx, 1200.0 * (peak / filter.as_ref()?.frequency).log2()))
+        })
+        .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()));
+    let Some((idx, offset)) = closest else {
+        return "Play a note first, so there's a harmonic to move".to_string();
+    };
+
+    let detune = &params.detune[idx].cents;
+    let cents = (detune.value() + offset).clamp(-1200.0, 1200.0);
+    setter.begin_set_parameter(detune);
+    setter.set_parameter(detune, cents);
+    setter.end_set_parameter(detune);
+
+    format!(
+        "Detuned harmonic {} to {} to land on {peak:.1} Hz",
+        idx + 1,
+        detune.normalized_value_to_string(detune.preview_normalized(cents), true)
+    )
+}
+
+/// The amount to morph between the two morph presets. The parameters only get set once the drag
+/// is let go of or a value is typed in, so the host sees one change per parameter like it would
+/// for loading a preset.
+fn morph_drag(
+    ui: &mut Ui,
+    params: &ScaleColorizrParams,
+    setter: &ParamSetter,
+    locked: &BTreeSet<String>,
+) {
+    let performance = &params.performance;
+    let mut amount = *performance
+        .morph_amount
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    let response = ui
+        .horizontal(|ui| {
+            ui.label("MORPH");
+            ui.add(
+                DragValue::new(&mut amount)
+                    .range(0.0..=1.0)
+                    .speed(0.005)
+                    .custom_formatter(|value, _| format!("{:.0}%", value * 100.0))
+                    .custom_parser(|text| {
+                        text.trim()
+                            .trim_end_matches('%')
+                            .trim()
+                            .parse::<f64>()
+                            .ok()
+                            .map(|value| value / 100.0)
+                    }),
+            )
+        })
+        .inner;
+    help_overlay(
+        ui,
+        response.rect,
+        "Moves every continuous parameter from preset A to preset B, once the drag is let go of",
+    );
+
+    if response.changed() {
+        *performance
+            .morph_amount
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = amount;
+    }
+    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+        let morph_presets = performance
+            .morph_presets
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let [Some((_, from)), Some((_, to))] = &*morph_presets {
+            Preset::morph(from, to, amount, params, setter, locked);
+        }
+    }
+}
+
+/// Load a tuning file dropped on the editor into the selected tuning slot, returning what to tell
+/// the user about it.
+fn load_dropped_tuning(
+    path: &Path,
+    scale: &ScaleParams,
+    tuning_paths: &mut [String; NUM_TUNING_SLOTS],
+) -> String {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "scl" | "syx" => {}
+        "kbm" => {
+            return "Keyboard mappings aren't supported - scales always start on middle C"
+                .to_string()
+        }
+        _ => return format!("{} isn't a tuning file", path.display()),
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    let slot = scale.tuning_slot.value() as usize - 1;
+    match Tuning::load(path) {
+        Ok(tuning) => {
+            let mut message = format!(
+                "Loaded {} into slot {} - {} notes",
+                tuning.name,
+                slot + 1,
+                tuning.num_notes
+            );
+            if !tuning.description.is_empty() {
+                message.push_str(&format!("\n{}", tuning.description));
+            }
+            tuning_paths[slot] = path.display().to_string();
+            scale
+                .tunings
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[slot] = Some(tuning);
+            message
+        }
+        Err(e) => e,
+    }
+}
+
+fn draw_log_grid(ui: &Ui, rect: Rect, viewport: GraphViewport) {
+    let painter = ui.painter_at(rect);
+    // Zoomed in to less than a decade there may not be a power of ten on screen to label, so every
+    // line gets one instead
+    let label_every_line = viewport.log_max - viewport.log_min < 1.0;
+
+    let mut previous = 10.0;
+    for max in PowersOfTen::new(10.0, FREQ_RANGE_HIGHEST_HZ * 10.0) {
         #[allow(clippy::cast_sign_loss)]
         for freq in (previous as i32..=max as i32).step_by(max as usize / 10) {
-            let freq = freq.max(20) as f32;
-            let x = ((freq.log10() - log_min) * (rect.width() - 1.0)) / (log_max - log_min)
-                + rect.left();
-            let x2 = (((freq - (max / 20.0)).log10() - log_min) * (rect.width() - 1.0))
-                / (log_max - log_min)
-                + rect.left();
+            let freq = freq as f32;
+            let x = viewport.x(rect, freq);
+            let x2 = viewport.x(rect, freq - (max / 20.0));
             painter.vline(
                 x,
                 rect.y_range(),
@@ -529,12 +2250,12 @@ fn draw_log_grid(ui: &Ui, rect: Rect) {
 
             #[allow(clippy::float_cmp)]
             // in testing this hasn't blown up, but this is a culprit if any flickering or random dissapearing is reported
-            if freq == max {
+            if freq == max || label_every_line {
                 painter.text(
                     pos2(x + 5.0, rect.bottom() - 10.0),
                     Align2::LEFT_CENTER,
                     if freq >= 1000.0 {
-                        format!("{:.0}k", max / 1000.0)
+                        format!("{:.0}k", freq / 1000.0)
                     } else {
                         format!("{freq:.0}")
                     },
@@ -553,16 +2274,473 @@ fn draw_log_grid(ui: &Ui, rect: Rect) {
     }
 }
 
+/// The frequency under the cursor, and the closest note to it with the current tuning slot, so
+/// what's on the graph lines up with what the filters play even outside of 12-TET.
+fn draw_cursor_readout(
+    ui: &Ui,
+    rect: Rect,
+    viewport: GraphViewport,
+    pos: Pos2,
+    scale: &ScaleParams,
+) {
+    let painter = ui.painter_at(rect);
+    let freq = viewport.freq(rect, pos.x);
+    let (note, cents) = scale.nearest_note(freq);
+    painter.vline(
+        pos.x,
+        rect.y_range(),
+        Stroke::new(1.0, Color32::GRAY.gamma_multiply(0.5)),
+    );
+    painter.text(
+        pos2(rect.right() - 5.0, rect.top() + 10.0),
+        Align2::RIGHT_CENTER,
+        format!("{freq:.1} Hz  {}{cents:+.0}c", note_name(note)),
+        FontId::new(10.0, egui::FontFamily::Name("0x".into())),
+        Color32::GRAY,
+    );
+}
+
+/// A strip of the limiter's gain reduction scrolling from right to left, newest on the right.
+fn draw_gain_reduction(ui: &Ui, history: &VecDeque<f32>) {
+    let rect = ui.available_rect_before_wrap();
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, Rounding::same(2.0), Color32::from_gray(20));
+
+    #[allow(clippy::cast_precision_loss)]
+    let bar_width = rect.width() / GAIN_REDUCTION_HISTORY_LEN as f32;
+    let offset = GAIN_REDUCTION_HISTORY_LEN - history.len();
+    for (idx, reduction) in history.iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let x = ((offset + idx) as f32).mul_add(bar_width, rect.left());
+        let depth = remap_clamp(
+            *reduction,
+            0.0..=GAIN_REDUCTION_RANGE_DB,
+            0.0..=rect.height(),
+        );
+        painter.rect_filled(
+            Rect::from_min_size(pos2(x, rect.top()), vec2(bar_width, depth)),
+            Rounding::ZERO,
+            Color32::RED.gamma_multiply(0.6),
+        );
+    }
+
+    let current = history.back().copied().unwrap_or_default();
+    painter.text(
+        rect.left_center() + vec2(4.0, 0.0),
+        Align2::LEFT_CENTER,
+        format!("GR {current:.1} dB"),
+        FontId::new(10.0, egui::FontFamily::Name("0x".into())),
+        Color32::GRAY,
+    );
+    help_overlay(
+        ui,
+        rect,
+        "How far the ceiling has been turning the output down lately, newest on the right",
+    );
+}
+
+/// The voice count, with buttons to step it up and down and how many voices are playing right
+/// now.
+/// Returns whether the usage readout was clicked, to open the voice grid.
+fn voice_stepper(ui: &mut Ui, setter: &ParamSetter, voice_count: &IntParam, in_use: usize) -> bool {
+    let mut clicked = false;
+    let response = ui
+        .vertical(|ui| {
+            ui.label("VOICES");
+            ui.horizontal(|ui| {
+                let value = voice_count.value();
+                let step = |ui: &mut Ui, label: &str, amount: i32| {
+                    if ui.small_button(label).clicked() {
+                        setter.begin_set_parameter(voice_count);
+                        setter.set_parameter(voice_count, value + amount);
+                        setter.end_set_parameter(voice_count);
+                    }
+                };
+                step(ui, "-", -1);
+                let color = if in_use >= usize::try_from(value).unwrap_or_default() {
+                    HIGHLIGHT_COL32
+                } else {
+                    Color32::GRAY
+                };
+                clicked = ui
+                    .add(
+                        Label::new(RichText::new(format!("{in_use} / {value}")).color(color))
+                            .sense(Sense::click()),
+                    )
+                    .on_hover_text(
+                        "Voices playing out of the most that can play at once. Click to see them",
+                    )
+                    .clicked();
+                step(ui, "+", 1);
+            });
+        })
+        .response;
+    help_overlay(
+        ui,
+        response.rect,
+        "How many notes can play at once. The oldest voice gets stolen once they're all in use",
+    );
+    clicked
+}
+
+/// Like C4, with C4 being note 60.
+fn note_name(note: u8) -> String {
+    format!(
+        "{}{}",
+        nih_plug::util::NOTES[usize::from(note % 12)],
+        i32::from(note / 12) - 1
+    )
+}
+
+/// One cell for each of the first `voice_count` voices, playing ones first. Each shows the note,
+/// how long it's been playing, and its envelope level as a bar in the note's gradient color.
+fn voice_grid(ui: &mut Ui, voices: &FilterDisplay, voice_count: usize, gradient: &CachedGradient) {
+    const CELL_SIZE: Vec2 = vec2(56.0, 40.0);
+    const COLUMNS: usize = 8;
+
+    let mut playing: Vec<_> = voices
+        .iter()
+        .filter_map(|voice| voice.envelope.map(|envelope| (voice, envelope)))
+        .collect();
+    playing.sort_by(|(a, _), (b, _)| b.age.total_cmp(&a.age));
+
+    Grid::new("voice_grid")
+        .spacing(vec2(4.0, 4.0))
+        .show(ui, |ui| {
+            for idx in 0..voice_count.max(playing.len()) {
+                let (rect, response) = ui.allocate_exact_size(CELL_SIZE, Sense::hover());
+                let painter = ui.painter_at(rect);
+                painter.rect_stroke(rect, 2.0, Stroke::new(1.0, Color32::DARK_GRAY));
+
+                if let Some((voice, envelope)) = playing.get(idx) {
+                    let color = gradient.at(f32::from(voice.note) / 127.0).to_rgba8();
+                    let color = Color32::from_rgb(color[0], color[1], color[2]);
+                    let level = envelope.value.clamp(0.0, 1.0);
+                    let bar = Rect::from_min_max(
+                        pos2(rect.left(), rect.bottom() - rect.height() * level),
+                        rect.max,
+                    );
+                    painter.rect_filled(bar, 2.0, color.gamma_multiply(0.6));
+                    painter.text(
+                        rect.center_top() + vec2(0.0, 4.0),
+                        Align2::CENTER_TOP,
+                        note_name(voice.note),
+                        FontId::proportional(14.0),
+                        Color32::WHITE,
+                    );
+                    painter.text(
+                        rect.center_bottom() - vec2(0.0, 4.0),
+                        Align2::CENTER_BOTTOM,
+                        format!("{:.1}s", voice.age),
+                        FontId::proportional(11.0),
+                        Color32::LIGHT_GRAY,
+                    );
+                    response.on_hover_text(format!("Envelope level {:.0}%", level * 100.0));
+                }
+
+                if (idx + 1) % COLUMNS == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+}
+
+/// Starts and stops recording the incoming MIDI, saving it under [`RECORDINGS_DIR`] when it stops.
+fn midi_recording(
+    ui: &mut Ui,
+    recorder: &MidiRecorder,
+    async_executor: &AsyncExecutor<ScaleColorizr>,
+    sample_rate: f32,
+) {
+    let recording = recorder.active.load(std::sync::atomic::Ordering::Relaxed);
+    ui.horizontal(|ui| {
+        if recording {
+            if ui.button("STOP & SAVE").clicked() {
+                recorder.stop();
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs());
+                async_executor.execute_background(BackgroundTask::SaveMidiRecording {
+                    path: RECORDINGS_DIR.join(format!("recording-{timestamp}.mid")),
+                    sample_rate,
+                });
+            }
+        } else if ui.button("RECORD").clicked() {
+            recorder.start();
+        }
+        ui.label(format!("{} EVENTS", recorder.num_events()));
+    });
+    match recorder.last_save() {
+        Some(Ok(path)) => {
+            ui.label(format!("Saved to {}", path.display()));
+        }
+        Some(Err(e)) => {
+            ui.colored_label(Color32::RED, e);
+        }
+        None => {}
+    }
+}
+
+/// Flashes red when a voice gets stolen, fading back to gray over a second. Hovering it shows how
+/// many have been stolen since playback started.
+fn steal_indicator(ui: &mut Ui, voices_stolen: u32, last_steal: Option<Instant>) {
+    let flash = last_steal.map_or(0.0, |last| 1.0 - last.elapsed().as_secs_f32().min(1.0));
+    let color = Color32::from_gray(90).lerp_to_gamma(Color32::RED, flash);
+    ui.label(RichText::new("STEAL").color(color))
+        .on_hover_text(format!(
+        "{voices_stolen} voices stolen since playback started. If this keeps going up, raise Voices"
+    ));
+}
+
+/// A bar from -1 to 1 with the output's stereo correlation marked on it. It turns red once the
+/// channels start cancelling each other out.
+fn correlation_meter(ui: &mut Ui, correlation: f32) {
+    let (rect, response) = ui.allocate_exact_size(vec2(60.0, 10.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, Rounding::same(2.0), Color32::DARK_GRAY);
+    painter.vline(
+        rect.center().x,
+        rect.y_range(),
+        Stroke::new(1.0, Color32::GRAY),
+    );
+
+    let x = remap(correlation, -1.0..=1.0, rect.x_range());
+    let color = if correlation < 0.0 {
+        Color32::RED
+    } else {
+        HIGHLIGHT_COL32
+    };
+    painter.rect_filled(
+        Rect::from_x_y_ranges(x - 1.5..=x + 1.5, rect.y_range()),
+        Rounding::ZERO,
+        color,
+    );
+
+    response.on_hover_text(format!("Stereo correlation {correlation:.2}"));
+    help_overlay(
+        ui,
+        rect,
+        "How alike the two channels are. Below the middle they start cancelling out when summed to mono",
+    );
+}
+
+/// Draw the latest stretch of the scope, starting from the last rising zero crossing that still
+/// leaves enough after it to fill the graph. Without a trigger point the waveform would crawl
+/// along every frame.
+fn draw_scope(ui: &Ui, rect: Rect, scope: &Scope, show_dry: bool, points: &mut Vec<Pos2>) {
+    /// How many samples fit across the graph.
+    const DISPLAY_LEN: usize = 1024;
+
+    let painter = ui.painter_at(rect);
+    painter.hline(
+        rect.x_range(),
+        rect.center().y,
+        Stroke::new(1.0, Color32::DARK_GRAY.gamma_multiply(0.5)),
+    );
+
+    let latest_start = SCOPE_LEN - DISPLAY_LEN;
+    let start = (1..=latest_start)
+        .rev()
+        .find(|&idx| scope.wet[idx - 1] < 0.0 && scope.wet[idx] >= 0.0)
+        .unwrap_or(latest_start);
+
+    let mut draw = |samples: &[f32], color: Color32| {
+        points.clear();
+        #[allow(clippy::cast_precision_loss)]
+        points.extend(samples[start..start + DISPLAY_LEN].iter().enumerate().map(
+            |(idx, sample)| {
+                pos2(
+                    remap(idx as f32, 0.0..=(DISPLAY_LEN - 1) as f32, rect.x_range()),
+                    remap_clamp(*sample, -1.0..=1.0, rect.bottom_up_range()),
+                )
+            },
+        ));
+        painter.add(PathShape::line(points.clone(), Stroke::new(1.5, color)));
+    };
+    if show_dry {
+        draw(&scope.dry, Color32::GRAY.gamma_multiply(0.5));
+    }
+    draw(&scope.wet, HIGHLIGHT_COL32);
+}
+
+/// List where the filters the safety switch skipped would have been down the graph's right edge,
+/// since they're almost always past the end of it. The list is cut short once it runs out of room.
+/// Draw the amplitude envelope's shape in the top left corner of `rect`, with a dot for every voice
+/// playing. Returns where it went.
+fn draw_envelope(
+    ui: &Ui,
+    rect: Rect,
+    envelope: &EnvelopeParams,
+    filter_mode: &EnumParam<FilterMode>,
+    filters: &FilterDisplay,
+    points: &mut Vec<Pos2>,
+) -> Rect {
+    /// How many points each segment gets drawn with.
+    const SEGMENT_POINTS: usize = 16;
+    /// The share of the width the sustain gets, since it doesn't have a length of its own.
+    const SUSTAIN_WIDTH: f32 = 0.3;
+    /// The least of the width any segment gets, so short ones can still be seen.
+    const MIN_WIDTH: f32 = 0.05;
+
+    let filter_mode = filter_mode.value();
+    let attack = envelope.active_attack(filter_mode).value();
+    let release = envelope.active_release(filter_mode).value();
+    let attack_shape = envelope.attack_shape.value();
+    let release_shape = envelope.release_shape.value();
+    // The stage, its length in ms, where it starts and ends, and its shape
+    let segments: [(EnvelopeStage, f32, f32, f32, f32); 3] = match envelope.mode.value() {
+        EnvelopeMode::Sustain => [
+            (EnvelopeStage::Attack, attack, 0.0, 1.0, attack_shape),
+            (EnvelopeStage::Sustain, 0.0, 1.0, 1.0, 0.0),
+            (EnvelopeStage::Release, release, 1.0, 0.0, release_shape),
+        ],
+        EnvelopeMode::OneShot => [
+            (EnvelopeStage::Attack, attack, 0.0, 1.0, attack_shape),
+            (EnvelopeStage::Hold, envelope.hold.value(), 1.0, 1.0, 0.0),
+            (
+                EnvelopeStage::Release,
+                envelope.decay.value(),
+                1.0,
+                0.0,
+                release_shape,
+            ),
+        ],
+        EnvelopeMode::SidechainTrigger => [
+            (EnvelopeStage::Attack, attack, 0.0, 1.0, attack_shape),
+            (EnvelopeStage::Hold, envelope.hold.value(), 1.0, 1.0, 0.0),
+            (
+                EnvelopeStage::Decay,
+                envelope.decay.value(),
+                1.0,
+                0.0,
+                release_shape,
+            ),
+        ],
+    };
+
+    let total_ms = segments
+        .iter()
+        .map(|segment| segment.1)
+        .sum::<f32>()
+        .max(1.0);
+    let widths = segments.map(|(stage, time_ms, ..)| {
+        if stage == EnvelopeStage::Sustain {
+            SUSTAIN_WIDTH
+        } else {
+            (time_ms / total_ms).max(MIN_WIDTH)
+        }
+    });
+    let total_width: f32 = widths.iter().sum();
+
+    let inset = Rect::from_min_size(rect.left_top() + vec2(8.0, 8.0), vec2(160.0, 56.0));
+    let plot = inset.shrink(4.0);
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(inset, Rounding::same(3.0), Color32::from_black_alpha(160));
+    let to_pos = |x: f32, value: f32| {
+        pos2(
+            remap(x, 0.0..=total_width, plot.x_range()),
+            remap(value, 0.0..=1.0, plot.bottom_up_range()),
+        )
+    };
+
+    points.clear();
+    let mut segment_start = 0.0;
+    for (&(_, _, start, end, shape), width) in segments.iter().zip(widths) {
+        #[allow(clippy::cast_precision_loss)]
+        points.extend((0..=SEGMENT_POINTS).map(|idx| {
+            let t = idx as f32 / SEGMENT_POINTS as f32;
+            to_pos(
+                t.mul_add(width, segment_start),
+                (end - start).mul_add(crate::envelope::curve(t, shape), start),
+            )
+        }));
+        segment_start += width;
+    }
+    painter.add(PathShape::line(
+        points.clone(),
+        Stroke::new(1.5, Color32::GRAY),
+    ));
+
+    for position in filters.iter().filter_map(|voice| voice.envelope) {
+        // Releasing early out of a sidechain trigger's hold still ends up at the last segment
+        let Some(idx) = segments
+            .iter()
+            .position(|segment| segment.0 == position.stage)
+            .or_else(|| (position.stage == EnvelopeStage::Release).then_some(segments.len() - 1))
+        else {
+            continue;
+        };
+        let start = widths[..idx].iter().sum::<f32>();
+        painter.circle_filled(
+            to_pos(
+                position.progress.mul_add(widths[idx], start),
+                position.value,
+            ),
+            3.0,
+            HIGHLIGHT_COL32,
+        );
+    }
+
+    inset
+}
+
+fn draw_skipped_filters(ui: &Ui, rect: Rect, filters: &FilterDisplay, frequencies: &mut Vec<f32>) {
+    const LINE_HEIGHT: f32 = 12.0;
+
+    frequencies.clear();
+    frequencies.extend(
+        filters
+            .iter()
+            .flat_map(|voice| voice.skipped.iter().flatten()),
+    );
+    if frequencies.is_empty() {
+        return;
+    }
+    frequencies.sort_unstable_by(f32::total_cmp);
+    // Voices playing the same note skip the same filters
+    frequencies.dedup_by(|a, b| (*a - *b).abs() < 1.0);
+
+    let painter = ui.painter_at(rect);
+    let color = Color32::GRAY.gamma_multiply(0.4);
+    let font = FontId::new(10.0, egui::FontFamily::Name("0x".into()));
+    painter.vline(
+        rect.right() - 1.0,
+        rect.y_range(),
+        Stroke::new(2.0, color.gamma_multiply(0.5)),
+    );
+
+    let mut pos = rect.right_top() + vec2(-6.0, 6.0);
+    painter.text(pos, Align2::RIGHT_TOP, "ABOVE NYQUIST", font.clone(), color);
+    #[allow(clippy::cast_sign_loss)]
+    let max_lines = ((rect.height() - LINE_HEIGHT) / LINE_HEIGHT) as usize;
+    for (idx, frequency) in frequencies.iter().enumerate() {
+        pos.y += LINE_HEIGHT;
+        if idx + 2 >= max_lines && idx + 1 < frequencies.len() {
+            painter.text(pos, Align2::RIGHT_TOP, "...", font, color);
+            break;
+        }
+        painter.text(
+            pos,
+            Align2::RIGHT_TOP,
+            format!("{:.1}k", frequency / 1000.0),
+            font.clone(),
+            color,
+        );
+    }
+}
+
 fn draw_spectrum(
     ui: &Ui,
     rect: Rect,
+    viewport: GraphViewport,
     spectrum: &mut SpectrumOutput,
+    points: &mut Vec<Pos2>,
     sample_rate: &AtomicF32,
     color: Color32,
 ) {
     let painter = ui.painter_at(rect);
 
-    let spectrum_data = spectrum.read();
+    let spectrum_data = spectrum.read().bins();
     let nyquist = sample_rate.load(std::sync::atomic::Ordering::Relaxed) / 2.0;
 
     let bin_freq = |bin_idx: f32| (bin_idx / spectrum_data.len() as f32) * nyquist;
@@ -570,36 +2748,39 @@ fn draw_spectrum(
         let magnitude_db = nih_plug::util::gain_to_db(magnitude);
         (magnitude_db + 80.0) / 100.0
     };
-    let bin_t = |bin_idx: f32| {
-        (bin_freq(bin_idx).log10() - FREQ_RANGE_START_HZ.log10())
-            / (FREQ_RANGE_END_HZ.log10() - FREQ_RANGE_START_HZ.log10())
-    };
+    let bin_t = |bin_idx: f32| viewport.t(bin_freq(bin_idx));
 
-    let points: Vec<Pos2> = spectrum_data
-        .iter()
-        .enumerate()
-        .filter_map(|(idx, magnitude)| {
-            let t = bin_t(idx as f32).max(0.0);
+    points.clear();
+    points.extend(
+        spectrum_data
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, magnitude)| {
+                let t = bin_t(idx as f32).max(0.0);
 
-            if t > 1.0 {
-                return None;
-            }
+                if t > 1.0 {
+                    return None;
+                }
 
-            let x_coord = rect.lerp_inside(vec2(t, 0.0)).x;
+                let x_coord = rect.lerp_inside(vec2(t, 0.0)).x;
 
-            let height = magnitude_height(*magnitude);
+                let height = magnitude_height(*magnitude);
 
-            Some(pos2(
-                x_coord,
-                rect.height().mul_add(1.0 - height, rect.top()),
-            ))
-        })
-        .collect();
+                Some(pos2(
+                    x_coord,
+                    rect.height().mul_add(1.0 - height, rect.top()),
+                ))
+            }),
+    );
 
     let color_bg = color.gamma_multiply(0.25);
 
-    for [left, right] in points.array_windows() {
-        let mut mesh = Mesh::default();
+    // everything goes into one mesh, one mesh per quad was a lot of tiny allocations
+    let mut mesh = Mesh::default();
+    mesh.reserve_vertices(points.len().saturating_sub(1) * 4);
+    mesh.reserve_triangles(points.len().saturating_sub(1) * 2);
+    for (left, right) in points.iter().zip(points.iter().skip(1)) {
+        let idx = mesh.vertices.len() as u32;
         mesh.colored_vertex(*left, color_bg);
         mesh.colored_vertex(*right, color_bg);
 
@@ -609,55 +2790,53 @@ fn draw_spectrum(
         mesh.colored_vertex(bottom_right, color_bg);
         mesh.colored_vertex(bottom_left, color_bg);
 
-        mesh.add_triangle(0, 1, 2);
-        mesh.add_triangle(3, 2, 0);
-
-        painter.add(mesh);
+        mesh.add_triangle(idx, idx + 1, idx + 2);
+        mesh.add_triangle(idx + 3, idx + 2, idx);
     }
+    painter.add(mesh);
 
-    painter.add(PathShape::line(points, Stroke::new(1.5, color)));
+    painter.add(PathShape::line(points.clone(), Stroke::new(1.5, color)));
 }
 
-fn draw_filter_line<G: Gradient + Sync + Send + 'static>(
+#[allow(clippy::too_many_arguments)]
+fn draw_filter_line(
     ui: &Ui,
     rect: Rect,
-    biquads: &Arc<FilterDisplay>,
-    gradient: G,
-) {
-    static ANIMATE_NOISE: Lazy<Perlin> = Lazy::new(|| Perlin::new(rand::random()));
-
+    viewport: GraphViewport,
+    filters: &FilterDisplay,
+    buffers: &mut GraphBuffers,
+    noise: &LineNoise,
+    sample_rate: &AtomicF32,
+    gradient: CachedGradient,
+) -> f32 {
     let painter = ui.painter_at(rect);
 
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-    let mut points = Vec::with_capacity(rect.width().round() as usize);
-    #[allow(clippy::cast_sign_loss)]
-    let mut sampled_frequencies = Vec::with_capacity(rect.width().round() as usize);
-
-    let active_biquads: Vec<GenericSVF<_>> = biquads
-        .iter()
-        .flatten()
-        .filter_map(AtomicCell::load)
-        .collect();
-
-    let is_active = !active_biquads.is_empty();
+    let GraphBuffers {
+        points,
+        sampled_frequencies,
+        active_filters,
+    } = buffers;
+    points.clear();
+    sampled_frequencies.clear();
+    active_filters.clear();
+    active_filters.extend(
+        filters
+            .iter()
+            .flat_map(|voice| voice.filters.iter().flatten()),
+    );
 
-    let log_min = FREQ_RANGE_START_HZ.log10();
-    let log_max = FREQ_RANGE_END_HZ.log10();
+    let sample_rate = sample_rate.load(std::sync::atomic::Ordering::Relaxed);
 
     #[allow(clippy::cast_possible_truncation)]
     for i in rect.left() as i32..=rect.right() as i32 {
         let x = i as f32;
-        let freq = (log_min.mul_add(
-            rect.left() + rect.width() - x - 1.0,
-            log_max * (x - rect.left()),
-        ) / ((rect.width() - 1.0) * E.log10()))
-        .exp();
+        let freq = viewport.freq(rect, x);
 
         sampled_frequencies.push(freq);
 
-        let result = active_biquads
+        let result = active_filters
             .iter()
-            .map(|biquad| biquad.frequency_response(freq))
+            .map(|filter| filter_response(filter, freq, sample_rate))
             .fold(Complex32::new(1.0, 0.0), |acc, resp| acc * resp);
 
         points.push(Pos2::new(
@@ -670,26 +2849,24 @@ fn draw_filter_line<G: Gradient + Sync + Send + 'static>(
         ));
     }
 
-    ui.memory_mut(|m| {
-        m.data
-            .insert_temp("sampled_frequencies".into(), sampled_frequencies);
-    });
-
     // DISGUSTING: i would MUCH rather meshify the line so i can apply shaders
     // but i couldn't get it to work, so i'm doing this terribleness instead.
-    let animation_position = ui.ctx().frame_nr() as f64 * 0.005;
-    let offset = ANIMATE_NOISE.get([animation_position * 0.01, 0.0]);
-    let interpolate = ui.ctx().animate_bool("active".into(), is_active);
-    ui.memory_mut(|m| m.data.insert_temp("active_amt".into(), interpolate));
+    let animation_position = noise.position;
+    let noise_scale = noise.scale;
+    let offset = noise.animate.get([animation_position * 0.01, 0.0]);
+    let line_noise = noise.line;
+    let interpolate = noise.active;
 
     painter.add(PathShape::line(
-        points,
+        points.clone(),
         PathStroke::new_uv(3.0, move |bounds, pos| {
-            static NOISE: Lazy<OpenSimplex> = Lazy::new(|| OpenSimplex::new(rand::random()));
-
             let noise_value = remap(
-                NOISE.get([
-                    f64::from(remap_clamp(pos.x, bounds.x_range(), 0.0..=1.5)),
+                line_noise.get([
+                    f64::from(remap_clamp(
+                        pos.x,
+                        bounds.x_range(),
+                        0.0..=1.5 / noise_scale,
+                    )),
                     animation_position + offset,
                 ]) as f32,
                 -0.5..=0.5,
@@ -704,6 +2881,95 @@ fn draw_filter_line<G: Gradient + Sync + Send + 'static>(
             Color32::from_rgba_premultiplied(color[0], color[1], color[2], color[3])
         }),
     ));
+
+    interpolate
+}
+
+/// The response of a filter at `freq`. The SVF is a bilinear transform of the analog prototype
+/// prewarped to the filter's frequency, so this just evaluates that prototype at the warped point.
+fn filter_response(filter: &FilterDescriptor, freq: f32, sample_rate: f32) -> Complex32 {
+    let warp = |freq: f32| (PI * freq / sample_rate).tan();
+    let s = Complex32::new(0.0, warp(freq) / warp(filter.frequency));
+
+    let response = match filter.mode {
+        FilterMode::Peak | FilterMode::Morph => {
+            let a = 10.0f32.powf(filter.gain / 40.0);
+            let k = (filter.q * a).recip();
+            (s * s + s * (k * a * a) + 1.0) / (s * s + s * k + 1.0)
+        }
+        FilterMode::Notch => {
+            let k = filter.q.recip();
+            (s * s + 1.0) / (s * s + s * k + 1.0)
+        }
+    };
+
+    #[allow(clippy::cast_possible_truncation)]
+    response.powu(filter.stages as u32)
+}
+
+/// A curve over harmonic index with a draggable point for every harmonic's level.
+fn falloff_editor(ui: &mut Ui, falloff: &[FalloffParams; NUM_FILTERS], setter: &ParamSetter) {
+    const POINT_RADIUS: f32 = 5.0;
+
+    let (rect, _) = ui.allocate_exact_size(vec2(300.0, 120.0), Sense::hover());
+    let rect = rect.shrink(POINT_RADIUS);
+    let painter = ui.painter_at(rect.expand(POINT_RADIUS));
+    painter.rect_filled(rect.expand(POINT_RADIUS), 5.0, Color32::from_gray(20));
+
+    #[allow(clippy::cast_precision_loss)]
+    let points: [Pos2; NUM_FILTERS] = std::array::from_fn(|idx| {
+        pos2(
+            remap(idx as f32, 0.0..=(NUM_FILTERS - 1) as f32, rect.x_range()),
+            remap(
+                falloff[idx].level.unmodulated_normalized_value(),
+                0.0..=1.0,
+                rect.bottom_up_range(),
+            ),
+        )
+    });
+    painter.add(PathShape::line(
+        points.to_vec(),
+        Stroke::new(1.5, Color32::WHITE),
+    ));
+
+    for ((idx, harmonic), point) in falloff.iter().enumerate().zip(points) {
+        let level = &harmonic.level;
+        let response = ui
+            .interact(
+                Rect::from_center_size(point, Vec2::splat(POINT_RADIUS * 3.0)),
+                ui.id().with(("falloff", idx)),
+                Sense::click_and_drag(),
+            )
+            .on_hover_text(format!("{}: {}", level.name(), level));
+
+        if response.double_clicked() {
+            setter.begin_set_parameter(level);
+            setter.set_parameter(level, level.default_plain_value());
+            setter.end_set_parameter(level);
+        }
+        if response.drag_started() {
+            setter.begin_set_parameter(level);
+        }
+        if let Some(pointer) = response
+            .interact_pointer_pos()
+            .filter(|_| response.dragged())
+        {
+            setter.set_parameter_normalized(
+                level,
+                remap_clamp(pointer.y, rect.bottom_up_range(), 0.0..=1.0),
+            );
+        }
+        if response.drag_stopped() {
+            setter.end_set_parameter(level);
+        }
+
+        let color = if response.hovered() || response.dragged() {
+            HIGHLIGHT_COL32
+        } else {
+            Color32::WHITE
+        };
+        painter.circle_filled(point, POINT_RADIUS, color);
+    }
 }
 
 fn switch<T: Enum + PartialEq>(ui: &mut Ui, param: &EnumParam<T>, setter: &ParamSetter) {
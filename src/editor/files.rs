@@ -0,0 +1,81 @@
+//! Writing the config and presets so a crash or power cut part way through can't leave them
+//! truncated. Files get written next to where they're going and then renamed into place, with the
+//! previous version kept around as a backup.
+
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Goes into the temp file names along with the process ID, so two instances saving the same
+/// file at once don't write over each other's temp files.
+static NEXT_TEMP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the file at `path` with `contents`. Whatever was there before is kept as a `.bak` next
+/// to it, and the directory is created if it has to be.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let temp_id = NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    let temp_path = with_suffix(path, &format!("{}-{temp_id}.tmp", std::process::id()));
+    let result = write_and_swap(path, &temp_path, contents.as_ref());
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn write_and_swap(path: &Path, temp_path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    // Copied rather than renamed, so there's never a moment where `path` is missing
+    match fs::copy(path, with_suffix(path, "bak")) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+
+    fs::rename(temp_path, path)
+}
+
+/// `path` with `.suffix` added on after its extension, so it doesn't get mistaken for the real
+/// thing.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_and_keeps_a_backup() {
+        let dir = std::env::temp_dir().join(format!("write-atomic-test-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let _ = fs::remove_dir_all(&dir);
+
+        write_atomic(&path, "first").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        assert!(!with_suffix(&path, "bak").exists());
+
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(
+            fs::read_to_string(with_suffix(&path, "bak")).unwrap(),
+            "first"
+        );
+
+        // Nothing but the file and its backup should be left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Presets are snapshots of the automatable parameters, stored as TOML files next to the config.
+//! They're normally loaded through the editor, which can tell the host about the new values.
+
+use super::files::write_atomic;
+use super::{lock, CONFIG_DIR};
+use nih_plug::context::gui::ParamSetter;
+use nih_plug::params::internals::ParamPtr;
+use nih_plug::params::{ParamFlags, Params};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub static PRESET_DIR: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("presets"));
+/// The preset new instances start from. This lives outside [`PRESET_DIR`] so it doesn't get a
+/// program number.
+static DEFAULT_PRESET_FILE: Lazy<PathBuf> = Lazy::new(|| CONFIG_DIR.join("default.toml"));
+const PRESET_EXTENSION: &str = "toml";
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Preset {
+    /// Normalized parameter values, keyed by parameter ID.
+    pub params: BTreeMap<String, f32>,
+}
+
+/// A preset file found in [`PRESET_DIR`]. Presets are only read when they're loaded.
+#[derive(Clone)]
+pub struct PresetEntry {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Preset {
+    /// Take a snapshot of every parameter that's exposed to the host. Hidden and non-automatable
+    /// parameters are settings rather than part of the sound, so they're left alone.
+    pub fn capture(params: &impl Params) -> Self {
+        Self {
+            params: params
+                .param_map()
+                .into_iter()
+                // SAFETY: the pointers come straight from the params object, which outlives this
+                .filter(|(_, ptr, _)| !is_setting(unsafe { ptr.flags() }))
+                .map(|(id, ptr, _)| (id, unsafe { ptr.unmodulated_normalized_value() }))
+                .collect(),
+        }
+    }
+
+    /// Set every parameter this preset has a value for, except for the ones in `locked`
+    /// categories. Parameters the preset doesn't know about, e.g. because it was saved with an
+    /// older version, keep their current value.
+    pub fn apply(&self, params: &impl Params, setter: &ParamSetter, locked: &BTreeSet<String>) {
+        for (id, ptr, group) in params.param_map() {
+            // SAFETY: see above
+            if is_setting(unsafe { ptr.flags() }) || locked.contains(category(&group)) {
+                continue;
+            }
+
+            if let Some(value) = self.params.get(&id) {
+                setter.raw_context.raw_begin_set_parameter(ptr);
+                setter
+                    .raw_context
+                    .raw_set_parameter_normalized(ptr, value.clamp(0.0, 1.0));
+                setter.raw_context.raw_end_set_parameter(ptr);
+            }
+        }
+    }
+
+    /// Set every continuous parameter `amount` of the way from `a` to `b`. Switches and other
+    /// stepped parameters are left alone, as are parameters only one of the presets knows about and
+    /// ones in `locked` categories.
+    pub fn morph(
+        a: &Self,
+        b: &Self,
+        amount: f32,
+        params: &impl Params,
+        setter: &ParamSetter,
+        locked: &BTreeSet<String>,
+    ) {
+        for (id, ptr, group) in params.param_map() {
+            // SAFETY: see above
+            if !matches!(ptr, ParamPtr::FloatParam(_))
+                || is_setting(unsafe { ptr.flags() })
+                || locked.contains(category(&group))
+            {
+                continue;
+            }
+
+            if let (Some(from), Some(to)) = (a.params.get(&id), b.params.get(&id)) {
+                setter.raw_context.raw_begin_set_parameter(ptr);
+                setter.raw_context.raw_set_parameter_normalized(
+                    ptr,
+                    (to - from).mul_add(amount, *from).clamp(0.0, 1.0),
+                );
+                setter.raw_context.raw_end_set_parameter(ptr);
+            }
+        }
+    }
+
+    /// Set the parameters straight away, without going through the host. This is only sound
+    /// before the plugin has been handed to the host, since nothing else can be looking at the
+    /// parameters then.
+    pub fn apply_before_init(&self, params: &impl Params) {
+        for (id, ptr, _) in params.param_map() {
+            // SAFETY: see above
+            if is_setting(unsafe { ptr.flags() }) {
+                continue;
+            }
+
+            if let Some(value) = self.params.get(&id) {
+                // SAFETY: nothing else has the parameters yet
+                unsafe { ptr.set_normalized_value(value.clamp(0.0, 1.0)) };
+            }
+        }
+    }
+
+    /// Set the parameters straight away, for program changes that come in while there's no editor
+    /// to go through. The host isn't told about the new values, so it can keep showing the old
+    /// ones until it reads them again. The smoothers glide to the new values at `sample_rate` like
+    /// they would for automation.
+    pub fn apply_without_editor(
+        &self,
+        params: &impl Params,
+        locked: &BTreeSet<String>,
+        sample_rate: f32,
+    ) {
+        for (id, ptr, group) in params.param_map() {
+            // SAFETY: see above
+            if is_setting(unsafe { ptr.flags() }) || locked.contains(category(&group)) {
+                continue;
+            }
+
+            if let Some(value) = self.params.get(&id) {
+                // SAFETY: the values and smoothers are atomics, so the audio thread can keep
+                // reading them while they change
+                unsafe {
+                    ptr.set_normalized_value(value.clamp(0.0, 1.0));
+                    ptr.update_smoother(sample_rate, false);
+                }
+            }
+        }
+    }
+
+    /// The preset set as the default, if there is one. A broken default is treated as not having
+    /// one, since there's nowhere to report it while the plugin is being created.
+    pub fn load_default() -> Option<Self> {
+        Self::load(&DEFAULT_PRESET_FILE).ok()
+    }
+
+    pub fn save_as_default(&self) -> Result<(), String> {
+        write_atomic(&DEFAULT_PRESET_FILE, toml::to_string_pretty(self).unwrap())
+            .map_err(|e| format!("Couldn't write the default preset - {e:?}"))
+    }
+
+    pub fn clear_default() -> Result<(), String> {
+        match fs::remove_file(DEFAULT_PRESET_FILE.as_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Couldn't remove the default preset - {e:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = fs::read_to_string(path).map_err(|e| format!("Can't read preset - {e:?}"))?;
+        toml::from_str(&file).map_err(|e| format!("Invalid preset - {e:?}"))
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        if name.trim().is_empty() || name.contains(['/', '\\']) {
+            return Err("Preset names can't be empty or contain slashes".to_string());
+        }
+
+        write_atomic(
+            &PRESET_DIR.join(format!("{name}.{PRESET_EXTENSION}")),
+            toml::to_string_pretty(self).unwrap(),
+        )
+        .map_err(|e| format!("Couldn't write preset - {e:?}"))
+    }
+}
+
+const fn is_setting(flags: ParamFlags) -> bool {
+    flags.contains(ParamFlags::HIDDEN) || flags.contains(ParamFlags::NON_AUTOMATABLE)
+}
+
+/// The category a parameter's group falls under, for locking it on preset load. Groups in an array,
+/// like the detune for every harmonic, all share one. Parameters outside of any group are in the
+/// empty category.
+pub fn category(group: &str) -> &str {
+    group
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == ' ')
+}
+
+/// Every category with parameters that presets touch, sorted.
+pub fn categories(params: &impl Params) -> Vec<String> {
+    params
+        .param_map()
+        .into_iter()
+        // SAFETY: see above
+        .filter(|(_, ptr, _)| !is_setting(unsafe { ptr.flags() }))
+        .map(|(_, _, group)| category(&group).to_string())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// The presets found in [`PRESET_DIR`] so far, sorted by name. A preset's position in this list
+/// is its program number. Scans run on the background thread and add presets one at a time, so
+/// the editor can show the list filling in without waiting on the disk.
+#[derive(Default)]
+pub struct PresetScan {
+    pub presets: Vec<PresetEntry>,
+    pub scanning: bool,
+    /// Set when a scan fails, until the editor picks it up.
+    pub error: Option<String>,
+    /// Goes up with every scan, so one that's been started over stops adding to the list.
+    generation: u64,
+}
+
+impl PresetScan {
+    /// Clear the list and find every preset again. This is for the background thread.
+    pub fn run(scan: &Mutex<Self>) {
+        let generation = {
+            let mut scan = lock(scan);
+            scan.presets.clear();
+            scan.scanning = true;
+            scan.generation += 1;
+            scan.generation
+        };
+
+        let entries = match fs::read_dir(PRESET_DIR.as_path()) {
+            Ok(entries) => entries,
+            // Nothing has been saved yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                lock(scan).scanning = false;
+                return;
+            }
+            Err(e) => {
+                let mut scan = lock(scan);
+                scan.error = Some(format!("Can't read presets - {e:?}"));
+                scan.scanning = false;
+                return;
+            }
+        };
+
+        let presets = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == PRESET_EXTENSION))
+            .filter_map(|path| {
+                Some(PresetEntry {
+                    name: path.file_stem()?.to_string_lossy().into_owned(),
+                    path,
+                })
+            });
+        for preset in presets {
+            let mut scan = lock(scan);
+            if scan.generation != generation {
+                return;
+            }
+            let key = preset.name.to_lowercase();
+            let idx = scan
+                .presets
+                .partition_point(|other| other.name.to_lowercase() <= key);
+            scan.presets.insert(idx, preset);
+        }
+
+        let mut scan = lock(scan);
+        if scan.generation == generation {
+            scan.scanning = false;
+        }
+    }
+}
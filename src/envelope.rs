@@ -0,0 +1,115 @@
+//! Envelope segments with an adjustable curve. NIH-plug's smoothers only come in a few fixed shapes,
+//! which isn't enough for shaping how a voice fades in and out.
+
+/// How sharply the exponential end of the shape range bends.
+const EXPONENTIAL_CURVE: f32 = 5.0;
+
+/// Ramps from one value to another over a set amount of time. The shape goes from exponential at
+/// -1, where most of the movement happens at the start, through linear at 0, to an s-curve at 1.
+#[derive(Clone, Copy)]
+pub struct Envelope {
+    start: f32,
+    target: f32,
+    /// How far along the current segment we are, from 0 to 1.
+    progress: f32,
+    /// How much `progress` moves every sample.
+    step: f32,
+    time_ms: f32,
+    shape: f32,
+    value: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            target: 0.0,
+            progress: 1.0,
+            step: 0.0,
+            time_ms: 0.0,
+            shape: 0.0,
+            value: 0.0,
+        }
+    }
+}
+
+impl Envelope {
+    /// Jump straight to `value`, without ramping.
+    pub fn reset(&mut self, value: f32) {
+        *self = Self {
+            start: value,
+            target: value,
+            value,
+            ..Self::default()
+        };
+    }
+
+    /// Start ramping from the current value to `target`.
+    pub fn set_target(&mut self, sample_rate: f32, target: f32, time_ms: f32, shape: f32) {
+        self.start = self.value;
+        self.target = target;
+        self.progress = 0.0;
+        self.time_ms = time_ms;
+        self.shape = shape.clamp(-1.0, 1.0);
+        self.set_sample_rate(sample_rate);
+    }
+
+    /// Recompute the step size for a new sample rate. Whatever's left of the current segment still
+    /// takes as long as it would have.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let num_samples = self.time_ms / 1000.0 * sample_rate;
+        self.step = if num_samples >= 1.0 {
+            num_samples.recip()
+        } else {
+            1.0
+        };
+    }
+
+    /// The value the envelope was at after the last call to [`next()`][Self::next()].
+    pub const fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// How far along the current segment the envelope is, from 0 to 1.
+    pub const fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// The value the current segment is ramping to.
+    pub const fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.progress < 1.0
+    }
+
+    pub fn next(&mut self) -> f32 {
+        if self.is_ramping() {
+            self.progress = (self.progress + self.step).min(1.0);
+            self.value =
+                (self.target - self.start).mul_add(curve(self.progress, self.shape), self.start);
+        }
+
+        self.value
+    }
+
+    pub fn next_block(&mut self, block: &mut [f32], block_len: usize) {
+        for value in &mut block[..block_len] {
+            *value = self.next();
+        }
+    }
+}
+
+/// How far a segment with `shape` has gotten from its start to its target once it's `t` of the way
+/// through, from 0 to 1.
+pub fn curve(t: f32, shape: f32) -> f32 {
+    if shape < 0.0 {
+        let exponential =
+            (1.0 - (-EXPONENTIAL_CURVE * t).exp()) / (1.0 - (-EXPONENTIAL_CURVE).exp());
+        (exponential - t).mul_add(-shape, t)
+    } else {
+        let s_curve = t * t * 2.0f32.mul_add(-t, 3.0);
+        (s_curve - t).mul_add(shape, t)
+    }
+}
@@ -0,0 +1,64 @@
+//! A frequency shifter built on an IIR Hilbert transformer. Unlike pitch shifting, this moves every
+//! partial by the same number of Hz, which is what makes harmonic content turn inharmonic.
+
+use crate::simd::f32x2;
+use std::f32::consts::TAU;
+
+/// Allpass coefficients for the two paths of the Hilbert transformer, whose outputs end up 90
+/// degrees apart over most of the audible range. These are Olli Niemitalo's.
+const PATH_A: [f32; 4] = [0.692_387_8, 0.936_065_4, 0.988_229_5, 0.998_748_8];
+const PATH_B: [f32; 4] = [0.402_192_1, 0.856_171_1, 0.972_290_9, 0.995_288_5];
+
+/// A second order allpass in `z^-2`: `y[n] = a^2 * (x[n] + y[n - 2]) - x[n - 2]`.
+#[derive(Clone, Copy, Default)]
+struct Allpass {
+    x: [f32x2; 2],
+    y: [f32x2; 2],
+}
+
+impl Allpass {
+    fn process(&mut self, input: f32x2, coefficient: f32) -> f32x2 {
+        let output = f32x2::splat(coefficient * coefficient) * (input + self.y[1]) - self.x[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+
+        output
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct FrequencyShifter {
+    path_a: [Allpass; 4],
+    path_b: [Allpass; 4],
+    /// Path B's output is delayed by a sample to line it up with path A.
+    path_b_delay: f32x2,
+    /// The shifting oscillator's phase, from 0 to 1.
+    phase: f32,
+}
+
+impl FrequencyShifter {
+    /// Shift `input` up by `shift_hz`, or down if it's negative.
+    pub fn process(&mut self, input: f32x2, shift_hz: f32, sample_rate: f32) -> f32x2 {
+        let in_phase = self
+            .path_a
+            .iter_mut()
+            .zip(PATH_A)
+            .fold(input, |sample, (allpass, coefficient)| {
+                allpass.process(sample, coefficient)
+            });
+        let quadrature = std::mem::replace(
+            &mut self.path_b_delay,
+            self.path_b
+                .iter_mut()
+                .zip(PATH_B)
+                .fold(input, |sample, (allpass, coefficient)| {
+                    allpass.process(sample, coefficient)
+                }),
+        );
+
+        let (sin, cos) = (self.phase * TAU).sin_cos();
+        self.phase = (self.phase + shift_hz / sample_rate).rem_euclid(1.0);
+
+        in_phase * f32x2::splat(cos) - quadrature * f32x2::splat(sin)
+    }
+}
@@ -1,169 +1,2019 @@
-#![feature(portable_simd)]
-#![feature(array_windows)]
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
 #![warn(clippy::pedantic, clippy::nursery)]
 
+mod correlation;
 mod editor;
+mod envelope;
+mod freq_shifter;
+mod limiter;
+mod midi_recorder;
+mod noise_gate;
+mod offline;
+mod scale;
+mod scope;
+mod simd;
 mod spectrum;
+mod test_tone;
+mod transient;
+mod tuning;
+mod voice_pool;
+mod worker_pool;
 
-use cozy_util::filter::svf::GenericSVF;
+use correlation::CorrelationMeter;
 use crossbeam::atomic::AtomicCell;
+use editor::{Preset, PresetScan};
+use envelope::Envelope;
+use freq_shifter::FrequencyShifter;
+use limiter::TruePeakLimiter;
+use midi_recorder::MidiRecorder;
 use nih_plug::prelude::*;
+use nih_plug::wrapper::state::{ParamValue, PluginState};
 use nih_plug_egui::EguiState;
-use spectrum::{SpectrumInput, SpectrumOutput};
-use std::simd::f32x2;
-use std::sync::Arc;
+use noise_gate::NoiseGate;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use scale::ScaleParams;
+use scope::{ScopeInput, ScopeOutput};
+use simd::{f32x2, StereoFilter};
+use spectrum::{SpectrumInput, SpectrumOutput, SpectrumSettings};
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64};
+use std::sync::{Arc, Mutex, RwLock};
+use test_tone::{TestTone, TestToneSettings};
+use transient::TransientDetector;
+use triple_buffer::TripleBuffer;
+use voice_pool::{PoolVoice, VoicePool};
+use worker_pool::WorkerPool;
 
 const MAX_BLOCK_SIZE: usize = 64;
 pub const NUM_VOICES: usize = 128;
 pub const NUM_FILTERS: usize = 8;
+/// The note band width key tracking is centered on. Notes below it get wider, notes above narrower.
+const KEYTRACK_CENTER_NOTE: f32 = 60.0;
+/// The most filters that get cascaded for each harmonic, for the steepest slope.
+const MAX_FILTER_STAGES: usize = 3;
+/// Below this many voices, splitting the work up across threads costs more than it saves.
+const MIN_THREADED_VOICES: usize = 8;
+const MAX_WORKER_THREADS: usize = 3;
+/// The trance gate's steps are sixteenth notes.
+const GATE_STEP_BEATS: f64 = 0.25;
+const MAX_GATE_STEPS: usize = 16;
+/// How long the gate takes to open or close, to keep it from clicking.
+const GATE_SMOOTHING_MS: f32 = 5.0;
+/// The CC mapping parameters' value for not listening to a CC at all.
+const CC_OFF: i32 = 128;
+/// How long a mapped CC takes to get to a new value, so sweeps don't staircase.
+const CC_SMOOTHING_MS: f32 = 10.0;
+/// How long switching between the coarse and fine band gain takes to fade over.
+const FINE_GAIN_CROSSFADE_MS: f32 = 50.0;
+/// The expression pedal's CC.
+const EXPRESSION_CC: u8 = 11;
+/// How many MIDI channels there are to keep track of the expression for.
+const NUM_MIDI_CHANNELS: usize = 16;
+/// The most note ons the quantizer can hold back at once. Anything past this gets dropped.
+const MAX_PENDING_NOTE_ONS: usize = NUM_VOICES;
+/// How many stereo aux outputs the voices get split across, in the layout that has them.
+const NUM_OUTPUT_GROUPS: usize = 4;
+/// How long a voice takes to move between the boost and cut outputs with boost/cut routing.
+const BOOST_CUT_CROSSFADE_MS: f32 = 20.0;
+/// The name of the aux output with the dry input on it. Layouts that have it put it last.
+const DRY_OUTPUT_NAME: &str = "Dry";
+/// How fast the dry ducking's envelope follower reacts to the resonance.
+const DUCK_ATTACK_MS: f32 = 5.0;
+const DUCK_RELEASE_MS: f32 = 150.0;
+/// How much wider filters at Nyquist get with HF compensation on. Filters an octave or more below
+/// Nyquist aren't touched, in between the widening fades in.
+const HF_COMPENSATION_MAX_WIDENING: f32 = 4.0;
+/// With the automatic harmonic count on, voices only run the harmonics below this or Nyquist,
+/// whichever is lower.
+const AUDIBLE_LIMIT_HZ: f32 = 20_000.0;
+/// At full humanize, attack times get scaled by up to this much in either direction.
+const HUMANIZE_MAX_ATTACK_SCALE: f32 = 0.5;
+const HUMANIZE_MAX_GAIN_DB: f32 = 3.0;
+/// Retunes at least this big, in semitones, crossfade to a fresh filter bank instead of sweeping
+/// the ringing one, which chirps on big jumps.
+const RETUNE_CROSSFADE_SEMITONES: f32 = 2.0;
+const RETUNE_CROSSFADE_MS: f32 = 30.0;
+/// Mixed into the seed for the per-filter scatter, so it doesn't follow the humanize offsets.
+const SCATTER_RNG_STREAM: u64 = 1 << 56;
+
+/// A snapshot of every voice's filters, published to the editor once per block.
+type FilterDisplay = [VoiceDisplay; NUM_VOICES];
+/// A receiver for the [`FilterDisplay`] snapshots published by the plugin.
+type FilterDisplayOutput = triple_buffer::Output<FilterDisplay>;
+
+#[derive(Clone, Copy, Default)]
+struct VoiceDisplay {
+    /// Filters that aren't currently running are `None`.
+    filters: [Option<FilterDescriptor>; NUM_FILTERS],
+    /// The frequencies of the filters the safety switch is skipping for being above Nyquist.
+    skipped: [Option<f32>; NUM_FILTERS],
+    /// Where the voice is along its amplitude envelope. `None` for empty slots.
+    envelope: Option<EnvelopePosition>,
+    note: u8,
+    /// Seconds since the note started.
+    age: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EnvelopeStage {
+    Attack,
+    /// Waiting out the hold in the one shot and sidechain trigger modes.
+    Hold,
+    /// Held at full level until the note is let go of.
+    Sustain,
+    /// The sidechain trigger mode's decay, while the note is still held.
+    Decay,
+    /// The release, or a one shot's decay.
+    Release,
+}
+
+#[derive(Clone, Copy)]
+struct EnvelopePosition {
+    stage: EnvelopeStage,
+    /// How far through the stage the voice is, from 0 to 1.
+    progress: f32,
+    value: f32,
+}
+
+pub const VERSION: &str = env!("VERGEN_GIT_DESCRIBE");
+
+#[derive(Clone)]
+struct Voice {
+    id: i32,
+    channel: u8,
+    note: u8,
+    frequency: f32,
+    internal_voice_id: u64,
+    velocity_sqrt: f32,
+    /// Added to the band width, from the note on's velocity. This is fixed for the whole note.
+    velocity_width: f32,
+    /// Every harmonic gets a cascade of filters, only as many of which are used as the slope
+    /// needs.
+    filters: [[StereoFilter; MAX_FILTER_STAGES]; NUM_FILTERS],
+    filter_descriptors: [Option<FilterDescriptor>; NUM_FILTERS],
+    /// The filters from before the last big retune, left ringing at
+    /// [`old_frequency`][Self::old_frequency] while they fade out.
+    old_filters: [[StereoFilter; MAX_FILTER_STAGES]; NUM_FILTERS],
+    old_frequency: f32,
+    /// How much of the output still comes from the old filters, from 1 right after a big retune
+    /// down to 0 once the crossfade is done.
+    retune_fade: f32,
+    /// Where the filters the safety switch skipped would have been, for the editor.
+    skipped_frequencies: [Option<f32>; NUM_FILTERS],
+    /// Every filter's random detune, from -1 to 1. This is picked when the voice starts and gets
+    /// scaled by the scatter amount.
+    scatter: [f32; NUM_FILTERS],
+    releasing: bool,
+    /// How long ago the note started, in samples.
+    age: u64,
+    /// In [`EnvelopeMode::OneShot`], how many more samples the voice holds at full level once the
+    /// attack is done.
+    hold_samples: u32,
+    /// Set when the voice's key was let go of while freeze was on. The voice keeps playing until
+    /// freeze is turned off again.
+    held_by_freeze: bool,
+    /// Set when the voice's key was let go of in [`EnvelopeMode::OneShot`], which doesn't release
+    /// it. If the mode gets switched before the one shot is over, it's released then.
+    key_up: bool,
+    amp_envelope: Envelope,
+    /// An attack/decay envelope that gets added to the band width.
+    width_envelope: Envelope,
+    width_envelope_decaying: bool,
+    /// Shifts what the filters add to the signal, in [`FrequencyShift`] modes other than off.
+    freq_shifter: FrequencyShifter,
+    noise_gate: NoiseGate,
+    /// Which aux output this voice's resonance goes to. Boost/cut routing uses
+    /// [`cut_fade`][Self::cut_fade] instead.
+    output_group: usize,
+    /// How far over to the cut output the voice is with boost/cut routing, from 0 to 1, at the
+    /// end of the block. This fades instead of switching so the outputs don't click.
+    cut_fade: f32,
+    /// [`cut_fade`][Self::cut_fade] at the start of the block.
+    cut_fade_start: f32,
+    /// Where this voice's resonance sits in the stereo field, from -1 to 1. Set by the host
+    /// through per-note expressions.
+    pan: f32,
+    /// A per-note expression that scales the band gain.
+    volume: f32,
+    /// The voice's channel's CC11 value, which also scales the band gain.
+    expression: f32,
+    /// [`expression`][Self::expression] with smoothing, so the pedal doesn't zipper.
+    smoothed_expression: f32,
+}
+
+impl PoolVoice for Voice {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    fn note(&self) -> u8 {
+        self.note
+    }
+
+    fn start_order(&self) -> u64 {
+        self.internal_voice_id
+    }
+}
+
+impl Voice {
+    /// Whether the voice's filters are boosting rather than cutting, going by the first one that's
+    /// running.
+    fn boosts(&self) -> bool {
+        let Some(filter) = self.filter_descriptors.iter().flatten().next() else {
+            return true;
+        };
+
+        filter.mode != FilterMode::Notch && filter.gain >= 0.0
+    }
+
+    /// Move [`cut_fade`][Self::cut_fade] towards the output the voice belongs on by one block's
+    /// worth of the crossfade.
+    fn follow_boost_cut(&mut self, block_len: usize, sample_rate: f32) {
+        #[allow(clippy::cast_precision_loss)]
+        let step = block_len as f32 / (BOOST_CUT_CROSSFADE_MS / 1000.0 * sample_rate);
+        let target = if self.boosts() { 0.0 } else { 1.0 };
+        self.cut_fade_start = self.cut_fade;
+        self.cut_fade = if target > self.cut_fade {
+            (self.cut_fade + step).min(target)
+        } else {
+            (self.cut_fade - step).max(target)
+        };
+    }
+
+    /// Add what the voice changed at `value_idx` to its aux output, or split it between the boost
+    /// and cut outputs with boost/cut routing.
+    fn add_to_group(
+        &self,
+        group_outputs: &mut [[f32x2; MAX_BLOCK_SIZE]; NUM_OUTPUT_GROUPS],
+        boost_cut: bool,
+        value_idx: usize,
+        block_len: usize,
+        change: f32x2,
+    ) {
+        if !boost_cut {
+            group_outputs[self.output_group][value_idx] += change;
+            return;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let progress = (value_idx + 1) as f32 / block_len as f32;
+        let cut = (self.cut_fade - self.cut_fade_start).mul_add(progress, self.cut_fade_start);
+        group_outputs[0][value_idx] += change * f32x2::splat(1.0 - cut);
+        group_outputs[1][value_idx] += change * f32x2::splat(cut);
+    }
+
+    /// Where the voice is along its amplitude envelope, for the editor. `hold_samples` is how long
+    /// a full hold is.
+    fn envelope_position(&self, mode: EnvelopeMode, hold_samples: u32) -> EnvelopePosition {
+        let envelope = &self.amp_envelope;
+        #[allow(clippy::cast_precision_loss)]
+        let (stage, progress) = if self.releasing {
+            (EnvelopeStage::Release, envelope.progress())
+        } else if envelope.is_ramping() && envelope.target() > 0.0 {
+            (EnvelopeStage::Attack, envelope.progress())
+        } else if envelope.is_ramping() || envelope.value() <= 0.0 {
+            (EnvelopeStage::Decay, envelope.progress())
+        } else if mode != EnvelopeMode::Sustain && self.hold_samples > 0 {
+            (
+                EnvelopeStage::Hold,
+                1.0 - self.hold_samples as f32 / hold_samples.max(1) as f32,
+            )
+        } else {
+            (EnvelopeStage::Sustain, 0.0)
+        };
+
+        EnvelopePosition {
+            stage,
+            progress: progress.clamp(0.0, 1.0),
+            value: envelope.value(),
+        }
+    }
+
+    /// Start the release portion of the amplitude envelope, echoing the note off if MIDI output is
+    /// on.
+    fn release(
+        &mut self,
+        host: &mut impl ProcessHost,
+        timing: u32,
+        sample_rate: f32,
+        envelope: &EnvelopeParams,
+        filter_mode: FilterMode,
+        midi_out: bool,
+    ) {
+        if midi_out && !self.releasing {
+            echo_note_off(host, timing, self);
+        }
+
+        self.releasing = true;
+        self.held_by_freeze = false;
+        // One shots only get here once their hold is over, so this is the decay
+        let time = match envelope.mode.value() {
+            EnvelopeMode::Sustain | EnvelopeMode::SidechainTrigger => {
+                envelope.release_time(self.note, filter_mode)
+            }
+            EnvelopeMode::OneShot => envelope.decay.value(),
+        };
+        self.amp_envelope
+            .set_target(sample_rate, 0.0, time, envelope.release_shape.value());
+    }
+
+    /// Move this voice over to a new sample rate. The filters' state only makes sense at the sample
+    /// rate it was built up at, so they start over from scratch. The envelopes keep going where they
+    /// were.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        for (stages, descriptor) in self.filters.iter_mut().zip(&self.filter_descriptors) {
+            for filter in stages.iter_mut() {
+                *filter = StereoFilter::default();
+                filter.set_sample_rate(sample_rate);
+            }
+            if let Some(descriptor) = descriptor {
+                descriptor.apply(stages);
+            }
+        }
+
+        // The old filters' state is just as stale, so any crossfade ends early
+        self.retune_fade = 0.0;
+        self.amp_envelope.set_sample_rate(sample_rate);
+        self.width_envelope.set_sample_rate(sample_rate);
+    }
+
+    /// Move the voice to `frequency`. Big jumps start the filters over from scratch and crossfade
+    /// from the old ones, which keep ringing at the old frequency for a moment. Jumps during a
+    /// crossfade sweep the new filters instead, since swapping the banks again would cut off the
+    /// old ones mid fade and click.
+    fn retune(&mut self, frequency: f32, sample_rate: f32) {
+        if self.retune_fade <= 0.0
+            && (frequency / self.frequency).log2().abs() * 12.0 >= RETUNE_CROSSFADE_SEMITONES
+        {
+            self.old_filters = self.filters;
+            self.old_frequency = self.frequency;
+            self.retune_fade = 1.0;
+            for filter in self.filters.iter_mut().flatten() {
+                *filter = StereoFilter::default();
+                filter.set_sample_rate(sample_rate);
+            }
+        }
+        self.frequency = frequency;
+    }
+
+    /// Run this voice's filters over a block of `samples`, in place. `band_width` is the
+    /// normalized band width before any of the voice's own modulation.
+    fn process(
+        &mut self,
+        samples: &mut [f32x2],
+        gain: &[f32],
+        band_width: &[f32],
+        params: &ScaleColorizrParams,
+        sample_rate: f32,
+    ) {
+        let mut amp_envelope = [0.0; MAX_BLOCK_SIZE];
+        self.amp_envelope
+            .next_block(&mut amp_envelope, samples.len());
+        let character = &params.character;
+        let (from, to) = (character.from.value(), character.to.value());
+        let falloff: [f32; NUM_FILTERS] = std::array::from_fn(|idx| {
+            let custom = params.falloff[idx].level.value();
+            let from = from.level(idx, custom);
+            character
+                .amount
+                .value()
+                .mul_add(to.level(idx, custom) - from, from)
+                * character.tilt_level(idx)
+        });
+        let spread = params.filter.spread.value();
+        let scatter = params.filter.scatter.value();
+        let ratios: [f32; NUM_FILTERS] = std::array::from_fn(|idx| {
+            let cents = self.scatter[idx].mul_add(scatter, params.detune[idx].cents.value());
+            spread.ratio(idx) * (cents / 1200.0).exp2()
+        });
+        // How much of each filter's effect ends up in each channel. Odd harmonics lean left, even
+        // ones lean right.
+        let alternation = params.filter.stereo_alternation.value();
+        let filter_mode = params.filter.filter_mode.value();
+        let keytrack = params.filter.keytrack.value()
+            * (KEYTRACK_CENTER_NOTE - f32::from(self.note))
+            / KEYTRACK_CENTER_NOTE;
+        let num_stages = params.filter.slope.value().stages();
+        #[allow(clippy::cast_precision_loss)]
+        let num_stages_f32 = num_stages as f32;
+        let stage_q_scale = (num_stages_f32.recip().exp2() - 1.0).sqrt();
+        let morph = if filter_mode == FilterMode::Morph {
+            params.filter.morph.value()
+        } else {
+            1.0
+        };
+        let channel_weights: [f32x2; NUM_FILTERS] = std::array::from_fn(|idx| {
+            if idx % 2 == 0 {
+                f32x2::from_array([1.0, 1.0 - alternation])
+            } else {
+                f32x2::from_array([1.0 - alternation, 1.0])
+            }
+        });
+
+        // Constant power panning, normalized so the center is unity gain
+        #[allow(clippy::float_cmp)]
+        let pan_weights = (self.pan != 0.0).then(|| {
+            let (right, left) = ((self.pan + 1.0) * FRAC_PI_4).sin_cos();
+            f32x2::from_array([left, right]) * f32x2::splat(SQRT_2)
+        });
+
+        let hf_compensation = params.filter.hf_compensation.value();
+        let retune_fade_step = 1000.0 / (RETUNE_CROSSFADE_MS * sample_rate);
+        // Turning the option off lets voices go back to full level
+        let expression = if params.performance.expression.value() {
+            self.expression
+        } else {
+            1.0
+        };
+        let expression_smoothing = 1.0 - (-1000.0 / (CC_SMOOTHING_MS * sample_rate)).exp();
+        let resonance_curve = params.filter.resonance_curve.value();
+        let max_resonance = params.filter.max_resonance.value();
+        // Everything past the last harmonic under the limit gets left out for the whole block,
+        // rather than checking each one per sample
+        let num_harmonics = if params.filter.auto_harmonics.value() {
+            let limit = (sample_rate / 2.0).min(AUDIBLE_LIMIT_HZ);
+            ratios
+                .iter()
+                .rposition(|ratio| self.frequency * ratio < limit)
+                .map_or(0, |idx| idx + 1)
+        } else {
+            NUM_FILTERS
+        };
+        for ((descriptor, skipped_frequency), ratio) in self.filter_descriptors[num_harmonics..]
+            .iter_mut()
+            .zip(&mut self.skipped_frequencies[num_harmonics..])
+            .zip(&ratios[num_harmonics..])
+        {
+            let frequency = self.frequency * ratio;
+            *descriptor = None;
+            *skipped_frequency = (frequency >= sample_rate / 2.0).then_some(frequency);
+        }
+
+        let noise_gate = &params.noise_gate;
+        let gate_settings = noise_gate
+            .enabled
+            .value()
+            .then(|| (noise_gate.threshold.value(), noise_gate.ratio.value()));
+
+        let shift_hz = match params.filter.freq_shift.value() {
+            FrequencyShift::Off => None,
+            FrequencyShift::Note => Some(self.frequency * params.filter.freq_shift_ratio.value()),
+            FrequencyShift::Hz => Some(params.filter.freq_shift_hz.value()),
+        };
+
+        for (((sample, gain), band_width), amp_envelope) in samples
+            .iter_mut()
+            .zip(gain)
+            .zip(band_width)
+            .zip(amp_envelope)
+        {
+            let input = *sample;
+            // The filters only see the gated input, the dry signal gets put back afterwards
+            let gated_input = gate_settings.map_or(input, |(threshold, ratio)| {
+                input
+                    * f32x2::splat(
+                        self.noise_gate
+                            .process(input, threshold, ratio, sample_rate),
+                    )
+            });
+            *sample = gated_input;
+            self.smoothed_expression +=
+                (expression - self.smoothed_expression) * expression_smoothing;
+            let amp =
+                gain * self.velocity_sqrt * self.volume * self.smoothed_expression * amp_envelope;
+            // Damping grows as the release fades out, like a string being muted
+            let damping = if self.releasing {
+                params.envelope.damping.value() * (1.0 - amp_envelope)
+            } else {
+                0.0
+            };
+
+            let width_envelope = self.width_envelope.next();
+            if !self.width_envelope_decaying && !self.width_envelope.is_ramping() {
+                self.width_envelope_decaying = true;
+                self.width_envelope.set_target(
+                    sample_rate,
+                    0.0,
+                    params.envelope.width_decay.value(),
+                    -1.0,
+                );
+            }
+            let band_width = (params
+                .envelope
+                .width_amount
+                .value()
+                .mul_add(width_envelope, *band_width)
+                + keytrack
+                + self.velocity_width
+                + damping)
+                .clamp(0.0, 1.0);
+            // Cascading filters narrows the band, so every stage gets widened to make up for it
+            let q = resonance_curve.q(band_width, max_resonance) * stage_q_scale;
+            // The old filters run their own chain on the same input, to be mixed in at the end
+            let mut old_sample = (self.retune_fade > 0.0).then_some(*sample);
+
+            for (
+                filter_idx,
+                (((((stages, old_stages), descriptor), skipped_frequency), ratio), channel_weight),
+            ) in self
+                .filters
+                .iter_mut()
+                .zip(&mut self.old_filters)
+                .zip(&mut self.filter_descriptors)
+                .zip(&mut self.skipped_frequencies)
+                .zip(ratios)
+                .zip(channel_weights)
+                .take(num_harmonics)
+                .enumerate()
+            {
+                #[allow(clippy::cast_precision_loss)]
+                let amp_falloff = falloff[filter_idx] * (-damping * filter_idx as f32).exp();
+                let descriptor_at = |frequency: f32| {
+                    // The bilinear transform squashes everything in the top octave towards
+                    // Nyquist, which makes narrow peaks there ring at the wrong width
+                    let top_octave_position = (frequency / (sample_rate / 4.0)).log2();
+                    let q = if hf_compensation && top_octave_position > 0.0 {
+                        q / (HF_COMPENSATION_MAX_WIDENING - 1.0)
+                            .mul_add(top_octave_position.min(1.0), 1.0)
+                    } else {
+                        q
+                    };
+
+                    FilterDescriptor {
+                        frequency,
+                        q,
+                        gain: amp * amp_falloff * morph / num_stages_f32,
+                        mode: filter_mode,
+                        stages: num_stages,
+                    }
+                };
+                let run = |stages: &mut [StereoFilter; MAX_FILTER_STAGES], sample: f32x2| {
+                    let filtered = stages[..num_stages]
+                        .iter_mut()
+                        .fold(sample, |sample, filter| filter.process(sample));
+                    if alternation > 0.0 {
+                        (filtered - sample) * channel_weight + sample
+                    } else {
+                        filtered
+                    }
+                };
+                let skips = |frequency: f32| {
+                    params.filter.safety_switch.value() && frequency >= sample_rate / 2.0
+                };
+
+                if let Some(old_sample) = &mut old_sample {
+                    let frequency = self.old_frequency * ratio;
+                    if !skips(frequency) {
+                        descriptor_at(frequency).apply(old_stages);
+                        *old_sample = run(old_stages, *old_sample);
+                    }
+                }
+
+                let frequency = self.frequency * ratio;
+                if skips(frequency) {
+                    *descriptor = None;
+                    *skipped_frequency = Some(frequency);
+                    continue;
+                }
+                *skipped_frequency = None;
+
+                let new_descriptor = descriptor_at(frequency);
+                new_descriptor.apply(stages);
+                *descriptor = Some(new_descriptor);
+                *sample = run(stages, *sample);
+            }
+
+            if let Some(old_sample) = old_sample {
+                *sample = (old_sample - *sample) * f32x2::splat(self.retune_fade) + *sample;
+                self.retune_fade = (self.retune_fade - retune_fade_step).max(0.0);
+            }
+
+            *sample += input - gated_input;
+
+            if let Some(shift_hz) = shift_hz {
+                let resonance = *sample - input;
+                *sample = input + self.freq_shifter.process(resonance, shift_hz, sample_rate);
+            }
+            if let Some(pan_weights) = pan_weights {
+                *sample = (*sample - input) * pan_weights + input;
+            }
+        }
+    }
+}
+
+/// Hands out mutable access to the elements of an array across threads. Whoever uses this has to
+/// make sure no two threads get the same element at the same time.
+#[derive(Clone, Copy)]
+struct SharedMut<T>(*mut T);
+
+// SAFETY: see above, access is disjoint
+unsafe impl<T: Send> Send for SharedMut<T> {}
+unsafe impl<T: Send> Sync for SharedMut<T> {}
+
+impl<T> SharedMut<T> {
+    /// # Safety
+    ///
+    /// `idx` has to be in bounds, and nothing else can be accessing that element.
+    const unsafe fn get<'a>(self, idx: usize) -> &'a mut T {
+        &mut *self.0.add(idx)
+    }
+}
+
+/// Work handed off to NIH-plug's background thread, so the audio thread doesn't have to block or
+/// allocate.
+pub enum BackgroundTask {
+    /// An incoming event for the MIDI recording, at `time` samples since it started.
+    RecordMidi {
+        time: u64,
+        event: NoteEvent<()>,
+    },
+    SaveMidiRecording {
+        path: PathBuf,
+        sample_rate: f32,
+    },
+    /// Find the presets on disk again, for the editor's preset list.
+    ScanPresets,
+    /// Load the preset for a program change that came in while the editor was closed.
+    LoadProgram(usize),
+}
+
+pub struct ScaleColorizr {
+    params: Arc<ScaleColorizrParams>,
+    /// Whether the user's default preset has been applied to [`params`][Self::params] yet. See
+    /// [`Plugin::params`].
+    default_preset_applied: AtomicBool,
+    voices: VoicePool<Voice, NUM_VOICES>,
+    /// The main input after the input gain, for the delta mix to fade towards.
+    dry_signal: [f32x2; MAX_BLOCK_SIZE],
+    /// Scratch buffers for every voice, used when the voices are processed in parallel.
+    voice_outputs: [[f32x2; MAX_BLOCK_SIZE]; NUM_VOICES],
+    worker_pool: Option<WorkerPool>,
+    filter_display_input: triple_buffer::Input<FilterDisplay>,
+    /// Like the spectrum outputs, this is shared with every editor instead of handed over, since
+    /// hosts are free to create a new editor as often as they like.
+    filter_display_output: Arc<Mutex<FilterDisplayOutput>>,
+    sample_rate: Arc<AtomicF32>,
+    midi_event_debug: Arc<AtomicCell<Option<NoteEvent<()>>>>,
+    /// The last program change that came in, for the editor to load if it's open. Otherwise it gets
+    /// loaded on the background thread. Bank selects are already included, so this is
+    /// `bank * 128 + program`.
+    program_change: Arc<AtomicCell<Option<usize>>>,
+    pending_note_ons: Vec<PendingNoteOn>,
+    /// The keys whose last note on was outside the velocity range, by channel and note. Their note
+    /// offs get dropped, so they don't release a voice that's playing the same key.
+    ignored_notes: [[bool; 128]; 16],
+    /// The trance gate's level, which gets multiplied into the band gain.
+    gate: Smoother<f32>,
+    gate_target: f32,
+    /// Whether the footswitch mapped to freeze is down.
+    freeze_footswitch: bool,
+    /// The band width set by the mapped CC, if one has come in since the mapping was set up.
+    band_width_cc: Smoother<f32>,
+    band_width_cc_active: bool,
+    /// How much of the band gain comes from the fine band gain, from 0 to 1. This fades when fine
+    /// mode gets switched, so the gain doesn't jump.
+    fine_gain_mix: Smoother<f32>,
+    /// Whether fine mode was on as of the last block.
+    fine_gain_enabled: bool,
+    /// The last CC11 value on every channel, for voices that start after it.
+    channel_expression: [f32; NUM_MIDI_CHANNELS],
+    frozen: bool,
+    /// The envelope follower on what the filters added, for ducking the dry signal.
+    duck_envelope: f32,
+    /// Finds hits in the sidechain for [`EnvelopeMode::SidechainTrigger`].
+    transient_detector: TransientDetector,
+    limiter: TruePeakLimiter,
+    /// The lowest gain the limiter has applied since the editor last looked.
+    limiter_gain: Arc<AtomicCell<f32>>,
+    /// How many voices have been stolen since the host's transport last started, for the editor.
+    voices_stolen: Arc<AtomicU32>,
+    /// Whether the transport was playing as of the last buffer, to tell when it starts.
+    was_playing: bool,
+    /// Whether the last aux output is [`DRY_OUTPUT_NAME`], going by the layout the host picked.
+    has_dry_output: bool,
+    test_tone: TestTone,
+    /// What the editor's test tone panel wants played.
+    test_tone_settings: Arc<AtomicCell<TestToneSettings>>,
+    midi_recorder: Arc<MidiRecorder>,
+    /// The editor's preset list, filled in by [`BackgroundTask::ScanPresets`].
+    preset_scan: Arc<Mutex<PresetScan>>,
+    /// Whether this buffer's events are being recorded, read once per buffer.
+    recording_midi: bool,
+    /// Samples since the recording started.
+    midi_recording_position: u64,
+    /// This buffer's events for the recording, with their times, until they're handed over to the
+    /// background thread.
+    recorded_midi: Vec<(u64, NoteEvent<()>)>,
+    bank_select_msb: u8,
+    bank_select_lsb: u8,
+    next_internal_voice_id: u64,
+    pre_spectrum_input: SpectrumInput,
+    pre_spectrum_output: Arc<Mutex<SpectrumOutput>>,
+    post_spectrum_input: SpectrumInput,
+    post_spectrum_output: Arc<Mutex<SpectrumOutput>>,
+    scope_input: ScopeInput,
+    scope_output: Arc<Mutex<ScopeOutput>>,
+    correlation_meter: CorrelationMeter,
+    /// The output's stereo correlation, for the editor's meter.
+    correlation: Arc<AtomicF32>,
+    spectrum_settings: Arc<AtomicCell<SpectrumSettings>>,
+    /// Samples since the spectrum was last updated while the editor was closed.
+    spectrum_background_elapsed: usize,
+}
+
+/// The grid the quantizer delays note ons to.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum QuantizeDivision {
+    Off,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl QuantizeDivision {
+    /// The length of a grid division, in beats.
+    const fn beats(self) -> Option<f64> {
+        match self {
+            Self::Off => None,
+            Self::Quarter => Some(1.0),
+            Self::Eighth => Some(0.5),
+            Self::Sixteenth => Some(0.25),
+            Self::ThirtySecond => Some(0.125),
+        }
+    }
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum GateSteps {
+    #[name = "8"]
+    Eight,
+    #[name = "16"]
+    Sixteen,
+}
+
+impl GateSteps {
+    const fn count(self) -> usize {
+        match self {
+            Self::Eight => 8,
+            Self::Sixteen => 16,
+        }
+    }
+}
+
+/// What happens when a note is played again while a voice for it is still around.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum RetriggerMode {
+    /// Start another voice, and let the old one ring out.
+    #[name = "Always New"]
+    AlwaysNew,
+    /// End the old voice right away, and start a new one.
+    #[name = "Steal Same Note"]
+    StealSameNote,
+    /// Restart the old voice's envelopes, keeping its filters.
+    #[name = "Legato Reuse"]
+    LegatoReuse,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum EnvelopeMode {
+    /// Hold for as long as the note is held, then release.
+    Sustain,
+    /// Ignore note offs, and instead hold for a fixed time before decaying to nothing. This keeps
+    /// hits the same length no matter how long their trigger notes are.
+    #[name = "One Shot"]
+    OneShot,
+    /// Like a one shot, but hits on the sidechain restart the attack and hold of every voice that's
+    /// still held. Voices decay to silence instead of ending, and release on note off.
+    #[name = "Sidechain Trigger"]
+    SidechainTrigger,
+}
+
+/// Everything needed to start a voice.
+#[derive(Clone, Copy)]
+struct NoteOn {
+    timing: u32,
+    voice_id: Option<i32>,
+    channel: u8,
+    note: u8,
+    velocity: f32,
+}
+
+/// A note on that's being held back by the quantizer. The timing is in samples from the start of
+/// the current buffer, and can be past the end of it.
+#[derive(Clone, Copy)]
+struct PendingNoteOn {
+    note_on: NoteOn,
+    /// Set when the note off comes in before the note starts. The voice still plays so short notes
+    /// aren't lost, but gets released right away.
+    released: bool,
+}
+
+#[derive(Enum, PartialEq, Clone, Copy)]
+enum FilterMode {
+    Peak,
+    Notch,
+    /// A bell whose gain follows the morph parameter, so it can go from cutting to boosting.
+    Morph,
+}
+
+/// Whether, and by how much, what the filters add gets frequency shifted.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum FrequencyShift {
+    Off,
+    /// Shifts by a fraction of the voice's fundamental, so the shift follows the note.
+    Note,
+    /// Shifts every voice by the same amount.
+    Hz,
+}
+
+/// What the filters run on. Layouts without a sidechain input always use the main input. Hosts
+/// send silence to a sidechain that nothing is routed to, so the sidechain modes need something
+/// connected before the filters have anything to ring on.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum ExcitationSource {
+    Main,
+    /// Filter the sidechain, and mix the main input back in dry.
+    #[name = "Sidechain + Dry"]
+    SidechainWithDry,
+    /// Filter the sidechain, and drop the main input. The delta mix fades towards silence here,
+    /// since there's no dry signal left.
+    #[name = "Sidechain"]
+    SidechainOnly,
+}
+
+/// How voices get split across the aux outputs.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum AuxRouting {
+    /// Every new voice goes to the next output.
+    #[name = "Round Robin"]
+    RoundRobin,
+    /// The keyboard is split into as many ranges as there are outputs, lowest first.
+    #[name = "Note Range"]
+    NoteRange,
+    /// What boosting voices add goes to the first output, and what cutting voices take out goes
+    /// to the second, still inverted like it is in the mix. This follows the filter mode and the
+    /// morph as they move, so a morph can be split into its two halves.
+    #[name = "Boost / Cut"]
+    BoostCut,
+}
+
+/// A level for every harmonic, for the character macro to blend between.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum HarmonicProfile {
+    /// The falloff curve drawn in the editor.
+    Custom,
+    Flat,
+    Dark,
+    Bright,
+    Saw,
+    /// Only the odd harmonics, like a square wave.
+    Square,
+}
+
+impl HarmonicProfile {
+    /// The level of the harmonic at `idx`, counting the fundamental as 0. `custom` is that
+    /// harmonic's level on the falloff curve.
+    #[allow(clippy::cast_precision_loss)]
+    fn level(self, idx: usize, custom: f32) -> f32 {
+        let harmonic = idx as f32 + 1.0;
+        match self {
+            Self::Custom => custom,
+            Self::Flat => 1.0,
+            Self::Dark => (-(idx as f32) * 0.75).exp(),
+            Self::Bright => harmonic / NUM_FILTERS as f32,
+            Self::Saw => harmonic.recip(),
+            Self::Square if idx % 2 == 0 => harmonic.recip(),
+            Self::Square => 0.0,
+        }
+    }
+}
+
+/// How steep each harmonic's filter is. Steeper slopes cascade more filters.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum FilterSlope {
+    #[name = "12 dB"]
+    Slope12,
+    #[name = "24 dB"]
+    Slope24,
+    #[name = "36 dB"]
+    Slope36,
+}
+
+impl FilterSlope {
+    const fn stages(self) -> usize {
+        match self {
+            Self::Slope12 => 1,
+            Self::Slope24 => 2,
+            Self::Slope36 => MAX_FILTER_STAGES,
+        }
+    }
+}
+
+/// How band width maps to the filters' Q, between 1 at full width and the max resonance at none.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum ResonanceCurve {
+    /// Q goes down in even steps. Most of the knob's travel ends up ringing hard.
+    Linear,
+    /// Q goes down by the same ratio for every step, easing into the ceiling.
+    Soft,
+}
+
+impl ResonanceCurve {
+    /// The Q for a normalized `band_width`, before any cascading.
+    fn q(self, band_width: f32, max_q: f32) -> f32 {
+        match self {
+            Self::Linear => (max_q - 1.0).mul_add(1.0 - band_width, 1.0),
+            Self::Soft => max_q.powf(1.0 - band_width),
+        }
+    }
+}
+
+/// Where the filters above the fundamental go. The major and minor spreads build their own scale
+/// on the fundamental and land on its 3rd, 5th and octave, then the 9th, 10th, 12th and two
+/// octaves up. They don't follow the scale parameters, which only pick the fundamental.
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+enum Spread {
+    Harmonics,
+    Major,
+    Minor,
+}
+
+impl Spread {
+    /// Which degree of the scale each filter lands on, counting from the fundamental at 0.
+    const CHORD_DEGREES: [usize; NUM_FILTERS] = [0, 2, 4, 7, 8, 9, 11, 14];
+
+    /// The frequency ratio between the fundamental and the filter at `filter_idx`.
+    fn ratio(self, filter_idx: usize) -> f32 {
+        let scale = match self {
+            #[allow(clippy::cast_precision_loss)]
+            Self::Harmonics => return filter_idx as f32 + 1.0,
+            Self::Major => &scale::MAJOR,
+            Self::Minor => &scale::MINOR,
+        };
+
+        let degree = Self::CHORD_DEGREES[filter_idx];
+        #[allow(clippy::cast_possible_truncation)]
+        let octave = (degree / scale.len()) as u8;
+        let semitones = octave * 12 + scale[degree % scale.len()];
+
+        (f32::from(semitones) / 12.0).exp2()
+    }
+}
+
+/// Everything the editor needs to know about a filter to draw its response, without depending on
+/// the internals of the SVF running on the audio thread.
+#[derive(Clone, Copy)]
+struct FilterDescriptor {
+    pub frequency: f32,
+    pub q: f32,
+    /// The bell gain in decibels. Unused in [`FilterMode::Notch`]. For [`FilterMode::Morph`] this
+    /// already includes the morph amount.
+    pub gain: f32,
+    pub mode: FilterMode,
+    /// How many copies of this filter are cascaded. The Q and gain are for a single one.
+    pub stages: usize,
+}
+
+impl FilterDescriptor {
+    fn apply(&self, filters: &mut [StereoFilter; MAX_FILTER_STAGES]) {
+        for filter in &mut filters[..self.stages] {
+            match self.mode {
+                FilterMode::Peak | FilterMode::Morph => {
+                    filter.set_bell(self.frequency, self.q, self.gain);
+                }
+                FilterMode::Notch => filter.set_notch(self.frequency, self.q),
+            };
+        }
+    }
+}
+
+#[derive(Params)]
+struct ScaleColorizrParams {
+    #[persist = "editor-state"]
+    pub editor_state: Arc<EguiState>,
+    /// Where everything random starts from. This is saved with the project so bouncing it twice
+    /// comes out the same.
+    #[persist = "seed"]
+    pub seed: AtomicU64,
+
+    #[nested(group = "Envelope")]
+    pub envelope: EnvelopeParams,
+    #[nested(group = "Filter")]
+    pub filter: FilterParams,
+    #[nested(group = "Output")]
+    pub output: OutputParams,
+    #[nested(group = "Rhythm")]
+    pub rhythm: RhythmParams,
+    #[nested(group = "Performance")]
+    pub performance: PerformanceParams,
+    /// Offsets for each filter, so the harmonics can be skewed into chords or clusters.
+    #[nested(array, group = "Detune")]
+    pub detune: [DetuneParams; NUM_FILTERS],
+    /// How much of the band gain each filter gets. The falloff follows the harmonic series, so
+    /// moving a filter with spread or detune keeps its level.
+    #[nested(array, group = "Falloff")]
+    pub falloff: [FalloffParams; NUM_FILTERS],
+    #[nested(group = "Character")]
+    pub character: CharacterParams,
+    #[nested(group = "Scale")]
+    pub scale: ScaleParams,
+    #[nested(group = "Noise Gate")]
+    pub noise_gate: NoiseGateParams,
+
+    #[id = "voice-count"]
+    pub voice_count: IntParam,
+    #[id = "retrigger"]
+    pub retrigger: EnumParam<RetriggerMode>,
+    /// Releases the voices on the same channel whose notes are in the same group as a new note.
+    /// Groups are runs of [`choke_group_size`][Self::choke_group_size] notes starting from note 0,
+    /// so the default of 12 makes every octave from C up a group.
+    #[id = "choke-groups"]
+    pub choke_groups: BoolParam,
+    #[id = "choke-group-size"]
+    pub choke_group_size: IntParam,
+    /// Runs every voice on the dry signal and sums what each one changed, instead of chaining the
+    /// voices one after another. That can be split across threads, and it sounds slightly different
+    /// where filters overlap. This holds with too few voices for the threads to be used, too.
+    #[id = "multithreading"]
+    pub multithreading: BoolParam,
+    #[id = "midi-out"]
+    pub midi_out: BoolParam,
+}
+
+#[derive(Params)]
+struct EnvelopeParams {
+    #[id = "envelope-mode"]
+    pub mode: EnumParam<EnvelopeMode>,
+    /// How long one shots stay at full level after their attack.
+    #[id = "hold"]
+    pub hold: FloatParam,
+    /// How long one shots take to fade out after their hold.
+    #[id = "decay"]
+    pub decay: FloatParam,
+    /// How loud hits on the sidechain need to be to trigger the envelopes.
+    #[id = "trigger-threshold"]
+    pub trigger_threshold: FloatParam,
+    #[id = "attack"]
+    pub attack: FloatParam,
+    #[id = "release"]
+    pub release: FloatParam,
+    /// Gives [`FilterMode::Notch`] its own attack and release, since the attack that sounds good
+    /// on a boost is usually far too slow for cutting out a tone.
+    #[id = "separate-notch-times"]
+    pub separate_notch_times: BoolParam,
+    #[id = "notch-attack"]
+    pub notch_attack: FloatParam,
+    #[id = "notch-release"]
+    pub notch_release: FloatParam,
+    /// Gives notes that start within [`chord_window`][Self::chord_window] of another held voice
+    /// the shorter [`chord_attack`][Self::chord_attack], so a chord played one note at a time
+    /// doesn't swell all over again with every note.
+    #[id = "split-attack"]
+    pub split_attack: BoolParam,
+    #[id = "chord-attack"]
+    pub chord_attack: FloatParam,
+    #[id = "chord-window"]
+    pub chord_window: FloatParam,
+    /// From exponential at -1, through linear, to an s-curve at 1.
+    #[id = "attack-shape"]
+    pub attack_shape: FloatParam,
+    #[id = "release-shape"]
+    pub release_shape: FloatParam,
+    /// How much the attack time follows the note. At 100% the time halves every octave above
+    /// middle C and doubles every octave below it.
+    #[id = "attack-keytrack"]
+    pub attack_keytrack: FloatParam,
+    /// The same for the release time.
+    #[id = "release-keytrack"]
+    pub release_keytrack: FloatParam,
+
+    /// How much the width envelope adds to the normalized band width. Negative amounts start
+    /// narrow and open up.
+    #[id = "width-env-amount"]
+    pub width_amount: FloatParam,
+    #[id = "width-env-attack"]
+    pub width_attack: FloatParam,
+    #[id = "width-env-decay"]
+    pub width_decay: FloatParam,
+    /// Widens the filters and rolls off the upper harmonics over the course of the release.
+    #[id = "damping"]
+    pub damping: FloatParam,
+
+    /// How much each new voice's attack time and gain get randomly nudged.
+    #[id = "humanize"]
+    pub humanize: FloatParam,
+}
+
+/// Keeps quiet parts of the input from getting boosted by the filters.
+#[derive(Params)]
+struct NoiseGateParams {
+    #[id = "noise-gate"]
+    pub enabled: BoolParam,
+    #[id = "noise-gate-threshold"]
+    pub threshold: FloatParam,
+    #[id = "noise-gate-ratio"]
+    pub ratio: FloatParam,
+}
+
+#[derive(Params)]
+struct FilterParams {
+    #[id = "band-width"]
+    pub band_width: FloatParam,
+    /// How much the band width follows the note. Positive amounts make low notes wider and high
+    /// notes narrower.
+    #[id = "band-width-keytrack"]
+    pub keytrack: FloatParam,
+    /// How much the band width follows velocity. Positive amounts make hard hits wider and soft
+    /// ones narrower, with half velocity leaving it alone.
+    #[id = "band-width-velocity"]
+    pub velocity: FloatParam,
+    /// How far each filter gets randomly detuned when a voice starts, in cents either way.
+    #[id = "scatter"]
+    pub scatter: FloatParam,
+    /// The Q the filters get at the narrowest band width.
+    #[id = "max-resonance"]
+    pub max_resonance: FloatParam,
+    #[id = "resonance-curve"]
+    pub resonance_curve: EnumParam<ResonanceCurve>,
+    #[id = "filter-mod"]
+    pub filter_mode: EnumParam<FilterMode>,
+    /// Only used in [`FilterMode::Morph`]. Goes from cutting as much as the band gain would boost
+    /// at -1, through flat at 0, to a full peak at 1.
+    #[id = "filter-morph"]
+    pub morph: FloatParam,
+    #[id = "filter-slope"]
+    pub slope: EnumParam<FilterSlope>,
+    #[id = "freq-shift-mode"]
+    pub freq_shift: EnumParam<FrequencyShift>,
+    /// The shift in [`FrequencyShift::Note`], relative to the fundamental.
+    #[id = "freq-shift-ratio"]
+    pub freq_shift_ratio: FloatParam,
+    /// The shift in [`FrequencyShift::Hz`].
+    #[id = "freq-shift-hz"]
+    pub freq_shift_hz: FloatParam,
+    /// Puts the upper filters on a major or minor chord instead of the harmonic series.
+    #[id = "spread"]
+    pub spread: EnumParam<Spread>,
+    /// Fades odd harmonics out of the right channel and even ones out of the left.
+    #[id = "stereo-alternation"]
+    pub stereo_alternation: FloatParam,
+    /// Skips filters at or above Nyquist, where they'd alias back down. The editor shows this
+    /// flipped around, as allowing filters above Nyquist.
+    #[id = "safety-switch"]
+    pub safety_switch: BoolParam,
+    /// Widens filters in the top octave below Nyquist. This works whether the safety switch is on
+    /// or not.
+    #[id = "hf-compensation"]
+    pub hf_compensation: BoolParam,
+    /// Leaves out the harmonics above the audible range or Nyquist for each voice, so low notes
+    /// cost more than high ones instead of every voice running every filter.
+    #[id = "auto-harmonics"]
+    pub auto_harmonics: BoolParam,
+}
 
-pub type FrequencyDisplay = [[AtomicCell<Option<f32>>; NUM_FILTERS]; NUM_VOICES];
-pub type FilterDisplay = [[AtomicCell<Option<GenericSVF<f32x2>>>; NUM_FILTERS]; NUM_VOICES];
+#[derive(Params)]
+struct RhythmParams {
+    #[id = "quantize"]
+    pub quantize: EnumParam<QuantizeDivision>,
+    #[id = "gate"]
+    pub gate: BoolParam,
+    #[id = "gate-depth"]
+    pub gate_depth: FloatParam,
+    #[id = "gate-steps"]
+    pub gate_steps: EnumParam<GateSteps>,
+    #[nested(array, group = "Gate Pattern")]
+    pub gate_pattern: [GateStepParams; MAX_GATE_STEPS],
+}
+
+#[derive(Params)]
+struct PerformanceParams {
+    #[id = "freeze"]
+    pub freeze: BoolParam,
+    /// The MIDI CC that works as a footswitch for freeze. [`CC_OFF`] means there isn't one.
+    #[id = "freeze-cc"]
+    pub freeze_cc: IntParam,
+    /// A MIDI CC that sets the band width, overriding the parameter once it's been moved.
+    #[id = "band-width-cc"]
+    pub band_width_cc: IntParam,
+    /// Turns each channel's voices down with CC11, the expression pedal, without having to map
+    /// it to anything. The band width CC and freeze CC come first if either is set to 11.
+    #[id = "expression-cc"]
+    pub expression: BoolParam,
+    /// Note ons softer than this, as a MIDI velocity, get ignored so ghost notes don't take up
+    /// voices.
+    #[id = "min-velocity"]
+    pub min_velocity: IntParam,
+    /// The same for note ons harder than this.
+    #[id = "max-velocity"]
+    pub max_velocity: IntParam,
+    /// Note ons for a note that already started less than this long ago on the same channel get
+    /// merged into that voice, for setups that echo every note a second time.
+    #[id = "duplicate-window"]
+    pub duplicate_window: FloatParam,
+    /// Releases every held voice when the host stops, since plenty of hosts don't send note offs
+    /// for the notes that were playing. Voices held by freeze get released too.
+    #[id = "release-on-stop"]
+    pub release_on_stop: BoolParam,
+    /// The presets the editor's morph goes from and to, along with their names.
+    #[persist = "morph-presets"]
+    pub morph_presets: RwLock<[Option<(String, Preset)>; 2]>,
+    /// How far between the two presets the morph was last set, from 0 to 1. The morph is something
+    /// done from the editor like loading a preset, rather than a parameter, so hosts don't get a
+    /// gesture for every parameter on every frame it moves.
+    #[persist = "morph-amount"]
+    pub morph_amount: RwLock<f32>,
+}
+
+#[derive(Params)]
+struct DetuneParams {
+    #[id = "detune"]
+    pub cents: FloatParam,
+}
+
+/// Blends the harmonics' levels between two profiles with a single knob.
+#[derive(Params)]
+struct CharacterParams {
+    #[id = "character"]
+    pub amount: FloatParam,
+    #[id = "character-from"]
+    pub from: EnumParam<HarmonicProfile>,
+    #[id = "character-to"]
+    pub to: EnumParam<HarmonicProfile>,
+    /// Turns the upper harmonics down, or the lower ones down, on top of the character.
+    #[id = "tilt"]
+    pub tilt: FloatParam,
+}
+
+#[derive(Params)]
+struct FalloffParams {
+    #[id = "falloff"]
+    pub level: FloatParam,
+}
+
+#[derive(Params)]
+struct GateStepParams {
+    #[id = "gate-step"]
+    pub on: BoolParam,
+}
+
+#[derive(Params)]
+struct OutputParams {
+    /// Only matters in the audio layout with aux outputs.
+    #[id = "aux-routing"]
+    pub aux_routing: EnumParam<AuxRouting>,
+    #[id = "source"]
+    pub source: EnumParam<ExcitationSource>,
+    /// Applied to the input before it reaches the filters. The dry signal Delta subtracts gets it
+    /// too, so Delta still only leaves what the filters added.
+    #[id = "input-gain"]
+    pub input_gain: FloatParam,
+    /// Negative values turn the harmonics down in peak mode instead of boosting them.
+    #[id = "gain"]
+    pub gain: FloatParam,
+    /// Uses [`fine_gain`][Self::fine_gain] in place of [`gain`][Self::gain], for small boosts
+    /// and cuts.
+    #[id = "fine-gain-enabled"]
+    pub fine_gain_enabled: BoolParam,
+    #[id = "fine-gain"]
+    pub fine_gain: FloatParam,
+    /// Replaced by [`delta_mix`][Self::delta_mix]. This is hidden and does nothing, it's only kept
+    /// so [`Plugin::filter_state`] can move old projects that had it on over to the delta mix.
+    #[id = "delta"]
+    pub delta: BoolParam,
+    /// Goes from only what the filters added at -100%, through the dry signal at 0%, to the
+    /// regular output at 100%.
+    #[id = "delta-mix"]
+    pub delta_mix: FloatParam,
+    /// Turns the whole output down whenever its true peak would go over
+    /// [`ceiling`][Self::ceiling].
+    #[id = "ceiling-enabled"]
+    pub ceiling_enabled: BoolParam,
+    #[id = "ceiling"]
+    pub ceiling: FloatParam,
+    /// How far the dry signal gets turned down while the filters resonate.
+    #[id = "duck-dry"]
+    pub duck_dry: FloatParam,
+}
+
+impl Default for ScaleColorizr {
+    fn default() -> Self {
+        let (pre_spectrum_input, pre_spectrum_output) = SpectrumInput::new(2);
+        let (post_spectrum_input, post_spectrum_output) = SpectrumInput::new(2);
+        let (scope_input, scope_output) = ScopeInput::new();
+        let (filter_display_input, filter_display_output) =
+            TripleBuffer::new(&[VoiceDisplay::default(); NUM_VOICES]).split();
+
+        Self {
+            params: Arc::new(ScaleColorizrParams::default()),
+            default_preset_applied: AtomicBool::new(false),
+            // TODO: this feels dumb
+            voices: VoicePool::default(),
+            dry_signal: [f32x2::default(); MAX_BLOCK_SIZE],
+            voice_outputs: [[f32x2::default(); MAX_BLOCK_SIZE]; NUM_VOICES],
+            worker_pool: None,
+            filter_display_input,
+            filter_display_output: Arc::new(Mutex::new(filter_display_output)),
+            sample_rate: Arc::new(AtomicF32::new(1.0)),
+            midi_event_debug: Arc::new(AtomicCell::new(None)),
+            program_change: Arc::new(AtomicCell::new(None)),
+            pending_note_ons: Vec::with_capacity(MAX_PENDING_NOTE_ONS),
+            ignored_notes: [[false; 128]; 16],
+            gate: Smoother::new(SmoothingStyle::Linear(GATE_SMOOTHING_MS)),
+            gate_target: 1.0,
+            freeze_footswitch: false,
+            band_width_cc: Smoother::new(SmoothingStyle::Linear(CC_SMOOTHING_MS)),
+            fine_gain_mix: Smoother::new(SmoothingStyle::Linear(FINE_GAIN_CROSSFADE_MS)),
+            fine_gain_enabled: false,
+            band_width_cc_active: false,
+            channel_expression: [1.0; NUM_MIDI_CHANNELS],
+            frozen: false,
+            duck_envelope: 0.0,
+            transient_detector: TransientDetector::default(),
+            limiter: TruePeakLimiter::default(),
+            limiter_gain: Arc::new(AtomicCell::new(1.0)),
+            voices_stolen: Arc::new(AtomicU32::new(0)),
+            was_playing: false,
+            has_dry_output: false,
+            test_tone: TestTone::default(),
+            test_tone_settings: Arc::new(AtomicCell::new(TestToneSettings::default())),
+            midi_recorder: Arc::new(MidiRecorder::default()),
+            preset_scan: Arc::new(Mutex::new(PresetScan::default())),
+            recording_midi: false,
+            midi_recording_position: 0,
+            recorded_midi: Vec::with_capacity(midi_recorder::MAX_EVENTS_PER_BUFFER),
+            bank_select_msb: 0,
+            bank_select_lsb: 0,
+            next_internal_voice_id: 0,
+            pre_spectrum_input,
+            pre_spectrum_output: Arc::new(Mutex::new(pre_spectrum_output)),
+            post_spectrum_input,
+            post_spectrum_output: Arc::new(Mutex::new(post_spectrum_output)),
+            scope_input,
+            scope_output: Arc::new(Mutex::new(scope_output)),
+            correlation_meter: CorrelationMeter::default(),
+            correlation: Arc::new(AtomicF32::new(1.0)),
+            spectrum_settings: Arc::new(AtomicCell::new(SpectrumSettings::default())),
+            spectrum_background_elapsed: 0,
+        }
+    }
+}
+
+impl Default for ScaleColorizrParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_editor_state(),
+            seed: AtomicU64::new(0),
+            envelope: EnvelopeParams::default(),
+            filter: FilterParams::default(),
+            output: OutputParams::default(),
+            rhythm: RhythmParams::default(),
+            performance: PerformanceParams::default(),
+            detune: std::array::from_fn(|idx| DetuneParams {
+                cents: FloatParam::new(
+                    format!("Harmonic {} Detune", idx + 1),
+                    0.0,
+                    FloatRange::Linear {
+                        min: -1200.0,
+                        max: 1200.0,
+                    },
+                )
+                .with_unit(" cents")
+                .with_step_size(0.1),
+            }),
+            falloff: std::array::from_fn(|idx| {
+                // This used to be a fixed exponential falloff, which is still the default
+                #[allow(clippy::cast_precision_loss)]
+                let default = (-(idx as f32) / (NUM_FILTERS / 2) as f32).exp();
+                FalloffParams {
+                    level: FloatParam::new(
+                        format!("Harmonic {} Level", idx + 1),
+                        default,
+                        FloatRange::Linear { min: 0.0, max: 1.0 },
+                    )
+                    .with_unit("%")
+                    .with_value_to_string(formatters::v2s_f32_percentage(0))
+                    .with_string_to_value(formatters::s2v_f32_percentage()),
+                }
+            }),
+            character: CharacterParams::default(),
+            scale: ScaleParams::default(),
+            noise_gate: NoiseGateParams::default(),
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            voice_count: IntParam::new(
+                "Voices",
+                16,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_VOICES as i32,
+                },
+            ),
+            retrigger: EnumParam::new("Retrigger", RetriggerMode::AlwaysNew),
+            choke_groups: BoolParam::new("Choke Groups", false),
+            choke_group_size: IntParam::new(
+                "Choke Group Size",
+                12,
+                IntRange::Linear { min: 1, max: 24 },
+            )
+            .with_unit(" notes"),
+            multithreading: BoolParam::new("Multithreading", false)
+                .non_automatable()
+                .hide(),
+            midi_out: BoolParam::new("MIDI Out", false).non_automatable(),
+        }
+    }
+}
+
+impl Default for EnvelopeParams {
+    fn default() -> Self {
+        Self {
+            mode: EnumParam::new("Envelope Mode", EnvelopeMode::Sustain),
+            hold: FloatParam::new(
+                "Hold",
+                50.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            decay: FloatParam::new(
+                "Decay",
+                200.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 5000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            trigger_threshold: FloatParam::new(
+                "Trigger Threshold",
+                -30.0,
+                FloatRange::Linear {
+                    min: -60.0,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            attack: FloatParam::new(
+                "Attack",
+                2.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            release: FloatParam::new(
+                "Release",
+                10.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            separate_notch_times: BoolParam::new("Separate Notch Times", false),
+            notch_attack: FloatParam::new(
+                "Notch Attack",
+                2.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            notch_release: FloatParam::new(
+                "Notch Release",
+                10.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            split_attack: BoolParam::new("Split Attack", false),
+            chord_attack: FloatParam::new(
+                "Chord Attack",
+                20.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            chord_window: FloatParam::new(
+                "Chord Window",
+                250.0,
+                FloatRange::Linear {
+                    min: 10.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            // These used to be fixed exponential curves
+            attack_shape: FloatParam::new(
+                "Attack Shape",
+                -1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            release_shape: FloatParam::new(
+                "Release Shape",
+                -1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            width_amount: FloatParam::new(
+                "Width Env Amount",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            width_attack: FloatParam::new(
+                "Width Env Attack",
+                2.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 2000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            width_decay: FloatParam::new(
+                "Width Env Decay",
+                200.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 5000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            damping: FloatParam::new("Damping", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            humanize: FloatParam::new("Humanize", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            attack_keytrack: keytrack_param("Attack Keytrack"),
+            release_keytrack: keytrack_param("Release Keytrack"),
+        }
+    }
+}
+
+impl EnvelopeParams {
+    /// The attack parameter in use in `filter_mode`.
+    pub fn active_attack(&self, filter_mode: FilterMode) -> &FloatParam {
+        if filter_mode == FilterMode::Notch && self.separate_notch_times.value() {
+            &self.notch_attack
+        } else {
+            &self.attack
+        }
+    }
+
+    /// The release parameter in use in `filter_mode`.
+    pub fn active_release(&self, filter_mode: FilterMode) -> &FloatParam {
+        if filter_mode == FilterMode::Notch && self.separate_notch_times.value() {
+            &self.notch_release
+        } else {
+            &self.release
+        }
+    }
+
+    /// The attack time in ms for `note`, after keytracking.
+    fn attack_time(&self, note: u8, filter_mode: FilterMode) -> f32 {
+        self.active_attack(filter_mode).value()
+            * keytrack_time_scale(self.attack_keytrack.value(), note)
+    }
+
+    /// The attack time in ms for `note` joining a chord with split attack on. This is never slower
+    /// than the normal attack.
+    fn chord_attack_time(&self, note: u8, filter_mode: FilterMode) -> f32 {
+        self.chord_attack
+            .value()
+            .min(self.active_attack(filter_mode).value())
+            * keytrack_time_scale(self.attack_keytrack.value(), note)
+    }
+
+    /// The release time in ms for `note`, after keytracking.
+    fn release_time(&self, note: u8, filter_mode: FilterMode) -> f32 {
+        self.active_release(filter_mode).value()
+            * keytrack_time_scale(self.release_keytrack.value(), note)
+    }
+}
+
+/// A keytracking amount for one of the envelope times.
+fn keytrack_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Linear {
+            min: -1.0,
+            max: 1.0,
+        },
+    )
+    .with_unit("%")
+    .with_value_to_string(formatters::v2s_f32_percentage(0))
+    .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+/// How much to scale an envelope time by for `note`. Positive amounts make high notes shorter.
+fn keytrack_time_scale(amount: f32, note: u8) -> f32 {
+    (-amount * (f32::from(note) - KEYTRACK_CENTER_NOTE) / 12.0).exp2()
+}
+
+impl Default for NoiseGateParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Noise Gate", false),
+            threshold: FloatParam::new(
+                "Noise Gate Threshold",
+                -50.0,
+                FloatRange::Linear {
+                    min: -80.0,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            ratio: FloatParam::new(
+                "Noise Gate Ratio",
+                4.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(":1")
+            .with_step_size(0.1),
+        }
+    }
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self {
+            band_width: FloatParam::new(
+                "Band Width",
+                100.0,
+                FloatRange::Linear {
+                    min: 100.0,
+                    max: 300.0,
+                },
+            )
+            .with_unit("%")
+            .with_step_size(0.1),
+            keytrack: FloatParam::new(
+                "Band Width Keytrack",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            velocity: FloatParam::new(
+                "Band Width Velocity",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            scatter: FloatParam::new(
+                "Scatter",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" cents")
+            .with_step_size(0.1),
+            max_resonance: FloatParam::new(
+                "Max Resonance",
+                40.0,
+                FloatRange::Skewed {
+                    min: 2.0,
+                    max: 400.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_step_size(0.1),
+            resonance_curve: EnumParam::new("Resonance Curve", ResonanceCurve::Linear),
+            filter_mode: EnumParam::new("Filter Mode", FilterMode::Peak),
+            slope: EnumParam::new("Filter Slope", FilterSlope::Slope12),
+            freq_shift: EnumParam::new("Frequency Shift", FrequencyShift::Off),
+            freq_shift_ratio: FloatParam::new(
+                "Shift Ratio",
+                0.25,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            freq_shift_hz: FloatParam::new(
+                "Shift Hz",
+                50.0,
+                FloatRange::SymmetricalSkewed {
+                    min: -1000.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                    center: 0.0,
+                },
+            )
+            .with_unit(" Hz")
+            .with_step_size(0.1),
+            morph: FloatParam::new(
+                "Filter Morph",
+                1.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            spread: EnumParam::new("Spread", Spread::Harmonics),
+            stereo_alternation: FloatParam::new(
+                "Stereo Alternation",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            safety_switch: BoolParam::new("Skip Filters Above Nyquist", true).hide(),
+            hf_compensation: BoolParam::new("HF Compensation", false),
+            auto_harmonics: BoolParam::new("Automatic Harmonic Count", false),
+        }
+    }
+}
 
-pub const VERSION: &str = env!("VERGEN_GIT_DESCRIBE");
+impl Default for RhythmParams {
+    fn default() -> Self {
+        Self {
+            quantize: EnumParam::new("Quantize", QuantizeDivision::Off),
+            gate: BoolParam::new("Gate", false),
+            gate_depth: FloatParam::new(
+                "Gate Depth",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            gate_steps: EnumParam::new("Gate Steps", GateSteps::Sixteen),
+            // Every other step is open, so turning the gate on does something right away
+            gate_pattern: std::array::from_fn(|idx| GateStepParams {
+                on: BoolParam::new(format!("Gate Step {}", idx + 1), idx % 2 == 0),
+            }),
+        }
+    }
+}
 
-#[derive(Clone)]
-struct Voice {
-    id: i32,
-    channel: u8,
-    note: u8,
-    frequency: f32,
-    internal_voice_id: u64,
-    velocity_sqrt: f32,
-    filters: [GenericSVF<f32x2>; NUM_FILTERS],
-    releasing: bool,
-    amp_envelope: Smoother<f32>,
+/// A parameter for picking a MIDI CC to listen to, or [`CC_OFF`].
+fn cc_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(
+        name,
+        default,
+        IntRange::Linear {
+            min: 0,
+            max: CC_OFF,
+        },
+    )
+    .with_value_to_string(Arc::new(|value| {
+        if value == CC_OFF {
+            "Off".to_string()
+        } else {
+            value.to_string()
+        }
+    }))
+    .with_string_to_value(Arc::new(|string| {
+        if string.trim().eq_ignore_ascii_case("off") {
+            Some(CC_OFF)
+        } else {
+            string.trim().parse().ok()
+        }
+    }))
+    .non_automatable()
 }
 
-pub struct ScaleColorizr {
-    params: Arc<ScaleColorizrParams>,
-    voices: [Option<Voice>; NUM_VOICES],
-    dry_signal: [f32x2; MAX_BLOCK_SIZE],
-    frequency_display: Arc<FrequencyDisplay>,
-    filter_display: Arc<FilterDisplay>,
-    sample_rate: Arc<AtomicF32>,
-    midi_event_debug: Arc<AtomicCell<Option<NoteEvent<()>>>>,
-    next_internal_voice_id: u64,
-    pre_spectrum_input: SpectrumInput,
-    pre_spectrum_output: Option<SpectrumOutput>,
-    post_spectrum_input: SpectrumInput,
-    post_spectrum_output: Option<SpectrumOutput>,
+/// One end of the velocity range, as a MIDI velocity.
+fn velocity_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(name, default, IntRange::Linear { min: 1, max: 127 })
 }
 
-#[derive(Enum, PartialEq)]
-enum FilterMode {
-    Peak,
-    Notch,
+impl Default for CharacterParams {
+    fn default() -> Self {
+        Self {
+            amount: FloatParam::new("Character", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            from: EnumParam::new("Character From", HarmonicProfile::Custom),
+            to: EnumParam::new("Character To", HarmonicProfile::Bright),
+            tilt: FloatParam::new(
+                "Tilt",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
 }
 
-#[derive(Params)]
-struct ScaleColorizrParams {
-    #[persist = "editor-state"]
-    pub editor_state: Arc<EguiState>,
+impl CharacterParams {
+    /// How much of harmonic `idx`'s level the tilt leaves. Tilting down keeps the fundamental
+    /// where it is and tilting up keeps the top harmonic where it is, so nothing goes past the
+    /// band gain.
+    #[allow(clippy::cast_precision_loss)]
+    fn tilt_level(&self, idx: usize) -> f32 {
+        let tilt = self.tilt.value();
+        let harmonic = idx as f32 + 1.0;
+        if tilt < 0.0 {
+            harmonic.powf(tilt)
+        } else {
+            (harmonic / NUM_FILTERS as f32).powf(tilt)
+        }
+    }
+}
 
-    #[id = "gain"]
-    pub gain: FloatParam,
-    #[id = "attack"]
-    pub attack: FloatParam,
-    #[id = "release"]
-    pub release: FloatParam,
-    #[id = "band-width"]
-    pub band_width: FloatParam,
-    #[id = "delta"]
-    pub delta: BoolParam,
-    #[id = "safety-switch"]
-    pub safety_switch: BoolParam,
-    #[id = "voice-count"]
-    pub voice_count: IntParam,
-    #[id = "filter-mod"]
-    pub filter_mode: EnumParam<FilterMode>,
+impl PerformanceParams {
+    /// Whether a note on with `velocity` is inside the velocity range. Nothing is if the minimum
+    /// is above the maximum.
+    fn accepts_velocity(&self, velocity: f32) -> bool {
+        #[allow(clippy::cast_possible_truncation)]
+        let velocity = (velocity * 127.0).round() as i32;
+        (self.min_velocity.value()..=self.max_velocity.value()).contains(&velocity)
+    }
 }
 
-impl Default for ScaleColorizr {
+impl Default for PerformanceParams {
     fn default() -> Self {
-        let (pre_spectrum_input, pre_spectrum_output) = SpectrumInput::new(2);
-        let (post_spectrum_input, post_spectrum_output) = SpectrumInput::new(2);
-
         Self {
-            params: Arc::new(ScaleColorizrParams::default()),
-            // TODO: this feels dumb
-            voices: [0; NUM_VOICES].map(|_| None),
-            dry_signal: [f32x2::default(); MAX_BLOCK_SIZE],
-            frequency_display: Arc::new(core::array::from_fn(|_| {
-                core::array::from_fn(|_| AtomicCell::default())
-            })),
-            filter_display: Arc::new(core::array::from_fn(|_| {
-                core::array::from_fn(|_| AtomicCell::default())
-            })),
-            sample_rate: Arc::new(AtomicF32::new(1.0)),
-            midi_event_debug: Arc::new(AtomicCell::new(None)),
-            next_internal_voice_id: 0,
-            pre_spectrum_input,
-            pre_spectrum_output: Some(pre_spectrum_output),
-            post_spectrum_input,
-            post_spectrum_output: Some(post_spectrum_output),
+            freeze: BoolParam::new("Freeze", false),
+            // CC 69 is hold 2, which footswitches send a lot of the time
+            freeze_cc: cc_param("Freeze CC", 69),
+            band_width_cc: cc_param("Band Width CC", CC_OFF),
+            expression: BoolParam::new("CC11 Expression", false),
+            min_velocity: velocity_param("Min Velocity", 1),
+            max_velocity: velocity_param("Max Velocity", 127),
+            duplicate_window: FloatParam::new(
+                "Duplicate Note Window",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+            release_on_stop: BoolParam::new("Release On Stop", false),
+            morph_presets: RwLock::default(),
+            morph_amount: RwLock::default(),
         }
     }
 }
 
-impl Default for ScaleColorizrParams {
+impl Default for OutputParams {
     fn default() -> Self {
         Self {
-            editor_state: editor::default_editor_state(),
+            source: EnumParam::new("Source", ExcitationSource::Main),
+            aux_routing: EnumParam::new("Aux Routing", AuxRouting::RoundRobin),
+            input_gain: FloatParam::new(
+                "Input Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 24.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
             gain: FloatParam::new(
                 "Band Gain",
                 10.0,
                 FloatRange::Linear {
-                    min: 2.0,
+                    min: -24.0,
                     max: 40.0,
                 },
             )
             .with_step_size(0.1)
             .with_unit(" dB"),
-            attack: FloatParam::new(
-                "Attack",
-                2.0,
+            fine_gain_enabled: BoolParam::new("Fine Band Gain", false),
+            fine_gain: FloatParam::new(
+                "Band Gain (Fine)",
+                1.0,
                 FloatRange::Linear {
-                    min: 2.0,
-                    max: 2000.0,
+                    min: -2.0,
+                    max: 2.0,
                 },
             )
-            .with_unit(" ms")
-            .with_step_size(0.1),
-            release: FloatParam::new(
-                "Release",
-                10.0,
+            .with_step_size(0.01)
+            .with_unit(" dB"),
+            delta: BoolParam::new("Delta", false).hide(),
+            delta_mix: FloatParam::new(
+                "Delta Mix",
+                1.0,
                 FloatRange::Linear {
-                    min: 2.0,
-                    max: 2000.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
-            .with_unit(" ms")
-            .with_step_size(0.1),
-
-            band_width: FloatParam::new(
-                "Band Width",
-                100.0,
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(Arc::new(|value| {
+                let percentage = (value.abs() * 100.0).round();
+                if value < 0.0 {
+                    format!("{percentage}% Delta")
+                } else {
+                    format!("{percentage}% Wet")
+                }
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                let string = string.trim().to_lowercase();
+                let sign = if string.contains("delta") { -1.0 } else { 1.0 };
+                let number = string.trim_end_matches(|c: char| !c.is_ascii_digit() && c != '.');
+                number.parse::<f32>().ok().map(|value| sign * value / 100.0)
+            })),
+            ceiling_enabled: BoolParam::new("Ceiling", false),
+            ceiling: FloatParam::new(
+                "Ceiling Level",
+                -0.3,
                 FloatRange::Linear {
-                    min: 100.0,
-                    max: 300.0,
+                    min: -24.0,
+                    max: 0.0,
                 },
             )
-            .with_unit("%")
+            .with_unit(" dBTP")
             .with_step_size(0.1),
+            duck_dry: FloatParam::new("Duck Dry", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
 
-            delta: BoolParam::new("Delta", false),
-            safety_switch: BoolParam::new("SAFETY SWITCH", true).hide(),
-            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
-            voice_count: IntParam::new(
-                "Voices",
-                16,
-                IntRange::Linear {
-                    min: 1,
-                    max: NUM_VOICES as i32,
-                },
-            ),
-            filter_mode: EnumParam::new("Filter Mode", FilterMode::Peak),
+impl OutputParams {
+    /// The band gain parameter in use, depending on whether fine mode is on.
+    pub fn band_gain(&self) -> &FloatParam {
+        if self.fine_gain_enabled.value() {
+            &self.fine_gain
+        } else {
+            &self.gain
         }
     }
 }
@@ -178,21 +2028,73 @@ impl Plugin for ScaleColorizr {
 
     // The first audio IO layout is used as the default. The other layouts may be selected either
     // explicitly or automatically by the host or the user depending on the plugin API/backend.
-    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
-        main_input_channels: NonZeroU32::new(2),
-        main_output_channels: NonZeroU32::new(2),
-
-        aux_input_ports: &[],
-        aux_output_ports: &[],
-
-        // Individual ports and the layout as a whole can be named here. By default these names
-        // are generated as needed. This layout will be called 'Stereo', while a layout with
-        // only one input and output channel would be called 'Mono'.
-        names: PortNames::const_default(),
-    }];
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            // The filters can run on this instead of the main input, see `ExcitationSource`
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[],
+
+            // Individual ports and the layout as a whole can be named here. By default these names
+            // are generated as needed. This layout will be called 'Stereo', while a layout with
+            // only one input and output channel would be called 'Mono'.
+            names: PortNames {
+                aux_inputs: &["Sidechain"],
+                ..PortNames::const_default()
+            },
+        },
+        // The same, but with every voice's resonance also going to one of the aux outputs, so
+        // voices can be mixed separately
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[new_nonzero_u32(2); NUM_OUTPUT_GROUPS],
+
+            names: PortNames {
+                layout: Some("Stereo + Voice Groups"),
+                aux_inputs: &["Sidechain"],
+                aux_outputs: &["Group 1", "Group 2", "Group 3", "Group 4"],
+                ..PortNames::const_default()
+            },
+        },
+        // The input after the input gain, untouched otherwise, for mixing dry and wet outside of
+        // the plugin
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[new_nonzero_u32(2)],
+
+            names: PortNames {
+                layout: Some("Stereo + Dry"),
+                aux_inputs: &["Sidechain"],
+                aux_outputs: &[DRY_OUTPUT_NAME],
+                ..PortNames::const_default()
+            },
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+
+            aux_input_ports: &[new_nonzero_u32(2)],
+            aux_output_ports: &[new_nonzero_u32(2); NUM_OUTPUT_GROUPS + 1],
+
+            names: PortNames {
+                layout: Some("Stereo + Voice Groups + Dry"),
+                aux_inputs: &["Sidechain"],
+                aux_outputs: &["Group 1", "Group 2", "Group 3", "Group 4", DRY_OUTPUT_NAME],
+                ..PortNames::const_default()
+            },
+        },
+    ];
 
     const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
-    const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
@@ -200,76 +2102,293 @@ impl Plugin for ScaleColorizr {
     // messages here. The type implements the `SysExMessage` trait, which allows conversion to and
     // from plain byte buffers.
     type SysExMessage = ();
-    // More advanced plugins can use this to run expensive background tasks. See the field's
-    // documentation for more information. `()` means that the plugin does not have any background
-    // tasks.
-    type BackgroundTask = ();
+    type BackgroundTask = BackgroundTask;
 
     fn params(&self) -> Arc<dyn Params> {
+        // Only the wrappers ask for this, right after creating the plugin and before restoring any
+        // state, so this is where new instances pick up the default preset. Offline renders and
+        // the benchmarks never see it, so they come out the same on every machine
+        if !self
+            .default_preset_applied
+            .swap(true, std::sync::atomic::Ordering::Relaxed)
+        {
+            if let Some(preset) = Preset::load_default() {
+                preset.apply_before_init(self.params.as_ref());
+            }
+        }
+
         self.params.clone()
     }
 
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         editor::create(
             self.params.clone(),
-            self.frequency_display.clone(),
-            self.pre_spectrum_output.take().expect("either the pre spectrum didn't initialize properly, or the editor is being queried twice. either way, something has gone horribly wrong"),
-            self.post_spectrum_output.take().expect("either the post spectrum didn't initialize properly, or the editor is being queried twice. either way, something has gone horribly wrong"),
+            self.pre_spectrum_output.clone(),
+            self.post_spectrum_output.clone(),
+            self.scope_output.clone(),
+            self.correlation.clone(),
+            self.limiter_gain.clone(),
+            self.voices_stolen.clone(),
+            self.test_tone_settings.clone(),
+            self.midi_recorder.clone(),
+            self.preset_scan.clone(),
+            async_executor,
+            self.spectrum_settings.clone(),
             self.sample_rate.clone(),
             self.midi_event_debug.clone(),
-            self.filter_display.clone(),
+            self.program_change.clone(),
+            self.filter_display_output.clone(),
         )
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let midi_recorder = self.midi_recorder.clone();
+        let preset_scan = self.preset_scan.clone();
+        let params = self.params.clone();
+        let sample_rate = self.sample_rate.clone();
+        Box::new(move |task| match task {
+            BackgroundTask::RecordMidi { time, event } => midi_recorder.record(time, event),
+            BackgroundTask::SaveMidiRecording { path, sample_rate } => {
+                midi_recorder.save(&path, sample_rate);
+            }
+            BackgroundTask::ScanPresets => PresetScan::run(&preset_scan),
+            BackgroundTask::LoadProgram(program) => editor::load_program_without_editor(
+                program,
+                &params,
+                &preset_scan,
+                sample_rate.load(std::sync::atomic::Ordering::Relaxed),
+            ),
+        })
+    }
+
+    fn filter_state(state: &mut PluginState) {
+        // Projects saved with the old delta switch on get the delta mix that sounds the same
+        if let Some(ParamValue::Bool(true)) = state.params.get("delta") {
+            state
+                .params
+                .insert("delta-mix".to_string(), ParamValue::F32(-1.0));
+            state
+                .params
+                .insert("delta".to_string(), ParamValue::Bool(false));
+        }
+    }
+
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
-        self.sample_rate.store(
-            buffer_config.sample_rate,
-            std::sync::atomic::Ordering::Relaxed,
-        );
+        self.has_dry_output = audio_io_layout.names.aux_outputs.last() == Some(&DRY_OUTPUT_NAME);
+        self.set_sample_rate(buffer_config.sample_rate);
+        #[allow(clippy::cast_sign_loss)]
+        context.set_current_voice_capacity(self.params.voice_count.value() as u32);
 
         self.pre_spectrum_input
             .update_sample_rate(buffer_config.sample_rate);
         self.post_spectrum_input
             .update_sample_rate(buffer_config.sample_rate);
 
+        if self.worker_pool.is_none() {
+            let num_workers = std::thread::available_parallelism()
+                .map_or(1, std::num::NonZeroUsize::get)
+                .saturating_sub(1)
+                .clamp(1, MAX_WORKER_THREADS);
+            self.worker_pool = Some(WorkerPool::new(num_workers));
+        }
+
         true
     }
 
     fn reset(&mut self) {
-        for voice in &mut self.voices {
-            if voice.is_some() {
-                *voice = None;
-            }
-        }
+        self.voices.clear();
+        self.pending_note_ons.clear();
+        self.ignored_notes = [[false; 128]; 16];
+        self.gate.reset(1.0);
+        self.gate_target = 1.0;
+        self.freeze_footswitch = false;
+        self.band_width_cc_active = false;
+        self.fine_gain_enabled = self.params.output.fine_gain_enabled.value();
+        self.fine_gain_mix
+            .reset(f32::from(u8::from(self.fine_gain_enabled)));
+        self.channel_expression = [1.0; NUM_MIDI_CHANNELS];
+        self.frozen = false;
+        self.duck_envelope = 0.0;
+        self.transient_detector.reset();
+        self.limiter.reset();
+        self.correlation_meter.reset();
+        // Humanize is seeded from this, so renders come out the same every time
+        self.next_internal_voice_id = 0;
     }
 
-    #[allow(clippy::too_many_lines)]
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
+        aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        // NIH-plug only tells the host about this when it actually changes
+        #[allow(clippy::cast_sign_loss)]
+        context.set_current_voice_capacity(self.params.voice_count.value() as u32);
+
+        let editor_open = self.params.editor_state.is_open();
+        let spectrum_settings = self.spectrum_settings.load();
+        let compute_spectrum =
+            editor_open || self.background_spectrum_due(spectrum_settings, buffer.samples());
+        if compute_spectrum {
+            self.pre_spectrum_input.set_settings(spectrum_settings);
+            self.pre_spectrum_input.compute(buffer);
+        }
+        if editor_open {
+            self.scope_input.write_dry(buffer);
+        }
+
+        let sidechain = aux.inputs.first().map(|sidechain| {
+            let [left, right] = sidechain.as_slice_immutable() else {
+                unreachable!("the sidechain is always stereo")
+            };
+            [&**left, &**right]
+        });
+        let recording_midi = self
+            .midi_recorder
+            .active
+            .load(std::sync::atomic::Ordering::Relaxed);
+        if recording_midi && !self.recording_midi {
+            self.midi_recording_position = 0;
+        }
+        self.recording_midi = recording_midi;
+
+        {
+            let mut host = PluginHost(&mut *context);
+            let mut host = self.test_tone.wrap(
+                &mut host,
+                self.test_tone_settings.load(),
+                buffer.samples(),
+                self.sample_rate.load(std::sync::atomic::Ordering::Relaxed),
+            );
+            self.process_audio(buffer.as_slice(), sidechain, aux.outputs, &mut host);
+        }
+
+        // The editor loads these itself while it's open, so the host hears about the new values
+        if !self.params.editor_state.is_open() {
+            if let Some(program) = self.program_change.take() {
+                context.execute_background(BackgroundTask::LoadProgram(program));
+            }
+        }
+
+        if self.recording_midi {
+            for (time, event) in self.recorded_midi.drain(..) {
+                context.execute_background(BackgroundTask::RecordMidi { time, event });
+            }
+            self.midi_recording_position += buffer.samples() as u64;
+        }
+
+        if editor_open {
+            self.scope_input.write_wet(buffer);
+            let sample_rate = self.sample_rate.load(std::sync::atomic::Ordering::Relaxed);
+            self.correlation.store(
+                self.correlation_meter.process(buffer, sample_rate),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let hold_samples = (self.params.envelope.hold.value() / 1000.0 * sample_rate) as u32;
+            let envelope_mode = self.params.envelope.mode.value();
+            let filter_display = self.filter_display_input.input_buffer();
+            for (voice, display) in self.voices.slots().iter().zip(filter_display.iter_mut()) {
+                *display =
+                    voice
+                        .as_ref()
+                        .map_or_else(VoiceDisplay::default, |voice| VoiceDisplay {
+                            filters: voice.filter_descriptors,
+                            skipped: voice.skipped_frequencies,
+                            envelope: Some(voice.envelope_position(envelope_mode, hold_samples)),
+                            note: voice.note,
+                            #[allow(clippy::cast_precision_loss)]
+                            age: voice.age as f32 / sample_rate,
+                        });
+            }
+            self.filter_display_input.publish();
+        }
+        if compute_spectrum {
+            self.post_spectrum_input.set_settings(spectrum_settings);
+            self.post_spectrum_input.compute(buffer);
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ScaleColorizr {
+    /// Whether the spectrum should be updated on this buffer while the editor is closed. The
+    /// spectrum only ever sees a buffer every so often then, which is plenty for keeping it roughly
+    /// up to date.
+    fn background_spectrum_due(&mut self, settings: SpectrumSettings, num_samples: usize) -> bool {
+        if settings.background_interval_ms == 0 {
+            return false;
+        }
+
+        let sample_rate = self.sample_rate.load(std::sync::atomic::Ordering::Relaxed);
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_precision_loss,
+            clippy::cast_sign_loss
+        )]
+        let interval = (settings.background_interval_ms as f32 / 1000.0 * sample_rate) as usize;
+        self.spectrum_background_elapsed += num_samples;
+        if self.spectrum_background_elapsed < interval {
+            return false;
+        }
+
+        self.spectrum_background_elapsed = 0;
+        true
+    }
+
+    /// Switch to a new sample rate, bringing any voices that are still playing along with it.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let old_sample_rate = self
+            .sample_rate
+            .swap(sample_rate, std::sync::atomic::Ordering::Relaxed);
+        if old_sample_rate == sample_rate {
+            return;
+        }
+
+        for voice in self.voices.iter_mut() {
+            voice.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Process the main input in place. If there's a `sidechain`, the filters can run on that
+    /// instead, depending on [`ExcitationSource`]. If there are `aux_outputs`, they get what each
+    /// group of voices added, and the dry output gets the main input after the input gain.
+    #[allow(clippy::too_many_lines)]
+    fn process_audio(
+        &mut self,
+        output: &mut [&mut [f32]],
+        sidechain: Option<[&[f32]; 2]>,
+        aux_outputs: &mut [Buffer],
+        host: &mut impl ProcessHost,
+    ) {
         // NIH-plug has a block-splitting adapter for `Buffer`. While this works great for effect
         // plugins, for polyphonic synths the block size should be `min(MAX_BLOCK_SIZE,
         // num_remaining_samples, next_event_idx - block_start_idx)`. Because blocks also need to be
         // split on note events, it's easier to work with raw audio here and to do the splitting by
         // hand.
-
-        if self.params.editor_state.is_open() {
-            self.pre_spectrum_input.compute(buffer);
-        }
-
-        let num_samples = buffer.samples();
+        let num_samples = output[0].len();
         let sample_rate = self.sample_rate.load(std::sync::atomic::Ordering::Relaxed);
-        let output = buffer.as_slice();
+        let (aux_outputs, mut dry_output) = split_dry_output(aux_outputs, self.has_dry_output);
 
-        let mut next_event = context.next_event();
+        let transport = host.transport();
+        if transport.playing && !self.was_playing {
+            self.voices_stolen
+                .store(0, std::sync::atomic::Ordering::Relaxed);
+        }
+        if self.was_playing && !transport.playing && self.params.performance.release_on_stop.value()
+        {
+            self.release_all(host, sample_rate);
+        }
+        self.was_playing = transport.playing;
+        let mut next_event = host.next_event();
         let mut block_start: usize = 0;
         let mut block_end: usize = MAX_BLOCK_SIZE.min(num_samples);
         while block_start < num_samples {
@@ -277,7 +2396,7 @@ impl Plugin for ScaleColorizr {
                 &mut next_event,
                 block_start,
                 sample_rate,
-                context,
+                host,
                 &mut block_end,
             );
 
@@ -285,138 +2404,311 @@ impl Plugin for ScaleColorizr {
             // have polyphonic modulation applied to them. With a plugin as simple as this it would
             // be possible to avoid this completely by simply always copying the smoother into the
             // voice's struct, but that may not be realistic when the plugin has hundreds of
-            // parameters. The `voice_*` arrays are scratch arrays that an individual voice can use.
+            // parameters.
             let block_len = block_end - block_start;
-            let mut gain = [0.0; MAX_BLOCK_SIZE];
-            let mut voice_amp_envelope = [0.0; MAX_BLOCK_SIZE];
-            self.params.gain.smoothed.next_block(&mut gain, block_len);
-
-            for (value_idx, sample_idx) in (block_start..block_end).enumerate() {
-                self.dry_signal[value_idx] =
-                    f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
+            self.update_holds(host, block_start, block_len, sample_rate);
+            self.follow_fine_gain(sample_rate);
+            for voice in self.voices.iter_mut() {
+                voice.age += block_len as u64;
             }
 
-            for voice in self.voices.iter_mut().filter_map(|v| v.as_mut()) {
-                voice
-                    .amp_envelope
-                    .next_block(&mut voice_amp_envelope, block_len);
+            if self.is_idle() {
+                self.skip_idle_block(output, aux_outputs, block_start, block_end);
+                // The input gain got applied in place, so the output is the dry signal
+                if let Some(dry_output) = dry_output.as_mut() {
+                    for (dry_channel, channel) in dry_output.as_slice().iter_mut().zip(&*output) {
+                        dry_channel[block_start..block_end]
+                            .copy_from_slice(&channel[block_start..block_end]);
+                    }
+                }
 
-                for (value_idx, sample_idx) in (block_start..block_end).enumerate() {
-                    let amp = gain[value_idx] * voice.velocity_sqrt * voice_amp_envelope[value_idx];
-                    let mut sample =
-                        f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
+                block_start = block_end;
+                block_end = (block_start + MAX_BLOCK_SIZE).min(num_samples);
+                continue;
+            }
 
-                    for (filter_idx, filter) in voice.filters.iter_mut().enumerate() {
-                        #[allow(clippy::cast_precision_loss)]
-                        let frequency = voice.frequency * (filter_idx as f32 + 1.0);
+            let mut gain = [0.0; MAX_BLOCK_SIZE];
+            self.next_band_gain_block(&mut gain, block_len);
+            self.apply_gate(&mut gain[..block_len], transport, block_start, sample_rate);
+            let gain = &gain[..block_len];
+
+            // Events split blocks, so a CC's smoothing starts on the exact sample it came in on
+            let mut band_width = [0.0; MAX_BLOCK_SIZE];
+            if self.params.performance.band_width_cc.value() == CC_OFF {
+                self.band_width_cc_active = false;
+            }
+            if self.band_width_cc_active {
+                self.band_width_cc.next_block(&mut band_width, block_len);
+            } else {
+                band_width.fill(self.params.filter.band_width.modulated_normalized_value());
+            }
+            let band_width = &band_width[..block_len];
+
+            let mut input_gain = [0.0; MAX_BLOCK_SIZE];
+            self.params
+                .output
+                .input_gain
+                .smoothed
+                .next_block(&mut input_gain, block_len);
+
+            if let (EnvelopeMode::SidechainTrigger, Some([left, right])) =
+                (self.params.envelope.mode.value(), sidechain)
+            {
+                let threshold = util::db_to_gain(self.params.envelope.trigger_threshold.value());
+                let mut is_hit = false;
+                for (left, right) in left[block_start..block_end]
+                    .iter()
+                    .zip(&right[block_start..block_end])
+                {
+                    let sample = left.abs().max(right.abs());
+                    is_hit |= self
+                        .transient_detector
+                        .process(sample, threshold, sample_rate);
+                }
+                if is_hit {
+                    self.trigger_voices(sample_rate);
+                }
+            }
 
-                        if self.params.safety_switch.value() && frequency >= sample_rate / 2.0 {
-                            continue;
-                        }
+            let source = self.params.output.source.value();
+            let sidechain = sidechain.filter(|_| source != ExcitationSource::Main);
+            // The dry signal is always the main input, so the delta mix fades towards it no matter
+            // what the filters are running on. Sidechain only drops it, so there it's silence.
+            let drop_main = sidechain.is_some() && source == ExcitationSource::SidechainOnly;
+            let mut main = [f32x2::default(); MAX_BLOCK_SIZE];
+            let mut excitation = [f32x2::default(); MAX_BLOCK_SIZE];
+            for ((value_idx, sample_idx), input_gain) in
+                (block_start..block_end).enumerate().zip(input_gain)
+            {
+                main[value_idx] = f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
+                excitation[value_idx] = sidechain.map_or(main[value_idx], |[left, right]| {
+                    f32x2::from_array([left[sample_idx], right[sample_idx]])
+                }) * f32x2::splat(input_gain);
+                self.dry_signal[value_idx] = if drop_main {
+                    f32x2::default()
+                } else {
+                    main[value_idx] * f32x2::splat(input_gain)
+                };
+            }
+            if let Some(dry_output) = dry_output.as_mut() {
+                let dry_output = dry_output.as_slice();
+                for ((sample, input_gain), sample_idx) in
+                    main.iter().zip(input_gain).zip(block_start..block_end)
+                {
+                    let sample = *sample * f32x2::splat(input_gain);
+                    dry_output[0][sample_idx] = sample.as_array()[0];
+                    dry_output[1][sample_idx] = sample.as_array()[1];
+                }
+            }
+            let mut wet = excitation;
+            let excitation = &excitation[..block_len];
+            let wet = &mut wet[..block_len];
+
+            let track_groups = !aux_outputs.is_empty();
+            let boost_cut = self.params.output.aux_routing.value() == AuxRouting::BoostCut;
+            let mut group_outputs = [[f32x2::default(); MAX_BLOCK_SIZE]; NUM_OUTPUT_GROUPS];
+            // The filters are from the last block, which is close enough for picking a side
+            if track_groups && boost_cut {
+                for voice in self.voices.iter_mut() {
+                    voice.follow_boost_cut(block_len, sample_rate);
+                }
+            }
 
-                        #[allow(clippy::cast_precision_loss)]
-                        let adjusted_frequency = (frequency - voice.frequency)
-                            / (voice.frequency * (NUM_FILTERS / 2) as f32);
-                        let amp_falloff = (-adjusted_frequency).exp();
-                        filter.set_sample_rate(sample_rate);
+            // Multithreading picks how the voices get mixed, whether or not there are enough of
+            // them to be worth splitting up, so the sound doesn't change as voices come and go
+            if self.params.multithreading.value() {
+                let voices = SharedMut(self.voices.slots_mut().as_mut_ptr());
+                let voice_outputs = SharedMut(self.voice_outputs.as_mut_ptr());
+                let params = &*self.params;
+                let process_voice = |voice_idx| {
+                    // SAFETY: every index is only handed out once per block
+                    let (voice, voice_output) =
+                        unsafe { (voices.get(voice_idx), voice_outputs.get(voice_idx)) };
+                    if let Some(voice) = voice {
+                        let voice_output = &mut voice_output[..block_len];
+                        voice_output.copy_from_slice(excitation);
+                        voice.process(voice_output, gain, band_width, params, sample_rate);
+                    }
+                };
+                match &self.worker_pool {
+                    Some(pool) if self.voices.active_voices() >= MIN_THREADED_VOICES => {
+                        pool.execute(NUM_VOICES, &process_voice);
+                    }
+                    _ => (0..NUM_VOICES).for_each(process_voice),
+                }
 
-                        let q = 39.0f32
-                            .mul_add(-self.params.band_width.modulated_normalized_value(), 40.0);
+                // The voices can't be chained when they run in parallel, so every voice filters
+                // the excitation signal and only what it changed gets summed back in
+                for (voice, voice_output) in self
+                    .voices
+                    .slots()
+                    .iter()
+                    .zip(&self.voice_outputs)
+                    .filter_map(|(voice, output)| Some((voice.as_ref()?, output)))
+                {
+                    for (value_idx, ((wet, voice_output), excitation)) in
+                        wet.iter_mut().zip(voice_output).zip(excitation).enumerate()
+                    {
+                        let change = *voice_output - *excitation;
+                        *wet += change;
+                        if track_groups {
+                            voice.add_to_group(
+                                &mut group_outputs,
+                                boost_cut,
+                                value_idx,
+                                block_len,
+                                change,
+                            );
+                        }
+                    }
+                }
+            } else {
+                for voice in self.voices.iter_mut() {
+                    if track_groups {
+                        let mut before = [f32x2::default(); MAX_BLOCK_SIZE];
+                        before[..block_len].copy_from_slice(wet);
+                        voice.process(wet, gain, band_width, &self.params, sample_rate);
+                        for (value_idx, (wet, before)) in wet.iter().zip(before).enumerate() {
+                            voice.add_to_group(
+                                &mut group_outputs,
+                                boost_cut,
+                                value_idx,
+                                block_len,
+                                *wet - before,
+                            );
+                        }
+                    } else {
+                        voice.process(wet, gain, band_width, &self.params, sample_rate);
+                    }
+                }
+            }
 
-                        match self.params.filter_mode.value() {
-                            FilterMode::Peak => filter.set_bell(frequency, q, amp * amp_falloff),
-                            FilterMode::Notch => filter.set_notch(frequency, q),
-                        };
+            for (aux_output, group_output) in aux_outputs.iter_mut().zip(&group_outputs) {
+                let aux_output = aux_output.as_slice();
+                for (sample, sample_idx) in group_output.iter().zip(block_start..block_end) {
+                    aux_output[0][sample_idx] = sample.as_array()[0];
+                    aux_output[1][sample_idx] = sample.as_array()[1];
+                }
+            }
 
-                        sample = filter.process(sample);
-                    }
+            duck_dry(
+                &mut self.duck_envelope,
+                self.params.output.duck_dry.value(),
+                wet,
+                excitation,
+                sample_rate,
+            );
 
-                    output[0][sample_idx] = sample.as_array()[0];
-                    output[1][sample_idx] = sample.as_array()[1];
+            if sidechain.is_some() && source == ExcitationSource::SidechainWithDry {
+                for (sample, dry) in wet.iter_mut().zip(&self.dry_signal) {
+                    *sample += *dry;
                 }
             }
 
-            if self.params.delta.value() {
-                for (value_idx, sample_idx) in (block_start..block_end).enumerate() {
-                    let mut sample =
-                        f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
-                    sample += self.dry_signal[value_idx] * f32x2::splat(-1.0);
+            for (sample, sample_idx) in wet.iter().zip(block_start..block_end) {
+                output[0][sample_idx] = sample.as_array()[0];
+                output[1][sample_idx] = sample.as_array()[1];
+            }
+
+            let mut delta_mix = [0.0; MAX_BLOCK_SIZE];
+            self.params
+                .output
+                .delta_mix
+                .smoothed
+                .next_block(&mut delta_mix, block_len);
+            #[allow(clippy::float_cmp)]
+            if delta_mix[..block_len].iter().any(|&mix| mix != 1.0) {
+                for ((value_idx, sample_idx), mix) in
+                    (block_start..block_end).enumerate().zip(delta_mix)
+                {
+                    let sample = f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
+                    let dry = self.dry_signal[value_idx];
+                    let delta = sample - dry;
+                    let sample = if mix >= 0.0 {
+                        dry + delta * f32x2::splat(mix)
+                    } else {
+                        dry * f32x2::splat(1.0 + mix) + delta * f32x2::splat(-mix)
+                    };
 
                     output[0][sample_idx] = sample.as_array()[0];
                     output[1][sample_idx] = sample.as_array()[1];
                 }
             }
 
-            // Terminate voices whose release period has fully ended. This could be done as part of
-            // the previous loop but this is simpler.
-            for voice in &mut self.voices {
-                match voice {
-                    Some(v) if v.releasing && v.amp_envelope.previous_value() == 0.0 => {
-                        // This event is very important, as it allows the host to manage its own modulation
-                        // voices
-                        #[allow(clippy::cast_possible_truncation)]
-                        context.send_event(NoteEvent::VoiceTerminated {
-                            timing: block_end as u32,
-                            voice_id: Some(v.id),
-                            channel: v.channel,
-                            note: v.note,
-                        });
-                        *voice = None;
-                    }
-                    _ => (),
-                }
-            }
+            // Terminate voices whose release period has fully ended. This could be done as part of
+            // the previous loop but this is simpler.
+            self.voices.remove_where(
+                |v| v.releasing && v.amp_envelope.value() == 0.0,
+                |v| {
+                    // This event is very important, as it allows the host to manage its own
+                    // modulation voices
+                    #[allow(clippy::cast_possible_truncation)]
+                    host.send_event(NoteEvent::VoiceTerminated {
+                        timing: block_end as u32,
+                        voice_id: Some(v.id),
+                        channel: v.channel,
+                        note: v.note,
+                    });
+                },
+            );
 
             // And then just keep processing blocks until we've run out of buffer to fill
             block_start = block_end;
             block_end = (block_start + MAX_BLOCK_SIZE).min(num_samples);
         }
 
-        if self.params.editor_state.is_open() {
-            for (voice, displays) in self.voices.iter().zip(self.frequency_display.iter()) {
-                if let Some(voice) = voice {
-                    for (voice_filter, display) in voice.filters.iter().zip(displays) {
-                        display.store(Some(voice_filter.frequency()));
-                    }
-                } else {
-                    for display in displays {
-                        display.store(None);
-                    }
-                }
-            }
-
-            for (voice, displays) in self.voices.iter().zip(self.filter_display.iter()) {
-                if let Some(voice) = voice {
-                    for (voice_filter, display) in voice.filters.iter().zip(displays) {
-                        display.store(Some(*voice_filter));
-                    }
-                } else {
-                    for display in displays {
-                        display.store(None);
-                    }
-                }
+        let output_params = &self.params.output;
+        if output_params.ceiling_enabled.value() {
+            let ceiling = util::db_to_gain(output_params.ceiling.value());
+            let mut lowest_gain = 1.0f32;
+            for sample_idx in 0..num_samples {
+                let sample = f32x2::from_array([output[0][sample_idx], output[1][sample_idx]]);
+                let sample = self.limiter.process(sample, ceiling, sample_rate);
+                output[0][sample_idx] = sample.as_array()[0];
+                output[1][sample_idx] = sample.as_array()[1];
+                lowest_gain = lowest_gain.min(self.limiter.gain());
             }
-
-            self.post_spectrum_input.compute(buffer);
+            // This can't fail, the update always goes through
+            let _ = self
+                .limiter_gain
+                .fetch_update(|gain| Some(gain.min(lowest_gain)));
+        } else {
+            self.limiter.reset();
         }
 
-        ProcessStatus::Normal
+        // Whatever the quantizer is still holding back happens in a later buffer
+        #[allow(clippy::cast_possible_truncation)]
+        for pending in &mut self.pending_note_ons {
+            pending.note_on.timing = pending.note_on.timing.saturating_sub(num_samples as u32);
+        }
     }
-}
 
-impl ScaleColorizr {
     /// Start a new voice with the given voice ID. If all voices are currently in use, the oldest
     /// voice will be stolen. Returns a reference to the new voice.
     fn start_voice(
         &mut self,
-        context: &mut impl ProcessContext<Self>,
+        host: &mut impl ProcessHost,
         sample_offset: u32,
         voice_id: Option<i32>,
         channel: u8,
         note: u8,
     ) -> &mut Voice {
-        #[allow(clippy::cast_precision_loss)]
-        let freq = util::midi_note_to_freq(note) / (NUM_FILTERS / 2) as f32;
+        let freq = self.voice_frequency(note);
+        let expression = self
+            .channel_expression
+            .get(usize::from(channel))
+            .copied()
+            .unwrap_or(1.0);
+        let mut filters = [[StereoFilter::default(); MAX_FILTER_STAGES]; NUM_FILTERS];
+        for filter in filters.iter_mut().flatten() {
+            filter.set_sample_rate(self.sample_rate.load(std::sync::atomic::Ordering::Relaxed));
+        }
+        // The filters haven't run yet, so notches start out on the cut output going by the mode
+        let cut_fade = if self.params.filter.filter_mode.value() == FilterMode::Notch {
+            1.0
+        } else {
+            0.0
+        };
         let new_voice = Voice {
             id: voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)),
             internal_voice_id: self.next_internal_voice_id,
@@ -424,77 +2716,342 @@ impl ScaleColorizr {
             note,
             frequency: freq,
             velocity_sqrt: 1.0,
+            velocity_width: 0.0,
 
             releasing: false,
-            amp_envelope: Smoother::none(),
-
-            filters: [GenericSVF::default(); NUM_FILTERS],
+            age: 0,
+            hold_samples: 0,
+            held_by_freeze: false,
+            key_up: false,
+            amp_envelope: Envelope::default(),
+            width_envelope: Envelope::default(),
+            width_envelope_decaying: false,
+            freq_shifter: FrequencyShifter::default(),
+            noise_gate: NoiseGate::default(),
+            pan: 0.0,
+            volume: 1.0,
+            expression,
+            smoothed_expression: expression,
+            output_group: match self.params.output.aux_routing.value() {
+                #[allow(clippy::cast_possible_truncation)]
+                AuxRouting::RoundRobin => {
+                    (self.next_internal_voice_id % NUM_OUTPUT_GROUPS as u64) as usize
+                }
+                AuxRouting::NoteRange => usize::from(note) * NUM_OUTPUT_GROUPS / 128,
+                AuxRouting::BoostCut => 0,
+            },
+            cut_fade,
+            cut_fade_start: cut_fade,
+
+            filters,
+            filter_descriptors: [None; NUM_FILTERS],
+            old_filters: filters,
+            old_frequency: freq,
+            retune_fade: 0.0,
+            skipped_frequencies: [None; NUM_FILTERS],
+            scatter: [0.0; NUM_FILTERS],
         };
         self.next_internal_voice_id = self.next_internal_voice_id.wrapping_add(1);
 
+        // If there is no free voice, the oldest one gets stolen
         #[allow(clippy::cast_sign_loss)]
-        if let Some(free_voice_idx) = self
+        let (voice, stolen_voice) = self
             .voices
-            .iter()
-            .take(self.params.voice_count.value() as usize)
-            .position(Option::is_none)
-        {
-            self.voices[free_voice_idx] = Some(new_voice);
-            return self.voices[free_voice_idx].as_mut().unwrap();
-        }
-        // If there is no free voice, find and steal the oldest one
-        // SAFETY: We can skip a lot of checked unwraps here since we already know all voices are in
-        //         use
-        let oldest_voice = unsafe {
-            #[allow(clippy::cast_sign_loss)]
-            self.voices
-                .iter_mut()
-                .take(self.params.voice_count.value() as usize)
-                .min_by_key(|voice| voice.as_ref().unwrap_unchecked().internal_voice_id)
-                .unwrap_unchecked()
-        };
+            .start(new_voice, self.params.voice_count.value() as usize);
 
         // The stolen voice needs to be terminated so the host can reuse its modulation
         // resources
-        {
-            let oldest_voice = oldest_voice.as_ref().unwrap();
-            context.send_event(NoteEvent::VoiceTerminated {
+        if let Some(stolen_voice) = stolen_voice {
+            self.voices_stolen
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if self.params.midi_out.value() && !stolen_voice.releasing {
+                echo_note_off(host, sample_offset, &stolen_voice);
+            }
+            host.send_event(NoteEvent::VoiceTerminated {
                 timing: sample_offset,
-                voice_id: Some(oldest_voice.id),
-                channel: oldest_voice.channel,
-                note: oldest_voice.note,
+                voice_id: Some(stolen_voice.id),
+                channel: stolen_voice.channel,
+                note: stolen_voice.note,
             });
         }
 
-        *oldest_voice = Some(new_voice);
-        return oldest_voice.as_mut().unwrap();
+        voice
     }
 
     /// Start the release process for one or more voice by changing their amplitude envelope. If
     /// `voice_id` is not provided, then this will terminate all matching voices.
     fn start_release_for_voices(
         &mut self,
+        host: &mut impl ProcessHost,
+        sample_offset: u32,
         sample_rate: f32,
         voice_id: Option<i32>,
         channel: u8,
         note: u8,
     ) {
+        let envelope = &self.params.envelope;
+        let filter_mode = self.params.filter.filter_mode.value();
+        if envelope.mode.value() == EnvelopeMode::OneShot {
+            for voice in self.voices.matching_mut(voice_id, channel, note) {
+                voice.key_up = true;
+            }
+            return;
+        }
+
+        let midi_out = self.params.midi_out.value();
+        // if we were provided with a voice id, this only gets the first match. otherwise, all of em
+        for voice in self.voices.matching_mut(voice_id, channel, note) {
+            if self.frozen {
+                voice.held_by_freeze = true;
+            } else {
+                voice.release(
+                    host,
+                    sample_offset,
+                    sample_rate,
+                    envelope,
+                    filter_mode,
+                    midi_out,
+                );
+            }
+        }
+    }
+
+    /// Release every voice that's still held, at the start of the buffer, and forget the quantized
+    /// note ons that haven't started yet. Like note offs, this leaves one shots alone.
+    fn release_all(&mut self, host: &mut impl ProcessHost, sample_rate: f32) {
+        self.pending_note_ons.clear();
+        let envelope = &self.params.envelope;
+        if envelope.mode.value() == EnvelopeMode::OneShot {
+            for voice in self.voices.iter_mut() {
+                voice.key_up = true;
+            }
+            return;
+        }
+
+        let midi_out = self.params.midi_out.value();
+        let filter_mode = self.params.filter.filter_mode.value();
+        for voice in self.voices.iter_mut().filter(|voice| !voice.releasing) {
+            voice.release(host, 0, sample_rate, envelope, filter_mode, midi_out);
+        }
+    }
+
+    /// Release every voice on `channel` in the same choke group as `note`, including ones playing
+    /// `note` itself. This works in every envelope mode, so one shots can be choked too, but not
+    /// while frozen.
+    fn choke_group(
+        &mut self,
+        host: &mut impl ProcessHost,
+        sample_offset: u32,
+        sample_rate: f32,
+        channel: u8,
+        note: u8,
+    ) {
+        if self.frozen {
+            return;
+        }
+
+        let envelope = &self.params.envelope;
+        let filter_mode = self.params.filter.filter_mode.value();
+        let midi_out = self.params.midi_out.value();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let group_size = self.params.choke_group_size.value().max(1) as u8;
+        for voice in self.voices.iter_mut().filter(|voice| {
+            voice.channel == channel
+                && !voice.releasing
+                && voice.note / group_size == note / group_size
+        }) {
+            voice.release(
+                host,
+                sample_offset,
+                sample_rate,
+                envelope,
+                filter_mode,
+                midi_out,
+            );
+        }
+    }
+
+    /// Whether the next block would come out exactly like the input, so there's no need to run it.
+    /// This is what keeps instances that are just sitting there cheap.
+    fn is_idle(&self) -> bool {
+        let output = &self.params.output;
+        self.voices.active_voices() == 0
+            && output.delta_mix.value() >= 0.0
+            && !output.delta_mix.smoothed.is_smoothing()
+            && output.source.value() == ExcitationSource::Main
+            && !output.input_gain.smoothed.is_smoothing()
+            && self.duck_envelope < util::MINUS_INFINITY_GAIN
+    }
+
+    /// Start fading between the coarse and fine band gain if fine mode was just switched.
+    fn follow_fine_gain(&mut self, sample_rate: f32) {
+        let fine_gain_enabled = self.params.output.fine_gain_enabled.value();
+        if fine_gain_enabled != self.fine_gain_enabled {
+            self.fine_gain_enabled = fine_gain_enabled;
+            self.fine_gain_mix
+                .set_target(sample_rate, f32::from(u8::from(fine_gain_enabled)));
+        }
+    }
+
+    /// Fill the first `block_len` samples of `gain` with the band gain. Both band gain parameters
+    /// keep smoothing, whichever one is in use.
+    fn next_band_gain_block(&self, gain: &mut [f32; MAX_BLOCK_SIZE], block_len: usize) {
+        let output = &self.params.output;
+        let mut fine_gain = [0.0; MAX_BLOCK_SIZE];
+        let mut mix = [0.0; MAX_BLOCK_SIZE];
+        output.gain.smoothed.next_block(gain, block_len);
+        output
+            .fine_gain
+            .smoothed
+            .next_block(&mut fine_gain, block_len);
+        self.fine_gain_mix.next_block(&mut mix, block_len);
+        for ((gain, fine_gain), mix) in gain[..block_len].iter_mut().zip(fine_gain).zip(mix) {
+            *gain = (fine_gain - *gain).mul_add(mix, *gain);
+        }
+    }
+
+    /// Pass an idle block through, keeping the smoothers moving as if it had been run.
+    fn skip_idle_block(
+        &mut self,
+        output: &mut [&mut [f32]],
+        aux_outputs: &mut [Buffer],
+        block_start: usize,
+        block_end: usize,
+    ) {
+        #[allow(clippy::cast_possible_truncation)]
+        let block_len = (block_end - block_start) as u32;
+        self.params.output.gain.smoothed.next_step(block_len);
+        self.params.output.fine_gain.smoothed.next_step(block_len);
+        self.fine_gain_mix.next_step(block_len);
+        self.band_width_cc.next_step(block_len);
+        self.duck_envelope = 0.0;
+
+        let input_gain = self.params.output.input_gain.smoothed.next();
+        #[allow(clippy::float_cmp)]
+        if input_gain != 1.0 {
+            for channel in output.iter_mut() {
+                for sample in &mut channel[block_start..block_end] {
+                    *sample *= input_gain;
+                }
+            }
+        }
+
+        // There are no voices, so there's nothing in any of the groups
+        for aux_output in aux_outputs.iter_mut() {
+            for channel in aux_output.as_slice() {
+                channel[block_start..block_end].fill(0.0);
+            }
+        }
+    }
+
+    /// Count down the holds of voices whose attacks are done, and start decaying the ones that run
+    /// out. One shots end once they've decayed, sidechain triggered voices wait for the next hit.
+    fn update_holds(
+        &mut self,
+        host: &mut impl ProcessHost,
+        block_start: usize,
+        block_len: usize,
+        sample_rate: f32,
+    ) {
+        let envelope = &self.params.envelope;
+        let filter_mode = self.params.filter.filter_mode.value();
+        let mode = envelope.mode.value();
+        let midi_out = self.params.midi_out.value();
+        // One shots whose keys were let go of would hang forever after switching away from one
+        // shot mode, since their note offs were already ignored
+        if mode != EnvelopeMode::OneShot {
+            #[allow(clippy::cast_possible_truncation)]
+            for voice in self.voices.iter_mut().filter(|voice| voice.key_up) {
+                voice.key_up = false;
+                if voice.releasing {
+                    continue;
+                }
+                if self.frozen {
+                    voice.held_by_freeze = true;
+                } else {
+                    voice.release(
+                        host,
+                        block_start as u32,
+                        sample_rate,
+                        envelope,
+                        filter_mode,
+                        midi_out,
+                    );
+                }
+            }
+        }
+        if mode == EnvelopeMode::Sustain {
+            return;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
         for voice in self
             .voices
             .iter_mut()
-            .filter_map(|v| {
-                v.as_mut().filter(|v| {
-                    voice_id.is_some_and(|id| v.id == id)
-                        || (v.channel == channel && v.note == note)
-                })
-            })
-            // if we were provided with a voice id, take the first thing in the iterator. otherwise, all of em
-            .take(voice_id.map_or(usize::MAX, |_| 1))
+            .filter(|voice| !voice.releasing && !voice.amp_envelope.is_ramping())
         {
-            voice.releasing = true;
-            voice.amp_envelope.style = SmoothingStyle::Exponential(self.params.release.value());
-            voice.amp_envelope.set_target(sample_rate, 0.0);
+            voice.hold_samples = voice.hold_samples.saturating_sub(block_len as u32);
+            if voice.hold_samples > 0 {
+                continue;
+            }
+
+            match mode {
+                EnvelopeMode::OneShot => {
+                    voice.release(
+                        host,
+                        block_start as u32,
+                        sample_rate,
+                        envelope,
+                        filter_mode,
+                        midi_out,
+                    );
+                }
+                // Voices that have already decayed have nothing left to do
+                EnvelopeMode::SidechainTrigger if voice.amp_envelope.value() > 0.0 => {
+                    voice.amp_envelope.set_target(
+                        sample_rate,
+                        0.0,
+                        envelope.decay.value(),
+                        envelope.release_shape.value(),
+                    );
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Restart the attack and hold of every voice that's still held, from wherever their envelopes
+    /// are now.
+    fn trigger_voices(&mut self, sample_rate: f32) {
+        let envelope = &self.params.envelope;
+        let filter_mode = self.params.filter.filter_mode.value();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let hold_samples = (envelope.hold.value() / 1000.0 * sample_rate) as u32;
+        for voice in self.voices.iter_mut().filter(|voice| !voice.releasing) {
+            voice.hold_samples = hold_samples;
+            voice.amp_envelope.set_target(
+                sample_rate,
+                1.0,
+                envelope.attack_time(voice.note, filter_mode),
+                envelope.attack_shape.value(),
+            );
+        }
+    }
+
+    /// Follow the freeze parameter and footswitch. When freeze gets turned off, every voice it was
+    /// holding on to gets released.
+    fn update_freeze(&mut self, host: &mut impl ProcessHost, timing: u32, sample_rate: f32) {
+        let frozen = self.params.performance.freeze.value() || self.freeze_footswitch;
+        if self.frozen && !frozen {
+            let midi_out = self.params.midi_out.value();
+            let envelope = &self.params.envelope;
+            let filter_mode = self.params.filter.filter_mode.value();
+            for voice in self.voices.iter_mut().filter(|voice| voice.held_by_freeze) {
+                voice.release(host, timing, sample_rate, envelope, filter_mode, midi_out);
+            }
         }
+
+        self.frozen = frozen;
     }
 
     /// Immediately terminate one or more voice, removing it from the pool and informing the host
@@ -502,44 +3059,55 @@ impl ScaleColorizr {
     /// matching voices.
     fn choke_voices(
         &mut self,
-        context: &mut impl ProcessContext<Self>,
+        host: &mut impl ProcessHost,
         sample_offset: u32,
         voice_id: Option<i32>,
         channel: u8,
         note: u8,
     ) {
-        for voice in self
-            .voices
-            .iter_mut()
-            .filter(|v| {
-                v.as_ref().is_some_and(|v| {
-                    voice_id.is_some_and(|id| v.id == id)
-                        || (v.channel == channel && v.note == note)
-                })
-            })
-            // if we were provided with a voice id, take the first thing in the iterator. otherwise, all of em
-            .take(voice_id.map_or(usize::MAX, |_| 1))
-        {
-            if let Some(voice) = voice.take() {
-                context.send_event(NoteEvent::VoiceTerminated {
+        let midi_out = self.params.midi_out.value();
+        // if we were provided with a voice id, this only removes the first match. otherwise, all of em
+        self.voices
+            .remove_matching(voice_id, channel, note, |voice| {
+                if midi_out && !voice.releasing {
+                    echo_note_off(host, sample_offset, &voice);
+                }
+
+                host.send_event(NoteEvent::VoiceTerminated {
                     timing: sample_offset,
                     // Notice how we always send the terminated voice ID here
                     voice_id: Some(voice.id),
                     channel,
                     note,
                 });
-            }
-        }
+            });
+    }
+
+    /// The base frequency of a voice playing `note`, which the filters' ratios multiply. The
+    /// voice keeps the note it was played with so note offs still find it, it just sounds at the
+    /// locked and tuned pitch.
+    #[allow(clippy::cast_precision_loss)]
+    fn voice_frequency(&self, note: u8) -> f32 {
+        self.params.scale.frequency(note) / (NUM_FILTERS / 2) as f32
     }
 
     fn retune_voice(&mut self, voice_id: Option<i32>, channel: u8, note: u8, tuning: f32) {
-        if let Some(voice) = self
-            .voices
-            .iter_mut()
-            .filter_map(|v| v.as_mut())
-            .find(|v| voice_id == Some(v.id) || (v.channel == channel && v.note == note))
-        {
-            voice.frequency = util::f32_midi_note_to_freq(f32::from(note) + tuning);
+        let frequency = self.voice_frequency(note) * (tuning / 12.0).exp2();
+        let sample_rate = self.sample_rate.load(std::sync::atomic::Ordering::Relaxed);
+        if let Some(voice) = self.voices.matching_mut(voice_id, channel, note).next() {
+            voice.retune(frequency, sample_rate);
+        }
+    }
+
+    fn pan_voice(&mut self, voice_id: Option<i32>, channel: u8, note: u8, pan: f32) {
+        if let Some(voice) = self.voices.matching_mut(voice_id, channel, note).next() {
+            voice.pan = pan.clamp(-1.0, 1.0);
+        }
+    }
+
+    fn set_voice_volume(&mut self, voice_id: Option<i32>, channel: u8, note: u8, gain: f32) {
+        if let Some(voice) = self.voices.matching_mut(voice_id, channel, note).next() {
+            voice.volume = gain.max(0.0);
         }
     }
 
@@ -548,9 +3116,12 @@ impl ScaleColorizr {
         next_event: &mut Option<NoteEvent<()>>,
         block_start: usize,
         sample_rate: f32,
-        context: &mut impl ProcessContext<Self>,
+        host: &mut impl ProcessHost,
         block_end: &mut usize,
     ) {
+        #[allow(clippy::cast_possible_truncation)]
+        self.update_freeze(host, block_start as u32, sample_rate);
+
         // First of all, handle all note events that happen at the start of the block, and cut
         // the block short if another event happens before the end of it.
         loop {
@@ -558,8 +3129,16 @@ impl ScaleColorizr {
                 // If the event happens now, then we'll keep processing events
                 Some(event) if (event.timing() as usize) <= block_start => {
                     self.midi_event_debug.store(Some(event));
-                    // This synth doesn't support any of the polyphonic expression events. A
-                    // real synth plugin however will want to support those.
+                    if self.recording_midi
+                        && self.recorded_midi.len() < midi_recorder::MAX_EVENTS_PER_BUFFER
+                    {
+                        self.recorded_midi.push((
+                            self.midi_recording_position + u64::from(event.timing()),
+                            event,
+                        ));
+                    }
+                    // Of the polyphonic expressions, only tuning, pan and volume do anything. The
+                    // rest, like pressure, vibrato and poly modulation, get ignored.
                     match event {
                         NoteEvent::NoteOn {
                             timing,
@@ -567,26 +3146,60 @@ impl ScaleColorizr {
                             channel,
                             note,
                             velocity,
-                        } => {
-                            // This starts with the attack portion of the amplitude envelope
-                            let amp_envelope = Smoother::new(SmoothingStyle::Exponential(
-                                self.params.attack.value(),
-                            ));
-                            amp_envelope.reset(0.0);
-                            amp_envelope.set_target(sample_rate, 1.0);
-
-                            let voice = self.start_voice(context, timing, voice_id, channel, note);
-                            voice.velocity_sqrt = velocity.sqrt();
-                            voice.amp_envelope = amp_envelope;
+                        } if self.params.performance.accepts_velocity(velocity) => {
+                            self.ignored_notes[usize::from(channel)][usize::from(note)] = false;
+                            let note_on = NoteOn {
+                                timing,
+                                voice_id,
+                                channel,
+                                note,
+                                velocity,
+                            };
+                            let delay = self.quantize_delay(host.transport(), timing, sample_rate);
+                            if delay == 0 {
+                                self.start_note(host, sample_rate, note_on);
+                            } else if self.pending_note_ons.len() < MAX_PENDING_NOTE_ONS {
+                                self.pending_note_ons.push(PendingNoteOn {
+                                    note_on: NoteOn {
+                                        timing: timing + delay,
+                                        ..note_on
+                                    },
+                                    released: false,
+                                });
+                            }
+                        }
+                        NoteEvent::NoteOn { channel, note, .. } => {
+                            self.ignored_notes[usize::from(channel)][usize::from(note)] = true;
+                        }
+                        NoteEvent::NoteOff { channel, note, .. }
+                            if self.ignored_notes[usize::from(channel)][usize::from(note)] =>
+                        {
+                            self.ignored_notes[usize::from(channel)][usize::from(note)] = false;
                         }
                         NoteEvent::NoteOff {
-                            timing: _,
+                            timing,
                             voice_id,
                             channel,
                             note,
                             velocity: _,
                         } => {
-                            self.start_release_for_voices(sample_rate, voice_id, channel, note);
+                            for pending in &mut self.pending_note_ons {
+                                let note_on = pending.note_on;
+                                if voice_id.is_some_and(|id| note_on.voice_id == Some(id))
+                                    || (note_on.channel == channel && note_on.note == note)
+                                {
+                                    pending.released = true;
+                                }
+                            }
+
+                            self.start_release_for_voices(
+                                host,
+                                timing,
+                                sample_rate,
+                                voice_id,
+                                channel,
+                                note,
+                            );
                         }
                         NoteEvent::Choke {
                             timing,
@@ -594,7 +3207,12 @@ impl ScaleColorizr {
                             channel,
                             note,
                         } => {
-                            self.choke_voices(context, timing, voice_id, channel, note);
+                            self.pending_note_ons.retain(|pending| {
+                                let note_on = pending.note_on;
+                                !(voice_id.is_some_and(|id| note_on.voice_id == Some(id))
+                                    || (note_on.channel == channel && note_on.note == note))
+                            });
+                            self.choke_voices(host, timing, voice_id, channel, note);
                         }
                         NoteEvent::PolyTuning {
                             voice_id,
@@ -605,21 +3223,475 @@ impl ScaleColorizr {
                         } => {
                             self.retune_voice(voice_id, channel, note, tuning);
                         }
+                        NoteEvent::PolyPan {
+                            voice_id,
+                            channel,
+                            note,
+                            pan,
+                            ..
+                        } => {
+                            self.pan_voice(voice_id, channel, note, pan);
+                        }
+                        NoteEvent::PolyVolume {
+                            voice_id,
+                            channel,
+                            note,
+                            gain,
+                            ..
+                        } => {
+                            self.set_voice_volume(voice_id, channel, note, gain);
+                        }
+                        NoteEvent::MidiCC {
+                            timing, cc, value, ..
+                        } if i32::from(cc) == self.params.performance.freeze_cc.value() => {
+                            self.freeze_footswitch = value >= 0.5;
+                            self.update_freeze(host, timing, sample_rate);
+                        }
+                        NoteEvent::MidiCC { cc, value, .. }
+                            if i32::from(cc) == self.params.performance.band_width_cc.value() =>
+                        {
+                            // The first value jumps, there's nothing sensible to smooth from
+                            if self.band_width_cc_active {
+                                self.band_width_cc.set_target(sample_rate, value);
+                            } else {
+                                self.band_width_cc.reset(value);
+                                self.band_width_cc_active = true;
+                            }
+                        }
+                        NoteEvent::MidiCC {
+                            channel,
+                            cc: EXPRESSION_CC,
+                            value,
+                            ..
+                        } if self.params.performance.expression.value() => {
+                            if let Some(expression) =
+                                self.channel_expression.get_mut(usize::from(channel))
+                            {
+                                *expression = value;
+                            }
+                            for voice in self.voices.iter_mut() {
+                                if voice.channel == channel {
+                                    voice.expression = value;
+                                }
+                            }
+                        }
+                        // Bank select, MSB and LSB
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                        NoteEvent::MidiCC { cc: 0, value, .. } => {
+                            self.bank_select_msb = (value * 127.0).round() as u8;
+                        }
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                        NoteEvent::MidiCC { cc: 32, value, .. } => {
+                            self.bank_select_lsb = (value * 127.0).round() as u8;
+                        }
+                        NoteEvent::MidiProgramChange { program, .. } => {
+                            let bank = (usize::from(self.bank_select_msb) << 7)
+                                | usize::from(self.bank_select_lsb);
+                            self.program_change
+                                .store(Some(bank * 128 + usize::from(program)));
+                        }
                         _ => {}
                     };
 
-                    *next_event = context.next_event();
+                    *next_event = host.next_event();
                 }
                 // If the event happens before the end of the block, then the block should be cut
                 // short so the next block starts at the event
                 Some(event) if (event.timing() as usize) < *block_end => {
                     *block_end = event.timing() as usize;
+                    break;
+                }
+                _ => break,
+            }
+        }
+
+        // Held back note ons split blocks the same way
+        let mut pending_idx = 0;
+        while pending_idx < self.pending_note_ons.len() {
+            let pending = self.pending_note_ons[pending_idx];
+            let timing = pending.note_on.timing as usize;
+            if timing <= block_start {
+                self.pending_note_ons.remove(pending_idx);
+
+                let note_on = pending.note_on;
+                self.start_note(host, sample_rate, note_on);
+                if pending.released {
+                    self.start_release_for_voices(
+                        host,
+                        note_on.timing,
+                        sample_rate,
+                        note_on.voice_id,
+                        note_on.channel,
+                        note_on.note,
+                    );
+                }
+            } else {
+                *block_end = (*block_end).min(timing);
+                pending_idx += 1;
+            }
+        }
+    }
+
+    /// Multiply the band gain by the trance gate's level. The gate stays open while it's off, or
+    /// when the host isn't playing.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn apply_gate(
+        &mut self,
+        gain: &mut [f32],
+        transport: TransportInfo,
+        block_start: usize,
+        sample_rate: f32,
+    ) {
+        let rhythm = &self.params.rhythm;
+        let position = match (transport.playing, transport.tempo, transport.pos_beats) {
+            (true, Some(tempo), Some(pos_beats)) if rhythm.gate.value() => {
+                Some((60.0 / tempo * f64::from(sample_rate), pos_beats))
+            }
+            _ => None,
+        };
+        let num_steps = rhythm.gate_steps.value().count() as i64;
+
+        for (sample_idx, gain) in (block_start..).zip(gain) {
+            let target = position.map_or(1.0, |(samples_per_beat, pos_beats)| {
+                let beat = pos_beats + sample_idx as f64 / samples_per_beat;
+                // Positions can be negative during a pre-roll
+                #[allow(clippy::cast_sign_loss)]
+                let step = ((beat / GATE_STEP_BEATS).floor() as i64).rem_euclid(num_steps) as usize;
+                if rhythm.gate_pattern[step].on.value() {
+                    1.0
+                } else {
+                    1.0 - rhythm.gate_depth.value()
+                }
+            });
+
+            #[allow(clippy::float_cmp)]
+            if target != self.gate_target {
+                self.gate_target = target;
+                self.gate.set_target(sample_rate, target);
+            }
+            *gain *= self.gate.next();
+        }
+    }
+
+    /// Start a voice for a note on, with the attack portion of the amplitude envelope.
+    fn start_note(&mut self, host: &mut impl ProcessHost, sample_rate: f32, note_on: NoteOn) {
+        let NoteOn {
+            timing,
+            voice_id,
+            channel,
+            note,
+            velocity,
+        } = note_on;
+        let midi_out = self.params.midi_out.value();
+
+        if self.is_duplicate(sample_rate, channel, note) {
+            return;
+        }
+        if self.params.choke_groups.value() {
+            self.choke_group(host, timing, sample_rate, channel, note);
+        }
+        match self.params.retrigger.value() {
+            RetriggerMode::AlwaysNew => (),
+            RetriggerMode::StealSameNote => {
+                self.choke_voices(host, timing, None, channel, note);
+            }
+            RetriggerMode::LegatoReuse => {
+                if self.retrigger_voice(host, sample_rate, note_on) {
                     return;
                 }
-                _ => return,
             }
         }
+
+        let (attack_scale, humanize_gain) = self.humanize(channel, note);
+        let velocity_width = self.params.filter.velocity.value() * velocity.mul_add(2.0, -1.0);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let hold_samples = (self.params.envelope.hold.value() / 1000.0 * sample_rate) as u32;
+        let envelope = &self.params.envelope;
+        let attack_time = self.note_attack_time(sample_rate, note);
+        let mut amp_envelope = Envelope::default();
+        amp_envelope.set_target(
+            sample_rate,
+            1.0,
+            attack_time * attack_scale,
+            envelope.attack_shape.value(),
+        );
+        let mut width_envelope = Envelope::default();
+        width_envelope.set_target(sample_rate, 1.0, envelope.width_attack.value(), 0.0);
+
+        let scatter = self.scatter(channel, note);
+        let voice = self.start_voice(host, timing, voice_id, channel, note);
+        voice.scatter = scatter;
+        voice.velocity_sqrt = velocity.sqrt() * humanize_gain;
+        voice.velocity_width = velocity_width;
+        voice.hold_samples = hold_samples;
+        voice.amp_envelope = amp_envelope;
+        voice.width_envelope = width_envelope;
+
+        // Only voices that actually got started are echoed
+        if midi_out {
+            host.send_event(NoteEvent::NoteOn {
+                timing,
+                voice_id: Some(voice.id),
+                channel,
+                note,
+                velocity,
+            });
+        }
+    }
+
+    /// The attack time in ms for a note starting now, which is the chord attack if it joins a
+    /// chord with split attack on.
+    fn note_attack_time(&self, sample_rate: f32, note: u8) -> f32 {
+        let envelope = &self.params.envelope;
+        let filter_mode = self.params.filter.filter_mode.value();
+        if envelope.split_attack.value() && self.joins_chord(sample_rate) {
+            envelope.chord_attack_time(note, filter_mode)
+        } else {
+            envelope.attack_time(note, filter_mode)
+        }
+    }
+
+    /// Whether a note starting now would be added to a chord, because another voice that's still
+    /// held started less than the chord window ago.
+    fn joins_chord(&self, sample_rate: f32) -> bool {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let window = (self.params.envelope.chord_window.value() / 1000.0 * sample_rate) as u64;
+        self.voices
+            .iter()
+            .any(|voice| !voice.releasing && voice.age < window)
+    }
+
+    /// Whether a note on is an echo of one that just started, going by the duplicate note window.
+    /// Events split the blocks, so the ages are exact here.
+    fn is_duplicate(&self, sample_rate: f32, channel: u8, note: u8) -> bool {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let window =
+            (self.params.performance.duplicate_window.value() / 1000.0 * sample_rate) as u64;
+        self.voices.iter().any(|voice| {
+            voice.channel == channel && voice.note == note && !voice.releasing && voice.age < window
+        })
+    }
+
+    /// Restart a voice that's already playing this note instead of starting a new one, so its
+    /// filters keep ringing without a second, phase shifted copy on top. The envelopes pick up from
+    /// where they are. Returns false if there was no voice to restart.
+    fn retrigger_voice(
+        &mut self,
+        host: &mut impl ProcessHost,
+        sample_rate: f32,
+        note_on: NoteOn,
+    ) -> bool {
+        let NoteOn {
+            timing,
+            voice_id,
+            channel,
+            note,
+            velocity,
+        } = note_on;
+        let (attack_scale, humanize_gain) = self.humanize(channel, note);
+        let velocity_width = self.params.filter.velocity.value() * velocity.mul_add(2.0, -1.0);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let hold_samples = (self.params.envelope.hold.value() / 1000.0 * sample_rate) as u32;
+        let attack_time = self.note_attack_time(sample_rate, note);
+        let Some(voice) = self.voices.matching_mut(None, channel, note).next() else {
+            return false;
+        };
+
+        let midi_out = self.params.midi_out.value();
+        if midi_out && !voice.releasing {
+            echo_note_off(host, timing, voice);
+        }
+
+        // As far as the host is concerned this is a new voice, so the old one needs to end
+        let new_id = voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel));
+        if new_id != voice.id {
+            host.send_event(NoteEvent::VoiceTerminated {
+                timing,
+                voice_id: Some(voice.id),
+                channel,
+                note,
+            });
+            voice.id = new_id;
+        }
+        voice.internal_voice_id = self.next_internal_voice_id;
+        self.next_internal_voice_id = self.next_internal_voice_id.wrapping_add(1);
+
+        let envelope = &self.params.envelope;
+        voice.age = 0;
+        voice.releasing = false;
+        voice.held_by_freeze = false;
+        voice.key_up = false;
+        voice.velocity_sqrt = velocity.sqrt() * humanize_gain;
+        voice.velocity_width = velocity_width;
+        voice.hold_samples = hold_samples;
+        voice.amp_envelope.set_target(
+            sample_rate,
+            1.0,
+            attack_time * attack_scale,
+            envelope.attack_shape.value(),
+        );
+        voice.width_envelope_decaying = false;
+        voice
+            .width_envelope
+            .set_target(sample_rate, 1.0, envelope.width_attack.value(), 0.0);
+
+        if midi_out {
+            host.send_event(NoteEvent::NoteOn {
+                timing,
+                voice_id: Some(voice.id),
+                channel,
+                note,
+                velocity,
+            });
+        }
+
+        true
+    }
+
+    /// Random offsets for the next voice's attack time and gain, returned as multipliers. These
+    /// are seeded from the note and how many voices came before it rather than from the system's
+    /// RNG, so an offline render of the same MIDI sounds the same every time.
+    fn humanize(&self, channel: u8, note: u8) -> (f32, f32) {
+        let amount = self.params.envelope.humanize.value();
+        #[allow(clippy::float_cmp)]
+        if amount == 0.0 {
+            return (1.0, 1.0);
+        }
+
+        let mut rng = self.voice_rng(channel, note, 0);
+        let attack_scale = amount * HUMANIZE_MAX_ATTACK_SCALE;
+        let gain_db = amount * HUMANIZE_MAX_GAIN_DB;
+
+        (
+            rng.gen_range(1.0 - attack_scale..=1.0 + attack_scale),
+            util::db_to_gain(rng.gen_range(-gain_db..=gain_db)),
+        )
+    }
+
+    /// Random offsets for the next voice's filter frequencies, from -1 to 1. These are seeded the
+    /// same way as [`humanize`][Self::humanize], so repeated notes each get a different spread
+    /// that's still the same on every render.
+    fn scatter(&self, channel: u8, note: u8) -> [f32; NUM_FILTERS] {
+        let mut rng = self.voice_rng(channel, note, SCATTER_RNG_STREAM);
+        std::array::from_fn(|_| rng.gen_range(-1.0..=1.0))
+    }
+
+    /// An RNG for the next voice. Different `stream`s give unrelated numbers for the same voice.
+    fn voice_rng(&self, channel: u8, note: u8, stream: u64) -> StdRng {
+        let seed = self.params.seed.load(std::sync::atomic::Ordering::Relaxed)
+            ^ ((self.next_internal_voice_id << 16) | (u64::from(channel) << 8) | u64::from(note))
+            ^ stream;
+        StdRng::seed_from_u64(seed)
+    }
+
+    /// How many samples a note on at `timing` needs to be held back to land on the quantizer's
+    /// next grid division. This is zero when quantizing is off, or when the host isn't playing or
+    /// doesn't say where it is.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn quantize_delay(&self, transport: TransportInfo, timing: u32, sample_rate: f32) -> u32 {
+        let Some(division) = self.params.rhythm.quantize.value().beats() else {
+            return 0;
+        };
+        let (true, Some(tempo), Some(pos_beats)) =
+            (transport.playing, transport.tempo, transport.pos_beats)
+        else {
+            return 0;
+        };
+
+        let samples_per_beat = 60.0 / tempo * f64::from(sample_rate);
+        let beat = pos_beats + f64::from(timing) / samples_per_beat;
+        // Without the tolerance, notes that are on the grid could get pushed a whole division back
+        // by rounding errors
+        let next_division = (beat / division - 1e-6).ceil() * division;
+
+        ((next_division - beat).max(0.0) * samples_per_beat).round() as u32
+    }
+}
+
+/// The parts of the host the DSP talks to. This lets the same processing run inside of a plugin host
+/// and in [`ScaleColorizr::render_offline`].
+trait ProcessHost {
+    fn next_event(&mut self) -> Option<NoteEvent<()>>;
+    fn send_event(&mut self, event: NoteEvent<()>);
+    fn transport(&self) -> TransportInfo;
+}
+
+/// The parts of the host's transport the DSP follows, as of the start of the buffer.
+#[derive(Clone, Copy, Default)]
+struct TransportInfo {
+    playing: bool,
+    tempo: Option<f64>,
+    pos_beats: Option<f64>,
+}
+
+/// Split the dry output off the end of `aux_outputs` if the layout has one, leaving the voice
+/// groups.
+fn split_dry_output<'a, 'b>(
+    aux_outputs: &'a mut [Buffer<'b>],
+    has_dry_output: bool,
+) -> (&'a mut [Buffer<'b>], Option<&'a mut Buffer<'b>>) {
+    let num_groups = if has_dry_output {
+        aux_outputs.len().saturating_sub(1)
+    } else {
+        aux_outputs.len()
+    };
+    let (groups, dry_output) = aux_outputs.split_at_mut(num_groups);
+    (groups, dry_output.first_mut())
+}
+
+/// Adapts NIH-plug's [`ProcessContext`] to a [`ProcessHost`].
+struct PluginHost<'a, C>(&'a mut C);
+
+impl<C: ProcessContext<ScaleColorizr>> ProcessHost for PluginHost<'_, C> {
+    fn next_event(&mut self) -> Option<NoteEvent<()>> {
+        self.0.next_event()
+    }
+
+    fn send_event(&mut self, event: NoteEvent<()>) {
+        self.0.send_event(event);
+    }
+
+    fn transport(&self) -> TransportInfo {
+        let transport = self.0.transport();
+        TransportInfo {
+            playing: transport.playing,
+            tempo: transport.tempo,
+            pos_beats: transport.pos_beats(),
+        }
+    }
+}
+
+/// Turn the dry part of `wet` down while the filters are resonating, so the resonance sits on
+/// top of the input instead of just adding to it. `envelope` is the follower's state.
+fn duck_dry(envelope: &mut f32, amount: f32, wet: &mut [f32x2], dry: &[f32x2], sample_rate: f32) {
+    if amount <= 0.0 {
+        *envelope = 0.0;
+        return;
     }
+
+    let attack = (-1.0 / (DUCK_ATTACK_MS / 1000.0 * sample_rate)).exp();
+    let release = (-1.0 / (DUCK_RELEASE_MS / 1000.0 * sample_rate)).exp();
+    for (wet, dry) in wet.iter_mut().zip(dry) {
+        let resonance = *wet - *dry;
+        let [left, right] = *resonance.as_array();
+        let level = left.abs().max(right.abs());
+        let coefficient = if level > *envelope { attack } else { release };
+        *envelope = coefficient.mul_add(*envelope - level, level);
+
+        let duck = amount.mul_add(-envelope.min(1.0), 1.0);
+        *wet = *dry * f32x2::splat(duck) + resonance;
+    }
+}
+
+/// Echo the end of a voice to the MIDI output. Voices that are already releasing have sent theirs.
+fn echo_note_off(host: &mut impl ProcessHost, timing: u32, voice: &Voice) {
+    host.send_event(NoteEvent::NoteOff {
+        timing,
+        voice_id: Some(voice.id),
+        channel: voice.channel,
+        note: voice.note,
+        velocity: 0.0,
+    });
 }
 
 /// Compute a voice ID in case the host doesn't provide them.
@@ -632,12 +3704,44 @@ impl ClapPlugin for ScaleColorizr {
     const CLAP_DESCRIPTION: Option<&'static str> = Some("Filter based sound colorizer");
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    // This is what enables CLAP's voice info extension, so hosts know how many voices to set up
+    // modulators for
+    #[allow(clippy::cast_possible_truncation)]
+    const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = Some(PolyModulationConfig {
+        max_voice_capacity: NUM_VOICES as u32,
+        supports_overlapping_voices: true,
+    });
 
     const CLAP_FEATURES: &'static [ClapFeature] = &[
         ClapFeature::AudioEffect,
         ClapFeature::Stereo,
         ClapFeature::Filter,
     ];
+
+    fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
+        context.add_section("Scale Colorizr", |section| {
+            section.add_page("Main", |page| {
+                page.add_param(&self.params.output.gain);
+                page.add_param(&self.params.filter.band_width);
+                page.add_param(&self.params.envelope.attack);
+                page.add_param(&self.params.envelope.release);
+                page.add_param(&self.params.character.tilt);
+                page.add_param(&self.params.output.delta_mix);
+                page.add_param(&self.params.filter.filter_mode);
+                page.add_param(&self.params.filter.morph);
+            });
+            section.add_page("Tone", |page| {
+                page.add_param(&self.params.character.amount);
+                page.add_param(&self.params.output.input_gain);
+                page.add_param(&self.params.output.duck_dry);
+                page.add_param(&self.params.filter.max_resonance);
+                page.add_param(&self.params.filter.keytrack);
+                page.add_param(&self.params.filter.scatter);
+                page.add_param(&self.params.filter.stereo_alternation);
+                page.add_param(&self.params.envelope.damping);
+            });
+        });
+    }
 }
 
 impl Vst3Plugin for ScaleColorizr {
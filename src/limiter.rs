@@ -0,0 +1,74 @@
+//! A true peak limiter for the output. Peaks between samples can end up well above the sample
+//! peaks once the signal gets converted to analog, so those get estimated by interpolating.
+
+use crate::simd::f32x2;
+
+/// How many points between every pair of samples get checked.
+const OVERSAMPLING: usize = 4;
+const RELEASE_MS: f32 = 200.0;
+
+#[derive(Clone, Copy)]
+pub struct TruePeakLimiter {
+    /// The last four input samples, oldest first.
+    history: [f32x2; 4],
+    gain: f32,
+}
+
+impl Default for TruePeakLimiter {
+    fn default() -> Self {
+        Self {
+            history: [f32x2::default(); 4],
+            gain: 1.0,
+        }
+    }
+}
+
+impl TruePeakLimiter {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Turn `input` down as far as it takes to keep its true peak under `ceiling`, in linear
+    /// gain. The gain drops right away and recovers slowly.
+    pub fn process(&mut self, input: f32x2, ceiling: f32, sample_rate: f32) -> f32x2 {
+        self.history = [self.history[1], self.history[2], self.history[3], input];
+        let peak = self.true_peak();
+
+        let release = (-1000.0 / (RELEASE_MS * sample_rate)).exp();
+        let target = if peak > ceiling { ceiling / peak } else { 1.0 };
+        self.gain = if target < self.gain {
+            target
+        } else {
+            release.mul_add(self.gain - target, target)
+        };
+
+        input * f32x2::splat(self.gain)
+    }
+
+    /// The gain applied to the last sample.
+    pub const fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// The highest peak between the middle two samples of the history, using a Catmull-Rom spline
+    /// through all four. This lags a sample behind, which the instant attack mostly makes up for.
+    fn true_peak(&self) -> f32 {
+        let [y0, y1, y2, y3] = self.history;
+        let half = f32x2::splat(0.5);
+        let c1 = half * (y2 - y0);
+        let c2 = y0 - f32x2::splat(2.5) * y1 + f32x2::splat(2.0) * y2 - half * y3;
+        let c3 = half * (y3 - y0) + f32x2::splat(1.5) * (y1 - y2);
+
+        let sample_peak = |sample: f32x2| {
+            let [left, right] = *sample.as_array();
+            left.abs().max(right.abs())
+        };
+        (1..=OVERSAMPLING)
+            .map(|step| {
+                #[allow(clippy::cast_precision_loss)]
+                let t = f32x2::splat(step as f32 / OVERSAMPLING as f32);
+                sample_peak(((c3 * t + c2) * t + c1) * t + y1)
+            })
+            .fold(sample_peak(y3), f32::max)
+    }
+}
@@ -0,0 +1,205 @@
+//! Recording the incoming MIDI to a standard MIDI file, for tracking down problems with how a
+//! host orders its events, or for keeping something improvised. The audio thread hands every
+//! event over to the background thread, which is where they pile up and get written out.
+
+use midly::num::{u14, u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use nih_plug::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// How many events the audio thread holds on to per buffer before handing them over. Anything
+/// past this gets dropped from the recording.
+pub const MAX_EVENTS_PER_BUFFER: usize = 256;
+const TICKS_PER_BEAT: u16 = 960;
+/// 120 BPM. Recordings don't follow the host's tempo, so this only has to turn ticks into time.
+const MICROS_PER_BEAT: u32 = 500_000;
+
+#[derive(Default)]
+pub struct MidiRecorder {
+    /// Set by the editor while it wants events recorded.
+    pub active: AtomicBool,
+    /// Every event so far, with its time in samples since recording started.
+    events: Mutex<Vec<(u64, NoteEvent<()>)>>,
+    /// How the last save went, for the editor.
+    last_save: Mutex<Option<Result<PathBuf, String>>>,
+}
+
+impl MidiRecorder {
+    /// Throw away anything recorded before and start recording.
+    pub fn start(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.active.store(false, Ordering::Relaxed);
+    }
+
+    pub fn num_events(&self) -> usize {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn last_save(&self) -> Option<Result<PathBuf, String>> {
+        self.last_save
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Add an event to the recording. This is for the background thread, since it can block and
+    /// allocate.
+    pub fn record(&self, time: u64, event: NoteEvent<()>) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((time, event));
+    }
+
+    /// Write everything recorded so far to a single track MIDI file at `path`. Events without a
+    /// MIDI equivalent, like the host's polyphonic modulation, are left out. This is for the
+    /// background thread too.
+    pub fn save(&self, path: &Path, sample_rate: f32) {
+        let result = self.write(path, sample_rate).map(|()| path.to_path_buf());
+        *self
+            .last_save
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(result);
+    }
+
+    fn write(&self, path: &Path, sample_rate: f32) -> Result<(), String> {
+        let ticks_per_sample = f64::from(TICKS_PER_BEAT) * 1_000_000.0
+            / f64::from(MICROS_PER_BEAT)
+            / f64::from(sample_rate);
+
+        let mut track = vec![TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(MICROS_PER_BEAT))),
+        }];
+        let mut last_tick = 0;
+        for &(time, event) in self
+            .events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            let Some((channel, message)) = to_midi(event) else {
+                continue;
+            };
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let tick = (time as f64 * ticks_per_sample).round() as u32;
+            track.push(TrackEvent {
+                delta: u28::new(tick.saturating_sub(last_tick)),
+                kind: TrackEventKind::Midi {
+                    channel: u4::new(channel),
+                    message,
+                },
+            });
+            last_tick = last_tick.max(tick);
+        }
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(TICKS_PER_BEAT)),
+        ));
+        smf.tracks.push(track);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Couldn't create the recordings folder - {e:?}"))?;
+        }
+        smf.save(path)
+            .map_err(|e| format!("Couldn't write the recording - {e:?}"))
+    }
+}
+
+/// The channel and MIDI message for `event`, if it has one.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_midi(event: NoteEvent<()>) -> Option<(u8, MidiMessage)> {
+    let seven_bit = |value: f32| u7::new((value.clamp(0.0, 1.0) * 127.0).round() as u8);
+    Some(match event {
+        NoteEvent::NoteOn {
+            channel,
+            note,
+            velocity,
+            ..
+        } => (
+            channel,
+            MidiMessage::NoteOn {
+                key: u7::new(note),
+                // A velocity of 0 would be a note off
+                vel: seven_bit(velocity).max(u7::new(1)),
+            },
+        ),
+        NoteEvent::NoteOff {
+            channel,
+            note,
+            velocity,
+            ..
+        } => (
+            channel,
+            MidiMessage::NoteOff {
+                key: u7::new(note),
+                vel: seven_bit(velocity),
+            },
+        ),
+        NoteEvent::PolyPressure {
+            channel,
+            note,
+            pressure,
+            ..
+        } => (
+            channel,
+            MidiMessage::Aftertouch {
+                key: u7::new(note),
+                vel: seven_bit(pressure),
+            },
+        ),
+        NoteEvent::MidiChannelPressure {
+            channel, pressure, ..
+        } => (
+            channel,
+            MidiMessage::ChannelAftertouch {
+                vel: seven_bit(pressure),
+            },
+        ),
+        NoteEvent::MidiPitchBend { channel, value, .. } => (
+            channel,
+            MidiMessage::PitchBend {
+                bend: midly::PitchBend(u14::new((value.clamp(0.0, 1.0) * 16383.0).round() as u16)),
+            },
+        ),
+        NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } => (
+            channel,
+            MidiMessage::Controller {
+                controller: u7::new(cc),
+                value: seven_bit(value),
+            },
+        ),
+        NoteEvent::MidiProgramChange {
+            channel, program, ..
+        } => (
+            channel,
+            MidiMessage::ProgramChange {
+                program: u7::new(program),
+            },
+        ),
+        _ => return None,
+    })
+}
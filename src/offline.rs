@@ -0,0 +1,134 @@
+//! Running the DSP outside of a plugin host, for regression tests and benchmarks.
+
+use crate::{ProcessHost, ScaleColorizr, TransportInfo};
+use nih_plug::prelude::*;
+use std::slice;
+
+/// Feeds a fixed list of events to the DSP, and drops anything the DSP sends back.
+struct OfflineHost<'a> {
+    events: slice::Iter<'a, NoteEvent<()>>,
+}
+
+impl ProcessHost for OfflineHost<'_> {
+    fn next_event(&mut self) -> Option<NoteEvent<()>> {
+        self.events.next().copied()
+    }
+
+    fn send_event(&mut self, _event: NoteEvent<()>) {}
+
+    /// There's no transport to follow offline, so anything tempo synced stays off.
+    fn transport(&self) -> TransportInfo {
+        TransportInfo::default()
+    }
+}
+
+/// Snap every parameter's smoother to its current value. Normally the wrapper takes care of this,
+/// and without it they all start out at 0.
+fn reset_smoothers(params: &impl Params) {
+    for (_, param, _) in params.param_map() {
+        // SAFETY: the pointers point into `params`, which is borrowed for the whole loop
+        unsafe {
+            match param {
+                ParamPtr::FloatParam(param) => (*param).smoothed.reset((*param).value()),
+                ParamPtr::IntParam(param) => (*param).smoothed.reset((*param).value()),
+                ParamPtr::BoolParam(_) | ParamPtr::EnumParam(_) => {}
+            }
+        }
+    }
+}
+
+impl ScaleColorizr {
+    /// Render a stereo signal through the plugin without a host. Event timings are in samples from
+    /// the start of `input` and need to be sorted. The current parameter values are used as-is,
+    /// and the voices start out empty, so rendering the same thing twice gives the same result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channels aren't the same length.
+    pub fn render_offline(
+        &mut self,
+        sample_rate: f32,
+        input: [&[f32]; 2],
+        events: &[NoteEvent<()>],
+    ) -> [Vec<f32>; 2] {
+        assert_eq!(
+            input[0].len(),
+            input[1].len(),
+            "both channels need to be the same length"
+        );
+
+        self.set_sample_rate(sample_rate);
+        reset_smoothers(self.params.as_ref());
+        self.reset();
+        self.next_internal_voice_id = 0;
+
+        let mut output = input.map(<[f32]>::to_vec);
+        let [left, right] = &mut output;
+        self.process_audio(
+            &mut [left.as_mut_slice(), right.as_mut_slice()],
+            None,
+            &mut [],
+            &mut OfflineHost {
+                events: events.iter(),
+            },
+        );
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_same_twice() {
+        let note_on = |timing, note| NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.8,
+        };
+        let note_off = |timing, note| NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        };
+        let events = [
+            note_on(0, 60),
+            note_on(500, 64),
+            note_on(500, 67),
+            note_off(3000, 60),
+            note_off(4000, 64),
+            note_off(4000, 67),
+        ];
+        // Anything with some high end to ring the filters will do, as long as it's the same
+        #[allow(clippy::cast_precision_loss)]
+        let input: Vec<f32> = (0..8192)
+            .map(|idx| ((idx * 7919) % 1000) as f32 / 500.0 - 1.0)
+            .collect();
+
+        let mut plugin = ScaleColorizr::default();
+        let first = plugin.render_offline(48_000.0, [&input, &input], &events);
+        let second = plugin.render_offline(48_000.0, [&input, &input], &events);
+        let without_notes = plugin.render_offline(48_000.0, [&input, &input], &[]);
+
+        assert_eq!(first, second);
+        // A silent render would come out the same twice too
+        let after_note_on = 1000..3000;
+        assert!(
+            first[0][after_note_on.clone()]
+                .iter()
+                .any(|sample| sample.abs() > 1e-3),
+            "the render shouldn't be silent"
+        );
+        assert_ne!(
+            first[0][after_note_on.clone()],
+            without_notes[0][after_note_on],
+            "the notes should have done something"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Locking incoming notes to a scale. The scale and root are parameters so key changes can be
+//! automated from the host.
+
+use crate::tuning::{Tuning, A4_FREQ, NUM_TUNING_SLOTS};
+use nih_plug::prelude::*;
+use std::sync::RwLock;
+
+pub const MAJOR: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+pub const MINOR: [u8; 7] = [0, 2, 3, 5, 7, 8, 10];
+pub const DORIAN: [u8; 7] = [0, 2, 3, 5, 7, 9, 10];
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub enum Scale {
+    /// Every note is in the scale, so nothing gets locked.
+    Chromatic,
+    Major,
+    Minor,
+    Dorian,
+    /// The notes picked in [`ScaleParams::custom`].
+    Custom,
+}
+
+#[derive(Enum, PartialEq, Eq, Clone, Copy)]
+pub enum Root {
+    C,
+    #[name = "C#"]
+    CSharp,
+    D,
+    #[name = "D#"]
+    DSharp,
+    E,
+    F,
+    #[name = "F#"]
+    FSharp,
+    G,
+    #[name = "G#"]
+    GSharp,
+    A,
+    #[name = "A#"]
+    ASharp,
+    B,
+}
+
+#[derive(Params)]
+pub struct ScaleParams {
+    #[id = "scale"]
+    pub scale: EnumParam<Scale>,
+    #[id = "root"]
+    pub root: EnumParam<Root>,
+    /// Which notes, counting up from the root, are in [`Scale::Custom`].
+    #[nested(array, group = "Custom Scale")]
+    pub custom: [ScaleNoteParams; 12],
+    /// Which of [`tunings`][Self::tunings] is in use. Empty slots are 12-TET.
+    #[id = "tuning-slot"]
+    pub tuning_slot: IntParam,
+    /// The frequency of A4 that 12-TET and Scala tunings are built from, for sessions that aren't
+    /// at 440 Hz.
+    #[id = "reference-pitch"]
+    pub reference_pitch: FloatParam,
+
+    #[persist = "tunings"]
+    pub tunings: RwLock<[Option<Tuning>; NUM_TUNING_SLOTS]>,
+}
+
+#[derive(Params)]
+pub struct ScaleNoteParams {
+    #[id = "scale-note"]
+    pub enabled: BoolParam,
+}
+
+impl Default for ScaleParams {
+    fn default() -> Self {
+        Self {
+            scale: EnumParam::new("Scale", Scale::Chromatic),
+            root: EnumParam::new("Root", Root::C),
+            // Starts out as a major scale, so switching to custom doesn't start from nothing
+            custom: std::array::from_fn(|idx| ScaleNoteParams {
+                enabled: BoolParam::new(
+                    format!("Custom Scale {}", idx + 1),
+                    MAJOR.iter().any(|&note| usize::from(note) == idx),
+                ),
+            }),
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            tuning_slot: IntParam::new(
+                "Tuning Slot",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: NUM_TUNING_SLOTS as i32,
+                },
+            ),
+            reference_pitch: FloatParam::new(
+                "Reference Pitch",
+                A4_FREQ,
+                FloatRange::Linear {
+                    min: 400.0,
+                    max: 480.0,
+                },
+            )
+            .with_step_size(0.1)
+            .with_unit(" Hz"),
+            tunings: RwLock::default(),
+        }
+    }
+}
+
+impl ScaleParams {
+    /// Whether `pitch_class`, counting up from the root, is part of the current scale.
+    fn contains(&self, pitch_class: u8) -> bool {
+        let intervals: &[u8] = match self.scale.value() {
+            Scale::Chromatic => return true,
+            Scale::Major => &MAJOR,
+            Scale::Minor => &MINOR,
+            Scale::Dorian => &DORIAN,
+            Scale::Custom => return self.custom[usize::from(pitch_class)].enabled.value(),
+        };
+
+        intervals.contains(&pitch_class)
+    }
+
+    /// Move `note` to the closest note in the scale. Ties go down. Notes stay where they are if the
+    /// scale doesn't have any notes at all.
+    pub fn lock(&self, note: u8) -> u8 {
+        #[allow(clippy::cast_possible_truncation)]
+        let root = self.root.value().to_index() as u8;
+        let in_scale = |note: u8| self.contains((note + 12 - root) % 12);
+
+        (0..12)
+            .flat_map(|distance| [note.checked_sub(distance), note.checked_add(distance)])
+            .flatten()
+            .find(|&candidate| candidate <= 127 && in_scale(candidate))
+            .unwrap_or(note)
+    }
+
+    /// The frequency `note` plays at, after locking it to the scale and tuning it with the current
+    /// tuning slot.
+    pub fn frequency(&self, note: u8) -> f32 {
+        self.tuned_frequency(self.lock(note))
+    }
+
+    /// Parse a frequency typed in either as Hz, or as a note name like `A2` or `C#4+14c` tuned with
+    /// the current tuning slot. Note names aren't locked to the scale, since they're already
+    /// picked on purpose. C4 is middle C.
+    pub fn parse_frequency(&self, text: &str) -> Option<f32> {
+        let text = text.trim();
+        if let Some(hz) = text
+            .strip_suffix("Hz")
+            .or_else(|| text.strip_suffix("hz"))
+            .unwrap_or(text)
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|hz| hz.is_finite() && *hz > 0.0)
+        {
+            return Some(hz);
+        }
+
+        let (note, cents) = parse_note_name(text)?;
+        Some(self.tuned_frequency(note) * (cents / 1200.0).exp2())
+    }
+
+    /// The note closest to `frequency` with the current tuning slot, and how many cents `frequency`
+    /// is away from it. Like [`parse_frequency`][Self::parse_frequency], this ignores the scale.
+    pub fn nearest_note(&self, frequency: f32) -> (u8, f32) {
+        (0..=127)
+            .map(|note| {
+                (
+                    note,
+                    1200.0 * (frequency / self.tuned_frequency(note)).log2(),
+                )
+            })
+            .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+            .expect("there's always a closest note")
+    }
+
+    fn tuned_frequency(&self, note: u8) -> f32 {
+        let reference_pitch = self.reference_pitch.value();
+        let equal_tempered = || util::midi_note_to_freq(note) * reference_pitch / A4_FREQ;
+        // The editor only holds the lock while it's loading a tuning. Falling back to 12-TET for a
+        // moment is better than waiting on it here
+        let Ok(tunings) = self.tunings.try_read() else {
+            return equal_tempered();
+        };
+
+        #[allow(clippy::cast_sign_loss)]
+        tunings[self.tuning_slot.value() as usize - 1]
+            .as_ref()
+            .map_or_else(equal_tempered, |tuning| {
+                tuning.frequency(note, reference_pitch)
+            })
+    }
+}
+
+/// Split a note name into a MIDI note and an offset in cents. The offset is optional, and has to
+/// end in `c`.
+pub fn parse_note_name(text: &str) -> Option<(u8, f32)> {
+    let mut chars = text.chars();
+    let pitch_class: i32 = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, rest) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+
+    // The octave can be negative, so the cents start at the first sign after its first character
+    let cents_start = rest
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| matches!(c, '+' | '-'))
+        .map_or(rest.len(), |(idx, _)| idx);
+    let (octave, cents) = rest.split_at(cents_start);
+    let octave: i32 = octave.trim().parse().ok()?;
+    let cents = if cents.is_empty() {
+        0.0
+    } else {
+        cents
+            .trim()
+            .strip_suffix('c')?
+            .parse::<f32>()
+            .ok()
+            .filter(|cents| cents.is_finite())?
+    };
+
+    let note = (octave + 1) * 12 + pitch_class + accidental;
+    u8::try_from(note)
+        .ok()
+        .filter(|&note| note <= 127)
+        .map(|note| (note, cents))
+}
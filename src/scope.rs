@@ -0,0 +1,87 @@
+//! A running record of the input and output for the editor's oscilloscope. Both get summed to
+//! mono, and the editor picks where to trigger from.
+
+use nih_plug::prelude::*;
+use triple_buffer::TripleBuffer;
+
+/// How many samples of history the editor gets. This needs to be a good deal longer than what's
+/// shown, so there's room to look back for a trigger point.
+pub const SCOPE_LEN: usize = 4096;
+
+pub type ScopeOutput = triple_buffer::Output<Scope>;
+
+/// The last [`SCOPE_LEN`] samples, oldest first.
+#[derive(Clone)]
+pub struct Scope {
+    pub dry: Vec<f32>,
+    pub wet: Vec<f32>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            dry: vec![0.0; SCOPE_LEN],
+            wet: vec![0.0; SCOPE_LEN],
+        }
+    }
+}
+
+pub struct ScopeInput {
+    /// Ring buffers that get unrolled into the [`Scope`] when it's published.
+    dry: Vec<f32>,
+    wet: Vec<f32>,
+    /// Where the next buffer starts in the rings.
+    position: usize,
+    triple_buffer_input: triple_buffer::Input<Scope>,
+}
+
+impl ScopeInput {
+    /// Create a new scope input and output pair. The output should be shared with the editor.
+    pub fn new() -> (Self, ScopeOutput) {
+        let (triple_buffer_input, triple_buffer_output) =
+            TripleBuffer::new(&Scope::default()).split();
+
+        (
+            Self {
+                dry: vec![0.0; SCOPE_LEN],
+                wet: vec![0.0; SCOPE_LEN],
+                position: 0,
+                triple_buffer_input,
+            },
+            triple_buffer_output,
+        )
+    }
+
+    /// Record the input before it's processed. This has to be followed by
+    /// [`write_wet()`][Self::write_wet] with the same buffer once it is.
+    pub fn write_dry(&mut self, buffer: &Buffer) {
+        write_mono(&mut self.dry, self.position, buffer);
+    }
+
+    /// Record the processed output and send both to the editor.
+    pub fn write_wet(&mut self, buffer: &Buffer) {
+        write_mono(&mut self.wet, self.position, buffer);
+        self.position = (self.position + buffer.samples()) % SCOPE_LEN;
+
+        let scope = self.triple_buffer_input.input_buffer();
+        for (ring, unrolled) in [(&self.dry, &mut scope.dry), (&self.wet, &mut scope.wet)] {
+            let (newest, oldest) = ring.split_at(self.position);
+            unrolled[..oldest.len()].copy_from_slice(oldest);
+            unrolled[oldest.len()..].copy_from_slice(newest);
+        }
+        self.triple_buffer_input.publish();
+    }
+}
+
+fn write_mono(ring: &mut [f32], position: usize, buffer: &Buffer) {
+    let channels = buffer.as_slice_immutable();
+    #[allow(clippy::cast_precision_loss)]
+    let channel_weight = (channels.len() as f32).recip();
+    for sample_idx in 0..buffer.samples() {
+        ring[(position + sample_idx) % SCOPE_LEN] = channels
+            .iter()
+            .map(|channel| channel[sample_idx])
+            .sum::<f32>()
+            * channel_weight;
+    }
+}
@@ -0,0 +1,90 @@
+//! The stereo sample and filter types the DSP runs on. With the `nightly` feature these use
+//! `std::simd`, without it they fall back to plain pairs of `f32`s so the plugin builds on stable.
+
+use cozy_util::filter::svf::GenericSVF;
+
+#[cfg(feature = "nightly")]
+pub use std::simd::f32x2;
+
+/// A filter that processes both channels of a stereo sample.
+#[cfg(feature = "nightly")]
+pub type StereoFilter = GenericSVF<f32x2>;
+
+#[cfg(not(feature = "nightly"))]
+pub use scalar::{f32x2, StereoFilter};
+
+#[cfg(not(feature = "nightly"))]
+mod scalar {
+    use super::GenericSVF;
+    use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+    /// A stand-in for `std::simd::f32x2` with just the parts the plugin uses.
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Default, Debug, PartialEq)]
+    pub struct f32x2([f32; 2]);
+
+    impl f32x2 {
+        pub const fn from_array(array: [f32; 2]) -> Self {
+            Self(array)
+        }
+
+        pub const fn as_array(&self) -> &[f32; 2] {
+            &self.0
+        }
+
+        pub const fn splat(value: f32) -> Self {
+            Self([value; 2])
+        }
+    }
+
+    macro_rules! impl_op {
+        ($op:ident, $fn:ident, $op_assign:ident, $fn_assign:ident) => {
+            impl $op for f32x2 {
+                type Output = Self;
+
+                fn $fn(self, rhs: Self) -> Self {
+                    Self([self.0[0].$fn(rhs.0[0]), self.0[1].$fn(rhs.0[1])])
+                }
+            }
+
+            impl $op_assign for f32x2 {
+                fn $fn_assign(&mut self, rhs: Self) {
+                    *self = (*self).$fn(rhs);
+                }
+            }
+        };
+    }
+
+    impl_op!(Add, add, AddAssign, add_assign);
+    impl_op!(Sub, sub, SubAssign, sub_assign);
+    impl_op!(Mul, mul, MulAssign, mul_assign);
+
+    /// A filter that processes both channels of a stereo sample, one channel at a time.
+    #[derive(Clone, Copy, Default)]
+    pub struct StereoFilter([GenericSVF<f32>; 2]);
+
+    impl StereoFilter {
+        pub fn set_sample_rate(&mut self, sample_rate: f32) {
+            for filter in &mut self.0 {
+                filter.set_sample_rate(sample_rate);
+            }
+        }
+
+        pub fn set_bell(&mut self, frequency: f32, q: f32, gain: f32) {
+            for filter in &mut self.0 {
+                filter.set_bell(frequency, q, gain);
+            }
+        }
+
+        pub fn set_notch(&mut self, frequency: f32, q: f32) {
+            for filter in &mut self.0 {
+                filter.set_notch(frequency, q);
+            }
+        }
+
+        pub fn process(&mut self, sample: f32x2) -> f32x2 {
+            let [left, right] = &mut self.0;
+            f32x2([left.process(sample.0[0]), right.process(sample.0[1])])
+        }
+    }
+}
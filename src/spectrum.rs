@@ -5,21 +5,83 @@ use nih_plug::prelude::*;
 use nih_plug::util::window::multiply_with_window;
 use realfft::num_complex::Complex32;
 use realfft::{RealFftPlanner, RealToComplex};
+use serde::{Deserialize, Serialize};
 use std::f32;
 use std::sync::Arc;
+use strum_macros::Display;
 use triple_buffer::TripleBuffer;
 
-pub const SPECTRUM_WINDOW_SIZE: usize = 4096;
-// Don't need that much precision here
-const SPECTRUM_WINDOW_OVERLAP: usize = 2;
+/// The FFT sizes the analyzer can switch between. These all get planned up front so switching
+/// between them on the audio thread doesn't allocate.
+pub const SPECTRUM_WINDOW_SIZES: [usize; 4] = [1024, 2048, 4096, 8192];
+pub const MAX_SPECTRUM_WINDOW_SIZE: usize = 8192;
+pub const SPECTRUM_OVERLAPS: [usize; 3] = [2, 4, 8];
+/// How often the spectrum can be updated while the editor is closed, in ms. Zero turns these
+/// updates off.
+pub const SPECTRUM_BACKGROUND_INTERVALS: [u32; 5] = [0, 50, 200, 1000, 5000];
 
 /// The time it takes for the spectrum to go down 12 dB. The upwards step is immediate like in a
 /// peak meter.
 const SMOOTHING_DECAY_MS: f32 = 100.0;
 
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, Display)]
+pub enum SpectrumWindow {
+    #[default]
+    Hann,
+    #[strum(to_string = "Blackman-Harris")]
+    BlackmanHarris,
+}
+
+/// How the spectrum gets analyzed. This is picked in the editor and applied on the audio thread.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpectrumSettings {
+    pub window: SpectrumWindow,
+    pub window_size: usize,
+    pub overlap: usize,
+    /// Keeps the spectrum running at a low rate while the editor is closed, so it's already
+    /// filled in when the editor opens. See [`SPECTRUM_BACKGROUND_INTERVALS`]. Like the rest of
+    /// these, this comes from the editor's config, so it only kicks in once the editor has been
+    /// opened.
+    pub background_interval_ms: u32,
+}
+
+impl Default for SpectrumSettings {
+    fn default() -> Self {
+        Self {
+            window: SpectrumWindow::Hann,
+            window_size: 4096,
+            // Don't need that much precision here
+            overlap: 2,
+            background_interval_ms: 0,
+        }
+    }
+}
+
 /// The amplitudes of all frequency bins in a windowed FFT of Diopser's output. Also includes the DC
-/// offset bin which we don't draw, just to make this a bit less confusing.
-pub type Spectrum = [f32; SPECTRUM_WINDOW_SIZE / 2 + 1];
+/// offset bin which we don't draw, just to make this a bit less confusing. Only the first
+/// `num_bins` bins are used, which depends on the current window size.
+#[derive(Clone, Copy)]
+pub struct Spectrum {
+    bins: [f32; MAX_SPECTRUM_WINDOW_SIZE / 2 + 1],
+    num_bins: usize,
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self {
+            bins: [0.0; MAX_SPECTRUM_WINDOW_SIZE / 2 + 1],
+            num_bins: SpectrumSettings::default().window_size / 2 + 1,
+        }
+    }
+}
+
+impl Spectrum {
+    pub fn bins(&self) -> &[f32] {
+        &self.bins[..self.num_bins]
+    }
+}
+
 /// A receiver for a spectrum computed by [`SpectrumInput`].
 pub type SpectrumOutput = triple_buffer::Output<Spectrum>;
 
@@ -29,6 +91,8 @@ pub struct SpectrumInput {
     stft: util::StftHelper,
     /// The number of channels we're working on.
     num_channels: usize,
+    settings: SpectrumSettings,
+    sample_rate: f32,
 
     /// The spectrum behaves like a peak meter. If the new value is higher than the previous one, it
     /// jump up immediately. Otherwise the old value is multiplied by this weight and the new value
@@ -41,10 +105,12 @@ pub struct SpectrumInput {
     /// A scratch buffer to compute the resulting power amplitude spectrum.
     spectrum_result_buffer: Spectrum,
 
+    /// The FFT plans for every size in [`SPECTRUM_WINDOW_SIZES`].
+    plans: Vec<Arc<dyn RealToComplex<f32>>>,
     /// The algorithm for the FFT operation used for our spectrum analyzer.
     plan: Arc<dyn RealToComplex<f32>>,
-    /// A Hann window window, passed to the STFT helper. The gain compensation is already part of
-    /// this window to save a multiplication step.
+    /// The window function passed to the STFT helper, sized for the largest window. The gain
+    /// compensation is already part of this window to save a multiplication step.
     compensated_window_function: Vec<f32>,
     /// The output of our real->complex FFT.
     complex_fft_buffer: Vec<Complex32>,
@@ -54,39 +120,96 @@ impl SpectrumInput {
     /// Create a new spectrum input and output pair. The output should be moved to the editor.
     pub fn new(num_channels: usize) -> (Self, SpectrumOutput) {
         let (triple_buffer_input, triple_buffer_output) =
-            TripleBuffer::new(&[0.0; SPECTRUM_WINDOW_SIZE / 2 + 1]).split();
+            TripleBuffer::new(&Spectrum::default()).split();
+
+        let mut planner = RealFftPlanner::new();
+        let plans: Vec<_> = SPECTRUM_WINDOW_SIZES
+            .iter()
+            .map(|size| planner.plan_fft_forward(*size))
+            .collect();
 
-        let input = Self {
-            stft: util::StftHelper::new(num_channels, SPECTRUM_WINDOW_SIZE, 0),
+        let mut input = Self {
+            stft: util::StftHelper::new(num_channels, MAX_SPECTRUM_WINDOW_SIZE, 0),
             num_channels,
+            settings: SpectrumSettings::default(),
+            // This is set in `initialize()`
+            sample_rate: 1.0,
 
             // This is set in `initialize()` based on the sample rate
             smoothing_decay_weight: 0.0,
 
             triple_buffer_input,
-            spectrum_result_buffer: [0.0; SPECTRUM_WINDOW_SIZE / 2 + 1],
-
-            plan: RealFftPlanner::new().plan_fft_forward(SPECTRUM_WINDOW_SIZE),
-            #[allow(clippy::cast_precision_loss)]
-            compensated_window_function: util::window::hann(SPECTRUM_WINDOW_SIZE)
-                .into_iter()
-                // Include the gain compensation in the window function to save some multiplications
-                .map(|x| x / SPECTRUM_WINDOW_SIZE as f32)
-                .collect(),
-            complex_fft_buffer: vec![Complex32::default(); SPECTRUM_WINDOW_SIZE / 2 + 1],
+            spectrum_result_buffer: Spectrum::default(),
+
+            plan: plans[0].clone(),
+            plans,
+            compensated_window_function: vec![0.0; MAX_SPECTRUM_WINDOW_SIZE],
+            complex_fft_buffer: vec![Complex32::default(); MAX_SPECTRUM_WINDOW_SIZE / 2 + 1],
         };
+        input.reconfigure();
 
         (input, triple_buffer_output)
     }
 
     /// Update the smoothing using the specified sample rate. Called in `initialize()`.
     pub fn update_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.reconfigure();
+    }
+
+    /// Switch to new analysis settings. Does nothing if the settings haven't changed, and never
+    /// allocates.
+    pub fn set_settings(&mut self, settings: SpectrumSettings) {
+        if settings == self.settings {
+            return;
+        }
+
+        self.settings = settings;
+        self.reconfigure();
+    }
+
+    fn reconfigure(&mut self) {
+        // Settings come from a config file, so they can't be trusted to be something we planned for
+        let default = SpectrumSettings::default();
+        let plan_idx = SPECTRUM_WINDOW_SIZES
+            .iter()
+            .position(|size| *size == self.settings.window_size)
+            .unwrap_or_else(|| {
+                self.settings.window_size = default.window_size;
+                SPECTRUM_WINDOW_SIZES
+                    .iter()
+                    .position(|size| *size == default.window_size)
+                    .unwrap()
+            });
+        if !SPECTRUM_OVERLAPS.contains(&self.settings.overlap) {
+            self.settings.overlap = default.overlap;
+        }
+
+        let window_size = self.settings.window_size;
+        self.stft.set_block_size(window_size);
+        self.plan = self.plans[plan_idx].clone();
+
+        let window = &mut self.compensated_window_function[..window_size];
+        match self.settings.window {
+            SpectrumWindow::Hann => util::window::hann_in_place(window),
+            SpectrumWindow::BlackmanHarris => blackman_harris_in_place(window),
+        }
+        // Include the gain compensation in the window function to save some multiplications. This
+        // is normalized to the window's sum so switching windows doesn't change the levels.
+        let compensation = 0.5 / window.iter().sum::<f32>();
+        for sample in window {
+            *sample *= compensation;
+        }
+
+        self.spectrum_result_buffer.bins.fill(0.0);
+        self.spectrum_result_buffer.num_bins = window_size / 2 + 1;
+
         // We'll express the dacay rate in the time it takes for the moving average to drop by 12 dB
         // NOTE: The effective sample rate accounts for the STFT interval, **and** for the number of
         //       channels. We'll average both channels to mono-ish.
         #[allow(clippy::cast_precision_loss)]
-        let effective_sample_rate = sample_rate / SPECTRUM_WINDOW_SIZE as f32
-            * SPECTRUM_WINDOW_OVERLAP as f32
+        let effective_sample_rate = self.sample_rate / window_size as f32
+            * self.settings.overlap as f32
             * self.num_channels as f32;
         let decay_samples = f64::from(SMOOTHING_DECAY_MS / 1000.0 * effective_sample_rate);
 
@@ -98,16 +221,22 @@ impl SpectrumInput {
 
     /// Compute the spectrum for a buffer and send it to the corresponding output pair.
     pub fn compute(&mut self, buffer: &Buffer) {
+        let window_size = self.settings.window_size;
+        let num_bins = window_size / 2 + 1;
+
         self.stft.process_analyze_only(
             buffer,
-            SPECTRUM_WINDOW_OVERLAP,
+            self.settings.overlap,
             |_channel_idx, real_fft_scratch_buffer| {
-                multiply_with_window(real_fft_scratch_buffer, &self.compensated_window_function);
+                multiply_with_window(
+                    real_fft_scratch_buffer,
+                    &self.compensated_window_function[..window_size],
+                );
 
                 self.plan
                     .process_with_scratch(
                         real_fft_scratch_buffer,
-                        &mut self.complex_fft_buffer,
+                        &mut self.complex_fft_buffer[..num_bins],
                         // We don't actually need a scratch buffer
                         &mut [],
                     )
@@ -118,10 +247,9 @@ impl SpectrumInput {
                 // value immediately, lower values decay gradually. This also results in quasi-mono
                 // summing since this same callback will be called for both channels. Gain
                 // compensation has already been baked into the window function.
-                for (bin, spectrum_result) in self
-                    .complex_fft_buffer
+                for (bin, spectrum_result) in self.complex_fft_buffer[..num_bins]
                     .iter()
-                    .zip(&mut self.spectrum_result_buffer)
+                    .zip(&mut self.spectrum_result_buffer.bins)
                 {
                     let magnitude = bin.norm();
                     if magnitude > *spectrum_result {
@@ -139,3 +267,21 @@ impl SpectrumInput {
         );
     }
 }
+
+/// A 4-term Blackman-Harris window. Its sidelobes are a lot lower than Hann's, at the cost of a
+/// wider main lobe.
+fn blackman_harris_in_place(window: &mut [f32]) {
+    #[allow(clippy::cast_precision_loss)]
+    let scale = (window.len() as f32 - 1.0).recip() * f32::consts::TAU;
+    for (i, sample) in window.iter_mut().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let phase = i as f32 * scale;
+        *sample = 0.01168f32.mul_add(
+            -(3.0 * phase).cos(),
+            0.14128f32.mul_add(
+                (2.0 * phase).cos(),
+                0.48829f32.mul_add(-phase.cos(), 0.35875),
+            ),
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! A note the editor can play without any MIDI coming in, so the effect can be set up on a track
+//! before anything is routed to it. The notes get merged in with the host's, so they go through
+//! everything a real note would.
+
+use crate::{ProcessHost, TransportInfo};
+use nih_plug::prelude::*;
+
+/// The most test tone events that can land in a single buffer. Anything past this waits for the
+/// next one.
+const MAX_EVENTS: usize = 8;
+
+/// What the editor wants played. This is shared with the plugin, which does the timing itself so
+/// the notes don't follow the editor's frame rate.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TestToneSettings {
+    pub note: u8,
+    pub velocity: f32,
+    /// How long every note is held for.
+    pub length_ms: f32,
+    /// When set, a new note starts this often for as long as it stays set.
+    pub repeat_ms: Option<f32>,
+    /// Goes up by one every time the editor asks for a single note.
+    pub trigger: u32,
+}
+
+impl Default for TestToneSettings {
+    fn default() -> Self {
+        Self {
+            note: 48,
+            velocity: 0.8,
+            length_ms: 500.0,
+            repeat_ms: None,
+            trigger: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TestTone {
+    last_trigger: u32,
+    was_repeating: bool,
+    /// Samples since the last note started, if one has.
+    since_note_on: Option<usize>,
+    /// The note that's still held, if there is one.
+    sounding: Option<u8>,
+}
+
+impl TestTone {
+    /// Work out the test tone's events for the next `num_samples`, returning a host that plays
+    /// them along with `host`'s own.
+    pub fn wrap<'a, H: ProcessHost>(
+        &mut self,
+        host: &'a mut H,
+        settings: TestToneSettings,
+        num_samples: usize,
+        sample_rate: f32,
+    ) -> TestToneHost<'a, H> {
+        let mut events = [None; MAX_EVENTS];
+        let mut num_events = 0;
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let to_samples = |ms: f32| ((ms / 1000.0 * sample_rate) as usize).max(1);
+        let length = to_samples(settings.length_ms);
+        let interval = settings.repeat_ms.map(to_samples);
+
+        // Asking for a note, or turning repeat on, starts one straight away
+        let mut start_now =
+            settings.trigger != self.last_trigger || (interval.is_some() && !self.was_repeating);
+        self.last_trigger = settings.trigger;
+        self.was_repeating = interval.is_some();
+
+        let mut position = 0;
+        while num_events < MAX_EVENTS {
+            let since = self.since_note_on.unwrap_or(0);
+            let note_off_in = self.sounding.map(|_| length.saturating_sub(since));
+            let note_on_in = if start_now {
+                Some(0)
+            } else {
+                interval
+                    .zip(self.since_note_on)
+                    .map(|(interval, since)| interval.saturating_sub(since))
+            };
+
+            // A note that's due to end at the same time the next one starts ends first
+            let (wait, is_note_on) = match (note_off_in, note_on_in) {
+                (Some(off), Some(on)) if on < off => (on, true),
+                (Some(off), _) => (off, false),
+                (None, Some(on)) => (on, true),
+                (None, None) => break,
+            };
+            if position + wait >= num_samples {
+                break;
+            }
+
+            position += wait;
+            self.since_note_on = self.since_note_on.map(|since| since + wait);
+            #[allow(clippy::cast_possible_truncation)]
+            let timing = position as u32;
+            if let Some(note) = self.sounding.take() {
+                events[num_events] = Some(NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: 0,
+                    note,
+                    velocity: 0.0,
+                });
+                num_events += 1;
+            }
+            if is_note_on && num_events < MAX_EVENTS {
+                events[num_events] = Some(NoteEvent::NoteOn {
+                    timing,
+                    voice_id: None,
+                    channel: 0,
+                    note: settings.note,
+                    velocity: settings.velocity,
+                });
+                num_events += 1;
+                self.sounding = Some(settings.note);
+                self.since_note_on = Some(0);
+                start_now = false;
+            }
+        }
+        self.since_note_on = self
+            .since_note_on
+            .map(|since| since + num_samples - position);
+
+        TestToneHost {
+            host,
+            tone_events: events,
+            next_tone_event: 0,
+            next_host_event: None,
+        }
+    }
+}
+
+/// Hands out the host's events and the test tone's in order of their timing.
+pub struct TestToneHost<'a, H> {
+    host: &'a mut H,
+    /// Sorted by timing, with any unused slots at the end.
+    tone_events: [Option<NoteEvent<()>>; MAX_EVENTS],
+    next_tone_event: usize,
+    /// The host's next event, once it's been taken out to compare against the test tone's.
+    next_host_event: Option<Option<NoteEvent<()>>>,
+}
+
+impl<H: ProcessHost> ProcessHost for TestToneHost<'_, H> {
+    fn next_event(&mut self) -> Option<NoteEvent<()>> {
+        let host_event = *self
+            .next_host_event
+            .get_or_insert_with(|| self.host.next_event());
+        let tone_event = self
+            .tone_events
+            .get(self.next_tone_event)
+            .copied()
+            .flatten();
+
+        match (host_event, tone_event) {
+            (Some(host_event), Some(tone_event)) if tone_event.timing() < host_event.timing() => {
+                self.next_tone_event += 1;
+                Some(tone_event)
+            }
+            (Some(host_event), _) => {
+                self.next_host_event = None;
+                Some(host_event)
+            }
+            (None, tone_event) => {
+                self.next_tone_event += 1;
+                tone_event
+            }
+        }
+    }
+
+    fn send_event(&mut self, event: NoteEvent<()>) {
+        self.host.send_event(event);
+    }
+
+    fn transport(&self) -> TransportInfo {
+        self.host.transport()
+    }
+}
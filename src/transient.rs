@@ -0,0 +1,75 @@
+//! Finds hits in a signal by comparing a fast envelope follower to a slow one. A hit makes the fast
+//! one jump ahead of the slow one, while steady sounds keep the two together.
+
+use nih_plug::util;
+
+const FAST_ATTACK_MS: f32 = 0.5;
+const FAST_RELEASE_MS: f32 = 10.0;
+const SLOW_ATTACK_MS: f32 = 30.0;
+const SLOW_RELEASE_MS: f32 = 150.0;
+/// How far the fast follower has to get above the slow one to count as a hit.
+const SENSITIVITY_DB: f32 = 6.0;
+/// How long to wait after a hit before the next one can be found, so one hit doesn't get found
+/// several times over.
+const HOLDOFF_MS: f32 = 50.0;
+
+#[derive(Clone, Copy, Default)]
+pub struct TransientDetector {
+    fast: f32,
+    slow: f32,
+    holdoff_samples: u32,
+}
+
+impl TransientDetector {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Follow the next sample, returning whether a hit starts on it. Hits quieter than
+    /// `threshold`, in linear gain, are ignored.
+    pub fn process(&mut self, sample: f32, threshold: f32, sample_rate: f32) -> bool {
+        let level = sample.abs();
+        self.fast = follow(
+            self.fast,
+            level,
+            FAST_ATTACK_MS,
+            FAST_RELEASE_MS,
+            sample_rate,
+        );
+        self.slow = follow(
+            self.slow,
+            level,
+            SLOW_ATTACK_MS,
+            SLOW_RELEASE_MS,
+            sample_rate,
+        );
+
+        if self.holdoff_samples > 0 {
+            self.holdoff_samples -= 1;
+            return false;
+        }
+
+        let is_hit =
+            self.fast > threshold && self.fast > self.slow * util::db_to_gain(SENSITIVITY_DB);
+        if is_hit {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                self.holdoff_samples = (HOLDOFF_MS / 1000.0 * sample_rate) as u32;
+            }
+        }
+
+        is_hit
+    }
+}
+
+/// A one pole follower step, with separate times for rising and falling.
+fn follow(current: f32, target: f32, attack_ms: f32, release_ms: f32, sample_rate: f32) -> f32 {
+    let time_ms = if target > current {
+        attack_ms
+    } else {
+        release_ms
+    };
+    let coefficient = (-1000.0 / (time_ms * sample_rate)).exp();
+
+    coefficient.mul_add(current - target, target)
+}
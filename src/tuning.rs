@@ -0,0 +1,185 @@
+//! Tunings other than 12-TET, loaded from Scala scale files or MIDI Tuning Standard bulk dumps.
+//! Either way they end up as a frequency for every MIDI note.
+
+use nih_plug::util;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const NUM_TUNING_SLOTS: usize = 4;
+/// The reference pitch [`util::midi_note_to_freq`] assumes.
+pub const A4_FREQ: f32 = 440.0;
+
+/// Scala files don't come with a keyboard mapping here, so the first degree of the scale always
+/// lands on middle C, at its usual 12-TET frequency.
+const SCALA_REFERENCE_NOTE: u8 = 60;
+
+/// The size of a bulk tuning dump, from the opening `F0` to the closing `F7`.
+const MTS_BULK_DUMP_LEN: usize = 408;
+const MTS_NAME_LEN: usize = 16;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tuning {
+    pub name: String,
+    /// The description line from a Scala file. Bulk dumps don't have one.
+    #[serde(default)]
+    pub description: String,
+    /// How many notes the scale repeats after. Bulk dumps tune every note on their own, so they
+    /// count as all 128.
+    #[serde(default)]
+    pub num_notes: usize,
+    /// The frequency of every MIDI note, in Hz, with A4 at [`A4_FREQ`].
+    frequencies: Vec<f32>,
+    /// Bulk dumps give every note an exact frequency, so the reference pitch doesn't move them.
+    /// Tunings saved before this was added don't have it, see [`is_absolute`][Self::is_absolute].
+    #[serde(default)]
+    absolute: Option<bool>,
+}
+
+impl Tuning {
+    /// Load a `.syx` file as an MTS bulk dump, and anything else as a Scala file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("syx"))
+        {
+            let bytes = fs::read(path).map_err(|e| format!("Can't read tuning - {e:?}"))?;
+            let tuning = Self::from_mts(&bytes)?;
+            // Dumps don't have to be named
+            if tuning.name.is_empty() {
+                return Ok(Self { name, ..tuning });
+            }
+
+            Ok(tuning)
+        } else {
+            let text =
+                fs::read_to_string(path).map_err(|e| format!("Can't read tuning - {e:?}"))?;
+            Self::from_scala(&text, name)
+        }
+    }
+
+    /// Parse a Scala `.scl` file. After the description and the number of notes, every line is a
+    /// pitch above the first degree, either in cents if it has a period in it or as a ratio
+    /// otherwise. The last pitch is the interval the scale repeats at.
+    pub fn from_scala(text: &str, name: String) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.starts_with('!'));
+        // The description can be empty, so it's never checked
+        let description = lines.next().unwrap_or_default().trim().to_string();
+
+        let num_notes: usize = lines
+            .next()
+            .and_then(|line| line.split_whitespace().next()?.parse().ok())
+            .ok_or("Invalid tuning - missing the number of notes")?;
+        let pitches = lines
+            .take(num_notes)
+            .map(|line| {
+                let pitch = line.split_whitespace().next().unwrap_or_default();
+                parse_scala_pitch(pitch)
+                    .ok_or_else(|| format!("Invalid tuning - bad pitch {pitch:?}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some(&period) = pitches.last() else {
+            return Err("Invalid tuning - the scale doesn't have any notes".to_string());
+        };
+        if pitches.len() < num_notes {
+            return Err("Invalid tuning - the scale has fewer notes than it says".to_string());
+        }
+
+        let reference_freq = util::midi_note_to_freq(SCALA_REFERENCE_NOTE);
+        #[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+        let frequencies = (0..128)
+            .map(|note: i32| {
+                let steps = note - i32::from(SCALA_REFERENCE_NOTE);
+                let periods = steps.div_euclid(num_notes as i32);
+                let degree = steps.rem_euclid(num_notes as i32) as usize;
+                let ratio = if degree == 0 {
+                    1.0
+                } else {
+                    pitches[degree - 1]
+                };
+
+                reference_freq * period.powi(periods) * ratio
+            })
+            .collect();
+
+        Ok(Self {
+            name,
+            description,
+            num_notes,
+            frequencies,
+            absolute: Some(false),
+        })
+    }
+
+    /// Parse a MIDI Tuning Standard bulk dump. Every note's frequency is a semitone number and a
+    /// 14 bit fraction of a semitone above it. Notes set to `7F 7F 7F` keep their 12-TET tuning.
+    pub fn from_mts(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != MTS_BULK_DUMP_LEN {
+            return Err("Invalid tuning - the bulk dump is the wrong size".to_string());
+        }
+        let [0xF0, 0x7E, _device, 0x08, 0x01, _program, data @ .., _checksum, 0xF7] = bytes else {
+            return Err("Invalid tuning - not an MTS bulk dump".to_string());
+        };
+
+        let (name, notes) = data.split_at(MTS_NAME_LEN);
+        let name = String::from_utf8_lossy(name).trim().to_string();
+        let frequencies = notes
+            .chunks_exact(3)
+            .zip(0..)
+            .map(|(note, idx)| match *note {
+                [0x7F, 0x7F, 0x7F] => util::midi_note_to_freq(idx),
+                [semitone, msb, lsb] => {
+                    let fraction = f32::from(u16::from(msb) << 7 | u16::from(lsb)) / 16384.0;
+                    util::f32_midi_note_to_freq(f32::from(semitone) + fraction)
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+
+        Ok(Self {
+            name,
+            description: String::new(),
+            num_notes: 128,
+            frequencies,
+            absolute: Some(true),
+        })
+    }
+
+    /// The frequency of `note` with A4 at `reference_pitch`. Scala tunings start from middle C's
+    /// 12-TET frequency, so they move along with it.
+    pub fn frequency(&self, note: u8, reference_pitch: f32) -> f32 {
+        let frequency = self.frequencies[usize::from(note)];
+        if self.is_absolute() {
+            frequency
+        } else {
+            frequency * reference_pitch / A4_FREQ
+        }
+    }
+
+    /// Whether the reference pitch leaves this tuning alone. Older tunings are told apart by their
+    /// note count instead, since only bulk dumps have all 128.
+    fn is_absolute(&self) -> bool {
+        self.absolute.unwrap_or(self.num_notes == 128)
+    }
+}
+
+/// A pitch as a frequency ratio.
+fn parse_scala_pitch(pitch: &str) -> Option<f32> {
+    if pitch.contains('.') {
+        let cents: f32 = pitch.parse().ok()?;
+        return Some((cents / 1200.0).exp2());
+    }
+
+    let (numerator, denominator) = pitch.split_once('/').unwrap_or((pitch, "1"));
+    let numerator: u32 = numerator.parse().ok()?;
+    let denominator: u32 = denominator.parse().ok()?;
+
+    #[allow(clippy::cast_precision_loss)]
+    (numerator > 0 && denominator > 0).then(|| numerator as f32 / denominator as f32)
+}
@@ -0,0 +1,294 @@
+//! A fixed size pool of voices, with oldest-first voice stealing.
+
+/// What the pool needs to know about a voice to match it against note events and to pick which one
+/// to steal.
+pub trait PoolVoice {
+    fn id(&self) -> i32;
+    fn channel(&self) -> u8;
+    fn note(&self) -> u8;
+    /// Increases with every voice that gets started. The voice with the lowest value gets stolen
+    /// first.
+    fn start_order(&self) -> u64;
+
+    /// Whether a note event for this voice ID, or this channel and note, is meant for this voice.
+    fn matches(&self, voice_id: Option<i32>, channel: u8, note: u8) -> bool {
+        voice_id.is_some_and(|id| self.id() == id)
+            || (self.channel() == channel && self.note() == note)
+    }
+}
+
+pub struct VoicePool<V, const N: usize> {
+    slots: [Option<V>; N],
+}
+
+impl<V, const N: usize> Default for VoicePool<V, N> {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+        }
+    }
+}
+
+impl<V: PoolVoice, const N: usize> VoicePool<V, N> {
+    /// Every slot in the pool, including the empty ones. A voice keeps its slot for as long as it
+    /// lives.
+    pub const fn slots(&self) -> &[Option<V>; N] {
+        &self.slots
+    }
+
+    pub fn slots_mut(&mut self) -> &mut [Option<V>; N] {
+        &mut self.slots
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &V> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.slots.iter_mut().flatten()
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill_with(|| None);
+    }
+
+    /// Add a voice to one of the first `capacity` slots. If all of those are in use, the oldest
+    /// voice in them gets stolen and returned so the host can be told about it.
+    pub fn start(&mut self, voice: V, capacity: usize) -> (&mut V, Option<V>) {
+        let capacity = capacity.clamp(1, N);
+        let slot_idx = self.slots[..capacity]
+            .iter()
+            .position(Option::is_none)
+            .or_else(|| {
+                // `None` sorts before `Some`, so this would still pick a free slot if there was one
+                self.slots[..capacity]
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, slot)| slot.as_ref().map(PoolVoice::start_order))
+                    .map(|(idx, _)| idx)
+            })
+            .expect("capacity is at least one");
+
+        let stolen = self.slots[slot_idx].take();
+        (self.slots[slot_idx].insert(voice), stolen)
+    }
+
+    /// All voices a note event is meant for. If the event has a voice ID, this is at most one voice.
+    pub fn matching_mut(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) -> impl Iterator<Item = &mut V> {
+        self.iter_mut()
+            .filter(move |voice| voice.matches(voice_id, channel, note))
+            .take(voice_id.map_or(usize::MAX, |_| 1))
+    }
+
+    /// Remove the voices a note event is meant for, calling `on_removed` for each of them.
+    pub fn remove_matching(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        on_removed: impl FnMut(V),
+    ) {
+        self.slots
+            .iter_mut()
+            .filter(|slot| {
+                slot.as_ref()
+                    .is_some_and(|voice| voice.matches(voice_id, channel, note))
+            })
+            .take(voice_id.map_or(usize::MAX, |_| 1))
+            .filter_map(Option::take)
+            .for_each(on_removed);
+    }
+
+    /// Remove every voice `predicate` returns true for, calling `on_removed` for each of them.
+    pub fn remove_where(
+        &mut self,
+        mut predicate: impl FnMut(&V) -> bool,
+        on_removed: impl FnMut(V),
+    ) {
+        self.slots
+            .iter_mut()
+            .filter(|slot| slot.as_ref().is_some_and(&mut predicate))
+            .filter_map(Option::take)
+            .for_each(on_removed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolVoice, VoicePool};
+    use proptest::prelude::*;
+
+    const NUM_SLOTS: usize = 16;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct TestVoice {
+        id: i32,
+        channel: u8,
+        note: u8,
+        start_order: u64,
+        releasing: bool,
+    }
+
+    impl PoolVoice for TestVoice {
+        fn id(&self) -> i32 {
+            self.id
+        }
+
+        fn channel(&self) -> u8 {
+            self.channel
+        }
+
+        fn note(&self) -> u8 {
+            self.note
+        }
+
+        fn start_order(&self) -> u64 {
+            self.start_order
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum Event {
+        Start {
+            voice_id: Option<i32>,
+            channel: u8,
+            note: u8,
+            capacity: usize,
+        },
+        Release {
+            voice_id: Option<i32>,
+            channel: u8,
+            note: u8,
+        },
+        Choke {
+            voice_id: Option<i32>,
+            channel: u8,
+            note: u8,
+        },
+        Finish,
+    }
+
+    fn event() -> impl Strategy<Value = Event> {
+        // Small ranges so events actually hit the voices that are playing
+        let voice_id = proptest::option::of(0..8i32);
+        let channel = 0..2u8;
+        let note = 60..68u8;
+        prop_oneof![
+            3 => (voice_id.clone(), channel.clone(), note.clone(), 0..=NUM_SLOTS + 2).prop_map(
+                |(voice_id, channel, note, capacity)| Event::Start {
+                    voice_id,
+                    channel,
+                    note,
+                    capacity,
+                }
+            ),
+            2 => (voice_id.clone(), channel.clone(), note.clone())
+                .prop_map(|(voice_id, channel, note)| Event::Release { voice_id, channel, note }),
+            1 => (voice_id, channel, note)
+                .prop_map(|(voice_id, channel, note)| Event::Choke { voice_id, channel, note }),
+            1 => Just(Event::Finish),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn pool_stays_consistent(events in proptest::collection::vec(event(), 0..256)) {
+            let mut pool = VoicePool::<TestVoice, NUM_SLOTS>::default();
+            let mut next_start_order = 0;
+
+            for event in events {
+                let before: Vec<_> = pool.iter().copied().collect();
+
+                match event {
+                    Event::Start { voice_id, channel, note, capacity } => {
+                        let voice = TestVoice {
+                            id: voice_id.unwrap_or(-1),
+                            channel,
+                            note,
+                            start_order: next_start_order,
+                            releasing: false,
+                        };
+                        next_start_order += 1;
+
+                        let clamped = capacity.clamp(1, NUM_SLOTS);
+                        let had_free_slot = pool.slots()[..clamped].iter().any(Option::is_none);
+                        let oldest = pool.slots()[..clamped]
+                            .iter()
+                            .flatten()
+                            .map(|voice| voice.start_order)
+                            .min();
+
+                        let (started, stolen) = pool.start(voice, capacity);
+                        prop_assert_eq!(*started, voice);
+
+                        if had_free_slot {
+                            prop_assert!(stolen.is_none());
+                            prop_assert_eq!(pool.active_voices(), before.len() + 1);
+                        } else {
+                            let stolen = stolen.expect("a full pool has to steal");
+                            prop_assert_eq!(Some(stolen.start_order), oldest);
+                            prop_assert_eq!(pool.active_voices(), before.len());
+                        }
+
+                        let position = pool
+                            .slots()
+                            .iter()
+                            .position(|slot| *slot == Some(voice))
+                            .unwrap();
+                        prop_assert!(position < clamped);
+                    }
+                    Event::Release { voice_id, channel, note } => {
+                        let released = pool
+                            .matching_mut(voice_id, channel, note)
+                            .map(|voice| voice.releasing = true)
+                            .count();
+                        if voice_id.is_some() {
+                            prop_assert!(released <= 1);
+                        } else {
+                            prop_assert_eq!(
+                                released,
+                                before.iter().filter(|voice| voice.matches(None, channel, note)).count()
+                            );
+                        }
+                        prop_assert_eq!(pool.active_voices(), before.len());
+                    }
+                    Event::Choke { voice_id, channel, note } => {
+                        let mut removed = Vec::new();
+                        pool.remove_matching(voice_id, channel, note, |voice| removed.push(voice));
+
+                        prop_assert!(removed.iter().all(|voice| voice.matches(voice_id, channel, note)));
+                        prop_assert_eq!(pool.active_voices(), before.len() - removed.len());
+                        if voice_id.is_some() {
+                            prop_assert!(removed.len() <= 1);
+                        } else {
+                            prop_assert!(!pool.iter().any(|voice| voice.matches(None, channel, note)));
+                        }
+                    }
+                    Event::Finish => {
+                        let mut removed = Vec::new();
+                        pool.remove_where(|voice| voice.releasing, |voice| removed.push(voice));
+
+                        prop_assert!(removed.iter().all(|voice| voice.releasing));
+                        prop_assert!(!pool.iter().any(|voice| voice.releasing));
+                        prop_assert_eq!(pool.active_voices(), before.len() - removed.len());
+                    }
+                }
+
+                // Voices never get duplicated
+                let mut start_orders: Vec<_> = pool.iter().map(|voice| voice.start_order).collect();
+                start_orders.sort_unstable();
+                start_orders.dedup();
+                prop_assert_eq!(start_orders.len(), pool.active_voices());
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! A tiny pool of worker threads used to split voice processing across cores. Posting a job doesn't
+//! allocate or lock, and the thread posting it works on the job too. Workers that haven't woken up
+//! by the time it runs out of items get left out, so it never waits on a thread that isn't running.
+
+use std::cell::UnsafeCell;
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+type Job = *const (dyn Fn(usize) + Sync);
+
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+struct Shared {
+    /// Bumped every time a job is posted, so workers can tell real wakeups from spurious ones.
+    generation: AtomicUsize,
+    job: UnsafeCell<Option<Job>>,
+    num_items: AtomicUsize,
+    next_item: AtomicUsize,
+    /// Whether workers can still join the current job.
+    open: AtomicBool,
+    /// The number of workers that have joined the current job and haven't checked back in yet.
+    active_workers: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+// SAFETY: `job` is only written by `WorkerPool::execute` while the job is closed and no workers are
+//         active, and workers only read it after joining an open job.
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+impl WorkerPool {
+    pub fn new(num_workers: usize) -> Self {
+        let shared = Arc::new(Shared {
+            generation: AtomicUsize::new(0),
+            job: UnsafeCell::new(None),
+            num_items: AtomicUsize::new(0),
+            next_item: AtomicUsize::new(0),
+            open: AtomicBool::new(false),
+            active_workers: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let workers = (0..num_workers)
+            .map(|idx| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("scale colorizr worker {idx}"))
+                    .spawn(move || shared.work())
+                    .expect("couldn't spawn a worker thread")
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    /// Calls `job` once for every index in `0..num_items`, spread out over the workers and the
+    /// calling thread. Returns once every item has been processed. Once the calling thread runs out
+    /// of items it only waits for the ones workers are still in the middle of.
+    pub fn execute(&self, num_items: usize, job: &(dyn Fn(usize) + Sync)) {
+        // SAFETY: we don't return until the job is closed and every worker that joined it has
+        //         checked back in, so the job outlives every use of this pointer
+        #[allow(clippy::transmute_ptr_to_ptr)]
+        let job: Job =
+            unsafe { std::mem::transmute::<*const (dyn Fn(usize) + Sync + '_), Job>(job) };
+
+        // SAFETY: the last job was closed with no workers active, so nobody else is looking at the
+        //         job
+        unsafe {
+            *self.shared.job.get() = Some(job);
+        }
+        self.shared.num_items.store(num_items, Ordering::Relaxed);
+        self.shared.next_item.store(0, Ordering::Relaxed);
+        self.shared.generation.fetch_add(1, Ordering::Release);
+        self.shared.open.store(true, Ordering::SeqCst);
+
+        for worker in &self.workers {
+            worker.thread().unpark();
+        }
+
+        // SAFETY: see above
+        self.shared.run(unsafe { &*job });
+
+        // Every item has been picked up by now. Closing the job before looking at who's active
+        // means any worker that joins after this sees it closed, so this only waits on items that
+        // are already being worked on
+        self.shared.open.store(false, Ordering::SeqCst);
+        while self.shared.active_workers.load(Ordering::SeqCst) != 0 {
+            hint::spin_loop();
+        }
+
+        // SAFETY: the job is closed and every worker that joined it has checked back in
+        unsafe {
+            *self.shared.job.get() = None;
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        for worker in self.workers.drain(..) {
+            worker.thread().unpark();
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Shared {
+    fn work(&self) {
+        let mut last_generation = 0;
+        loop {
+            thread::park();
+            if self.shutdown.load(Ordering::Acquire) {
+                return;
+            }
+
+            let generation = self.generation.load(Ordering::Acquire);
+            if generation == last_generation {
+                continue;
+            }
+            last_generation = generation;
+
+            self.active_workers.fetch_add(1, Ordering::SeqCst);
+            // The job could have been closed, or even replaced by the next one, since we woke up
+            if self.open.load(Ordering::SeqCst)
+                && self.generation.load(Ordering::Acquire) == generation
+            {
+                // SAFETY: the job is set before it's opened, and isn't touched again until it's
+                //         closed and we've checked back in
+                if let Some(job) = unsafe { *self.job.get() } {
+                    self.run(unsafe { &*job });
+                }
+            }
+            self.active_workers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn run(&self, job: &(dyn Fn(usize) + Sync)) {
+        loop {
+            let item = self.next_item.fetch_add(1, Ordering::Relaxed);
+            if item >= self.num_items.load(Ordering::Relaxed) {
+                return;
+            }
+
+            job(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_item_runs_once() {
+        let pool = WorkerPool::new(3);
+        let counts: Vec<AtomicUsize> = (0..64).map(|_| AtomicUsize::new(0)).collect();
+        for _ in 0..1000 {
+            pool.execute(counts.len(), &|idx| {
+                counts[idx].fetch_add(1, Ordering::Relaxed);
+            });
+        }
+
+        assert!(counts
+            .iter()
+            .all(|count| count.load(Ordering::Relaxed) == 1000));
+    }
+}